@@ -1 +1,523 @@
-// create code, res fmtrs
+use mir::{MirProgram, MirFunc, MirStmnt, MirExpr, MirPlace, ReturnStrategy, CHAIN_LIMIT};
+use manager::{Manager, LABELS};
+use type_checker::Type;
+use util::{Error, ErrorCode, vec_fmt};
+
+/// the real variables, in the order that globals are handed them
+const NUMERIC_VARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZθ";
+/// packed strings store their length as the fractional part: start + length/ᴇ4
+const PACK_SCALE: &str = "ᴇ4";
+
+#[derive(Debug)]
+pub struct BuildFlags {
+    pub initialize_master_string: bool,
+    pub initialize_stack_frames: bool,
+    pub initialize_obj_mem: bool,
+    /// how many globals didn't fit into the real variables and spilled into ⌊GLB
+    pub spilled_globals: usize,
+}
+
+impl BuildFlags {
+    fn new() -> BuildFlags {
+        BuildFlags {
+            initialize_master_string: false,
+            initialize_stack_frames: false,
+            initialize_obj_mem: false,
+            spilled_globals: 0,
+        }
+    }
+}
+
+struct Builder<'a> {
+    m: &'a Manager,
+    mir: &'a MirProgram,
+    prgm: String,
+    build_flags: BuildFlags,
+    /// labels past the functions' and call sites' ones handed out so far
+    extra_labels: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn func_label(&self, func_id: usize) -> Result<&'static str, Error> {
+        LABELS.get(func_id)
+            .cloned()
+            .ok_or(Error::new_zero(ErrorCode::TooManyLabels, format!("exceeded label maximum (at maximum: {} functions and call sites)", LABELS.len())))
+    }
+    fn call_label(&self, call_site_id: usize) -> Result<&'static str, Error> {
+        LABELS.get(self.m.func_manager.count + call_site_id)
+            .cloned()
+            .ok_or(Error::new_zero(ErrorCode::TooManyLabels, format!("exceeded label maximum (at maximum: {} functions and call sites)", LABELS.len())))
+    }
+    fn fresh_label(&mut self) -> Result<&'static str, Error> {
+        self.extra_labels += 1;
+        LABELS.get(self.m.func_manager.count + self.m.func_manager.call_site_count + self.extra_labels - 1)
+            .cloned()
+            .ok_or(Error::new_zero(ErrorCode::TooManyLabels, format!("exceeded label maximum (at maximum: {} functions and call sites)", LABELS.len())))
+    }
+
+    /// the text that reads or writes this place
+    fn place(&mut self, place: MirPlace) -> String {
+        match place {
+            MirPlace::Global(index) => {
+                if let Some(var) = NUMERIC_VARS.chars().nth(index) {
+                    var.to_string()
+                } else {
+                    let spilled = index + 1 - NUMERIC_VARS.chars().count();
+                    self.build_flags.spilled_globals = self.build_flags.spilled_globals.max(spilled);
+                    format!("⌊GLB({})", spilled)
+                }
+            }
+            MirPlace::Local(offset) => format!("⌊ARGS(dim(⌊ARGS)-{})", offset),
+        }
+    }
+
+    /// a valid TI-84 Basic expression for this MIR expression
+    fn expr(&mut self, expr: &MirExpr) -> String {
+        match *expr {
+            MirExpr::Void => String::new(),
+            MirExpr::Num(ref text) => {
+                if let Some(magnitude) = text.strip_prefix('-') {
+                    // the calculator's negation sign is not its minus sign
+                    format!("(⁻{})", magnitude)
+                } else {
+                    text.clone()
+                }
+            }
+            MirExpr::Str(ref text) => format!("\"{}\"", text),
+            MirExpr::Read(place) => self.place(place),
+            MirExpr::ObjGet { ref base, offset } => {
+                self.build_flags.initialize_obj_mem = true;
+                let base = self.expr(base);
+                if offset == 0 {
+                    format!("⌊OBJ({})", base)
+                } else {
+                    format!("⌊OBJ({}+{})", base, offset)
+                }
+            }
+            MirExpr::Unpack(ref handle) => {
+                self.build_flags.initialize_master_string = true;
+                let handle = self.expr(handle);
+                format!("sub(Str0,iPart({h}),{scale}fPart({h}))", h = handle, scale = PACK_SCALE)
+            }
+            MirExpr::Fmt { ref fmt_str, ref handles } => {
+                let handles = handles.iter().map(|handle| self.expr(handle)).collect::<Vec<String>>();
+                vec_fmt(fmt_str, &handles)
+            }
+        }
+    }
+
+    fn stmnts(&mut self, stmnts: &[MirStmnt]) -> Result<(), Error> {
+        for stmnt in stmnts {
+            self.stmnt(stmnt)?;
+        }
+        Ok(())
+    }
+
+    fn stmnt(&mut self, stmnt: &MirStmnt) -> Result<(), Error> {
+        //WARN every line of code must end in a newline
+        match *stmnt {
+            MirStmnt::Malloc { at, ref width } => {
+                self.build_flags.initialize_obj_mem = true;
+                let at = self.place(at);
+                let width = self.expr(width);
+                self.prgm.push_str(&format!("dim(⌊OBJ)+1→{}\ndim(⌊OBJ)+{}→dim(⌊OBJ\n", at, width));
+            }
+            MirStmnt::WriteAtFixed { at, ref expr } => {
+                let at = self.place(at);
+                let expr = self.expr(expr);
+                // prevent spurious assignment, i.e. A→A
+                if expr != at && !expr.is_empty() {
+                    self.prgm.push_str(&format!("{}→{}\n", expr, at));
+                }
+            }
+            MirStmnt::Pack { at, ref expr } => {
+                self.build_flags.initialize_master_string = true;
+                let at = self.place(at);
+                let expr = self.expr(expr);
+                self.prgm.push_str(&format!("length(Str0)+1+length({e})/{scale}→{at}\nStr0+{e}→Str0\n", e = expr, at = at, scale = PACK_SCALE));
+            }
+            MirStmnt::ObjSet { ref base, offset, ref expr } => {
+                self.build_flags.initialize_obj_mem = true;
+                let base = self.expr(base);
+                let expr = self.expr(expr);
+                if offset == 0 {
+                    self.prgm.push_str(&format!("{}→⌊OBJ({})\n", expr, base));
+                } else {
+                    self.prgm.push_str(&format!("{}→⌊OBJ({}+{})\n", expr, base, offset));
+                }
+            }
+            MirStmnt::Exec { ref fmt_str, ref handles } => {
+                let handles = handles.iter().map(|handle| self.expr(handle)).collect::<Vec<String>>();
+                self.prgm.push_str(&vec_fmt(fmt_str, &handles));
+            }
+            MirStmnt::Format { at, utils, ref pieces } => {
+                self.build_flags.initialize_master_string = true;
+                let at = self.place(at);
+                let utils = (self.place(utils.0), self.place(utils.1));
+                self.prgm.push_str(&format!("length(Str0)+1→{}\n", at));
+                for (piece, piece_type) in pieces {
+                    let piece = self.expr(piece);
+                    build_stringification(self.m, &piece, piece_type, &utils, &mut self.prgm);
+                }
+                self.prgm.push_str(&format!("{at}+(length(Str0)+1-{at})/{scale}→{at}\n", at = at, scale = PACK_SCALE));
+            }
+            MirStmnt::InvokeFunc { call_site_id, func_id, ref args, result } => {
+                // we want to push onto ⌊ARGS: everything in the frame of the function plus our call_id
+                self.build_flags.initialize_stack_frames = true;
+                let frame_size = self.mir.frame_size(func_id).expect("calling a function without MIR");
+                self.prgm.push_str(&format!("{}→dim(⌊AUX\n", frame_size + 1));
+                // our call back identifier is on top of the stack
+                self.prgm.push_str(&format!("{}→⌊AUX({})\n", call_site_id, frame_size + 1));
+                for (index, arg) in args.iter().enumerate() {
+                    let arg = self.expr(arg);
+                    self.prgm.push_str(&format!("{}→⌊AUX({})\n", arg, frame_size - index));
+                }
+                self.prgm.push_str(&format!("augment(⌊ARGS,⌊AUX→⌊ARGS\nGoto {func_label}\nLbl {call_label}\n",
+                                            func_label = self.func_label(func_id)?,
+                                            call_label = self.call_label(call_site_id)?));
+                self.prgm.push_str(&format!("dim(⌊ARGS)-{}→dim(⌊ARGS\n", frame_size + 1));
+                if let Some(at) = result {
+                    // pop the top of the result stack into our place
+                    let at = self.place(at);
+                    self.prgm.push_str(&format!("⌊RES(dim(⌊RES))→{}\ndim(⌊RES)-1→dim(⌊RES\n", at));
+                }
+            }
+            MirStmnt::MakeClosure { at, func_id, ref captures } => {
+                self.build_flags.initialize_obj_mem = true;
+                let at = self.place(at);
+                self.prgm.push_str(&format!("dim(⌊OBJ)+1→{}\ndim(⌊OBJ)+{}→dim(⌊OBJ\n{}→⌊OBJ({})\n", at, captures.len() + 1, func_id, at));
+                for (index, capture) in captures.iter().enumerate() {
+                    let capture = self.expr(capture);
+                    self.prgm.push_str(&format!("{}→⌊OBJ({}+{})\n", capture, at, index + 1));
+                }
+            }
+            MirStmnt::Restart { func_id, ref predicate } => {
+                // a plain If guards the Goto, so no block is left open when it jumps
+                let predicate = self.expr(predicate);
+                self.prgm.push_str(&format!("If {}\nGoto {}\n", predicate, self.func_label(func_id)?));
+            }
+            MirStmnt::Free { ref base, width } => {
+                let base = self.expr(base);
+                if width == 1 {
+                    self.prgm.push_str(&format!("If dim(⌊OBJ)={0}\n{0}-1→dim(⌊OBJ\n", base));
+                } else {
+                    self.prgm.push_str(&format!("If dim(⌊OBJ)={0}+{1}\n{0}-1→dim(⌊OBJ\n", base, width - 1));
+                }
+            }
+            MirStmnt::InvokeValue { call_site_id, ref callee, ref typ, ref args, result } => {
+                self.build_flags.initialize_stack_frames = true;
+                self.build_flags.initialize_obj_mem = true;
+                let candidates = self.mir.closure_candidates(self.m, typ);
+                let frame_size = self.mir.value_frame_size(self.m, typ, args.len());
+                self.prgm.push_str(&format!("{}→dim(⌊AUX\n", frame_size + 1));
+                self.prgm.push_str(&format!("{}→⌊AUX({})\n", call_site_id, frame_size + 1));
+                for (index, arg) in args.iter().enumerate() {
+                    let arg = self.expr(arg);
+                    self.prgm.push_str(&format!("{}→⌊AUX({})\n", arg, frame_size - index));
+                }
+                let callee = self.expr(callee);
+                self.prgm.push_str(&format!("{}→⌊AUX({})\n", callee, frame_size - args.len()));
+                self.prgm.push_str("augment(⌊ARGS,⌊AUX→⌊ARGS\n");
+                // the first word of the record says which function to jump to
+                for (index, &func_id) in candidates.iter().enumerate() {
+                    if index + 1 < candidates.len() {
+                        self.prgm.push_str(&format!("If ⌊OBJ(⌊ARGS(dim(⌊ARGS)-{}))={}\n", args.len() + 1, func_id));
+                    }
+                    self.prgm.push_str(&format!("Goto {}\n", self.func_label(func_id)?));
+                }
+                self.prgm.push_str(&format!("Lbl {}\n", self.call_label(call_site_id)?));
+                self.prgm.push_str(&format!("dim(⌊ARGS)-{}→dim(⌊ARGS\n", frame_size + 1));
+                if let Some(at) = result {
+                    let at = self.place(at);
+                    self.prgm.push_str(&format!("⌊RES(dim(⌊RES))→{}\ndim(⌊RES)-1→dim(⌊RES\n", at));
+                }
+            }
+            MirStmnt::IfBlock { ref predicate, ref if_stmnts, ref else_stmnts } => {
+                if if_stmnts.is_empty() && else_stmnts.is_empty() {
+                    return Ok(());
+                }
+                let predicate = self.expr(predicate);
+                if if_stmnts.is_empty() {
+                    self.prgm.push_str(&format!("If not({})\nThen\n", predicate));
+                    self.stmnts(else_stmnts)?;
+                } else {
+                    self.prgm.push_str(&format!("If {}\nThen\n", predicate));
+                    self.stmnts(if_stmnts)?;
+                    if !else_stmnts.is_empty() {
+                        self.prgm.push_str("Else\n");
+                        self.stmnts(else_stmnts)?;
+                    }
+                }
+                self.prgm.push_str("End\n");
+            }
+            MirStmnt::WhileBlock { ref predicate_stmnts, ref predicate, ref body } => {
+                let predicate = self.expr(predicate);
+                if predicate_stmnts.is_empty() {
+                    self.prgm.push_str(&format!("While {}\n", predicate));
+                    self.stmnts(body)?;
+                    self.prgm.push_str("End\n");
+                } else {
+                    // the predicate's statements may hold labels, so they can't be copied before the loop
+                    // instead check the saved predicate at the bottom of a Repeat
+                    self.prgm.push_str(&format!("Repeat not({})\n", predicate));
+                    self.stmnts(predicate_stmnts)?;
+                    self.prgm.push_str(&format!("If {}\nThen\n", predicate));
+                    self.stmnts(body)?;
+                    self.prgm.push_str("End\nEnd\n");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///append the goto + function body code to the code of the program
+    fn build_func(&mut self, func: &MirFunc) -> Result<(), Error> {
+        let label = self.func_label(func.func_id)?;
+        self.prgm.push_str(&format!("Lbl {}\n", label));
+        self.stmnts(&func.body)?;
+        if !self.m.func_manager.out_type[func.func_id].unwrap().is_void() {
+            // push the result to the result stack
+            let result = self.expr(&func.result);
+            self.prgm.push_str(&format!("{}→⌊RES(dim(⌊RES)+1)\n", result));
+        }
+        // jump back to whoever called us
+        match func.returns {
+            ReturnStrategy::Unused => {}
+            ReturnStrategy::Direct(call_site_id) => self.prgm.push_str(&format!("Goto {}\n", self.call_label(call_site_id)?)),
+            ReturnStrategy::Chain(ref call_sites) | ReturnStrategy::BinarySearch(ref call_sites) => self.return_to(call_sites)?,
+        }
+        Ok(())
+    }
+
+    /// jump to whichever of these call sites is on top of ⌊ARGS, splitting them in half while there are too many to check in turn
+    fn return_to(&mut self, call_sites: &[usize]) -> Result<(), Error> {
+        if call_sites.len() > CHAIN_LIMIT {
+            let (low, high) = call_sites.split_at(call_sites.len() / 2);
+            let label = self.fresh_label()?;
+            self.prgm.push_str(&format!("If ⌊ARGS(dim(⌊ARGS))≥{}\nGoto {}\n", high[0], label));
+            self.return_to(low)?;
+            self.prgm.push_str(&format!("Lbl {}\n", label));
+            return self.return_to(high);
+        }
+        for (index, &call_site_id) in call_sites.iter().enumerate() {
+            if index + 1 < call_sites.len() {
+                self.prgm.push_str(&format!("If ⌊ARGS(dim(⌊ARGS))={}\n", call_site_id));
+            }
+            self.prgm.push_str(&format!("Goto {}\n", self.call_label(call_site_id)?));
+        }
+        Ok(())
+    }
+
+    ///get the initializations that might be called for
+    fn build_header(&self) -> String {
+        let mut header = String::new();
+        if self.build_flags.initialize_master_string {
+            header.push_str("\" \"→Str0\n");
+        }
+        if self.build_flags.initialize_stack_frames {
+            header.push_str("{0}→⌊RES\n{0}→⌊ARGS\n");
+        }
+        if self.build_flags.initialize_obj_mem {
+            header.push_str("{0}→⌊OBJ\n");
+        }
+        if self.build_flags.spilled_globals > 0 {
+            header.push_str(&format!("{}→dim(⌊GLB\n", self.build_flags.spilled_globals));
+        }
+        header
+    }
+}
+
+///append the code needed to convert a given value to a String
+/// the program will add the stringified version onto the end of Str0
+fn build_stringification(m: &Manager, target: &str, arg_type: &Type, utils: &(String, String), prgm: &mut String) {
+    let (ref util, ref power) = *utils;
+    match *arg_type {
+        Type::String => {
+            prgm.push_str(&format!("Str0+{}→Str0\n", target));
+        },
+        Type::Void => {
+            prgm.push_str("Str0+\"void\"→Str0\n");
+        },
+        Type::Boole => {
+            prgm.push_str(&format!(
+                "\
+If {target}
+Then
+Str0+\"true\"→Str0
+Else
+Str0+\"false\"→Str0
+End
+",
+                target = target));
+        },
+        Type::Int | Type::Real => {
+            // write out the integer part one digit at a time, from the largest power of ten down
+            prgm.push_str(&format!(
+                "\
+If {target}<0
+Str0+\"-\"→Str0
+abs({target})→{util}
+1→{power}
+While 10{power}≤{util}
+10{power}→{power}
+End
+While {power}≥1
+Str0+sub(\"0123456789\",iPart({util}/{power})+1,1)→Str0
+{util}-{power}iPart({util}/{power})→{util}
+{power}/10→{power}
+End
+",
+                target = target, util = util, power = power));
+            if let Type::Real = *arg_type {
+                // what's left over in util is the fractional part
+                prgm.push_str(&format!(
+                    "\
+Str0+\".\"→Str0
+If {util}=0
+Str0+\"0\"→Str0
+While {util}>0
+Str0+sub(\"0123456789\",iPart(10{util})+1,1)→Str0
+fPart(10{util})→{util}
+End
+",
+                    util = util));
+            }
+        },
+        // compound types are harder
+        Type::List(_) => unreachable!("type checking refuses to format lists"),
+        Type::CustomType(ref name, id) => {
+            //TODO if this type has an to-string function, we should call it
+            let kind = if m.udt_manager.is_enum(id) { "Enum" } else { "Struct" };
+            prgm.push_str(&format!("Str0+\"<{} {}>\"→Str0\n", kind, name));
+        }
+        Type::Func(..) => {
+            prgm.push_str("Str0+\"<Func>\"→Str0\n");
+        }
+        // only the instances of generic functions are compiled, and their types have had the parameters put in
+        Type::Generic(_) => unreachable!("stringifying a type parameter, which only generic functions have and those are only compiled as their instances"),
+    }
+}
+
+/// generate the TI-84 Basic source for a whole program
+pub fn build_program(m: &Manager, mir: &MirProgram) -> Result<String, Error> {
+    let mut builder = Builder {
+        m,
+        mir,
+        prgm: String::new(),
+        build_flags: BuildFlags::new(),
+        extra_labels: 0,
+    };
+    builder.stmnts(&mir.main)?;
+    let result = builder.expr(&mir.result);
+    if !result.is_empty() {
+        builder.prgm.push_str(&result);
+        builder.prgm.push('\n');
+    }
+
+    if !mir.funcs.is_empty() {
+        builder.prgm.push_str("Return\n");
+        for func in mir.funcs.iter() {
+            builder.build_func(func)?;
+        }
+    }
+
+    let mut text = builder.build_header();
+    text.push_str(&builder.prgm);
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {tokenizer, parser, scoping, type_checker, mir};
+
+    /// `inc` called from `sites` places, the last of them through a function value when `through_value` is set.
+    /// it is never inlined, so it has to find its way back to each of them
+    fn calls_to_inc(sites: usize, through_value: bool) -> String {
+        let mut source = "(func inc x: int -> int (add x 1))\n".to_string();
+        for site in 0..sites {
+            if through_value && site + 1 == sites {
+                source.push_str(&format!("(declare f inc (print (f {})))\n", site));
+            } else {
+                source.push_str(&format!("(print (inc {}))\n", site));
+            }
+        }
+        source
+    }
+
+    /// how `inc` returns, and the code that does it: everything after the result is pushed onto ⌊RES
+    fn returns_of_inc(source: String) -> (ReturnStrategy, String) {
+        let mut m = Manager::new(source);
+        let tokens = tokenizer::tokenize(&m.source).expect("should lex");
+        parser::generate_global_sexprs(&mut m, tokens).expect("should parse");
+        m.initialize_type_info().expect("should have its types");
+        scoping::create_all_scopes(&mut m).expect("should scope");
+        type_checker::type_check_all(&mut m).expect("should type check");
+        let mir = mir::make_all_mir(&mut m, 0).unwrap();
+        let text = build_program(&m, &mir).unwrap();
+        let returns = mir.funcs.iter().find(|func| m.func_manager.name[func.func_id] == "inc").expect("inc was inlined").returns.clone();
+        let pushed = "→⌊RES(dim(⌊RES)+1)\n";
+        (returns, text[text.find(pushed).expect("inc never returns a value") + pushed.len()..].to_string())
+    }
+
+    fn count_lines(text: &str, start: &str) -> usize {
+        text.lines().filter(|line| line.starts_with(start)).count()
+    }
+
+    #[test]
+    fn no_call_sites_never_return() {
+        let (returns, code) = returns_of_inc(calls_to_inc(0, false));
+        assert!(matches!(returns, ReturnStrategy::Unused), "{:?}", returns);
+        assert_eq!(count_lines(&code, "Goto "), 0);
+    }
+
+    #[test]
+    fn one_call_site_goes_straight_back() {
+        let (returns, code) = returns_of_inc(calls_to_inc(1, true));
+        assert!(matches!(returns, ReturnStrategy::Direct(_)), "{:?}", returns);
+        assert_eq!(count_lines(&code, "Goto "), 1);
+        assert_eq!(count_lines(&code, "If "), 0);
+    }
+
+    #[test]
+    fn a_few_call_sites_are_checked_in_turn() {
+        let (returns, code) = returns_of_inc(calls_to_inc(3, false));
+        assert!(matches!(returns, ReturnStrategy::Chain(ref sites) if sites.len() == 3), "{:?}", returns);
+        assert_eq!(count_lines(&code, "Goto "), 3);
+        assert_eq!(count_lines(&code, "If ⌊ARGS(dim(⌊ARGS))="), 2);
+        assert_eq!(count_lines(&code, "Lbl "), 0);
+    }
+
+    #[test]
+    fn more_call_sites_than_the_chain_limit_are_searched() {
+        let (returns, code) = returns_of_inc(calls_to_inc(CHAIN_LIMIT + 1, false));
+        assert!(matches!(returns, ReturnStrategy::BinarySearch(ref sites) if sites.len() == 5), "{:?}", returns);
+        // sites 0 and 1 below the split, 2, 3 and 4 above it, each going back to the label after their call.
+        // inc is labelled A and the call sites B to F, so the split gets the next label
+        assert_eq!(code, "\
+If ⌊ARGS(dim(⌊ARGS))≥2
+Goto G
+If ⌊ARGS(dim(⌊ARGS))=0
+Goto B
+Goto C
+Lbl G
+If ⌊ARGS(dim(⌊ARGS))=2
+Goto D
+If ⌊ARGS(dim(⌊ARGS))=3
+Goto E
+Goto F
+");
+    }
+
+    #[test]
+    fn the_search_splits_until_the_halves_can_be_chained() {
+        let (returns, code) = returns_of_inc(calls_to_inc(9, false));
+        assert!(matches!(returns, ReturnStrategy::BinarySearch(ref sites) if sites.len() == 9), "{:?}", returns);
+        // 9 splits into 4, which is chained, and 5, which splits again into 2 and 3
+        assert_eq!(count_lines(&code, "If ⌊ARGS(dim(⌊ARGS))≥"), 2);
+        assert_eq!(count_lines(&code, "Lbl "), 2);
+        assert_eq!(count_lines(&code, "If ⌊ARGS(dim(⌊ARGS))="), 3 + 1 + 2);
+        assert_eq!(count_lines(&code, "Goto "), 2 + 9);
+        assert!(code.starts_with("If ⌊ARGS(dim(⌊ARGS))≥4\n"), "{}", code);
+    }
+}
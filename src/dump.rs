@@ -0,0 +1,280 @@
+//! readable forms of what each stage of the compiler produces, for looking inside of it
+
+use manager::Manager;
+use sexpr::{SexprId, SexprKind, MapTarget};
+use scope::ScopeId;
+use tokenizer::Token;
+use type_checker::FutureType;
+use mir::{MirProgram, MirStmnt};
+use formatter::{Tree, render_all};
+use util::vec_fmt;
+
+fn atom(text: &str) -> Tree {
+    Tree::Atom(text.to_string())
+}
+
+/// names with their types, laid out the way definitions write them: `name: type`
+fn typed_names(names: &[String], types: &[FutureType]) -> Vec<Tree> {
+    names.iter()
+        .zip(types.iter())
+        .flat_map(|(name, typ)| vec![atom(name), atom(":"), atom(&typ.unwrap().to_string())])
+        .collect()
+}
+
+impl Manager {
+    /// the statements of a block, which declare, while and func write inline
+    fn statement_trees(&self, block: SexprId) -> Vec<Tree> {
+        match *self.kind(block) {
+            SexprKind::Block { ref statements } => statements.iter().map(|&statement| self.ast_tree(statement)).collect(),
+            _ => vec![self.ast_tree(block)],
+        }
+    }
+    /// an s-expr and its children as they stand after type checking
+    fn ast_tree(&self, sexpr_id: SexprId) -> Tree {
+        let call = |head: &str, exprs: &mut dyn Iterator<Item = &SexprId>| {
+            Tree::List(Some(atom(head)).into_iter().chain(exprs.map(|&expr| self.ast_tree(expr))).collect())
+        };
+        let mut items = vec![];
+        match *self.kind(sexpr_id) {
+            SexprKind::Declare { ref variable_pattern, ref annotation, expr, body } => {
+                items.extend(vec![atom("declare"), atom(variable_pattern)]);
+                if let Some(annotation) = annotation {
+                    items.extend(vec![atom(":"), atom(&annotation.unwrap().to_string())]);
+                }
+                items.push(self.ast_tree(expr));
+                items.extend(self.statement_trees(body));
+            }
+            SexprKind::Assign { ref variable_pattern, expr } => {
+                items.extend(vec![atom("assign"), atom(variable_pattern), self.ast_tree(expr)]);
+            }
+            SexprKind::IfSwitch { predicate, if_branch, else_branch } => {
+                items.extend(vec![atom("if"), self.ast_tree(predicate), self.ast_tree(if_branch), self.ast_tree(else_branch)]);
+            }
+            SexprKind::WhileLoop { predicate, body } => {
+                items.extend(vec![atom("while"), self.ast_tree(predicate)]);
+                items.extend(self.statement_trees(body));
+            }
+            SexprKind::Block { ref statements } => return call("block", &mut statements.iter()),
+            SexprKind::FuncDef { func_id } => {
+                items.extend(vec![atom("func"), atom(&self.func_manager.title(func_id))]);
+                items.extend(typed_names(&self.func_manager.args[func_id], &self.func_manager.in_types[func_id]));
+                items.extend(vec![atom("->"), atom(&self.func_manager.out_type[func_id].unwrap().to_string())]);
+                items.extend(self.statement_trees(self.func_manager.body[func_id]));
+            }
+            SexprKind::Lambda { func_id } => {
+                items.push(atom("lambda"));
+                items.extend(typed_names(&self.func_manager.args[func_id], &self.func_manager.in_types[func_id]));
+                items.extend(vec![atom("->"), atom(&self.func_manager.out_type[func_id].unwrap().to_string())]);
+                items.extend(self.statement_trees(self.func_manager.body[func_id]));
+            }
+            SexprKind::FuncCall { func_id, ref exprs, .. } => return call(&self.func_manager.name[func_id], &mut exprs.iter()),
+            SexprKind::FuncRef { func_id } => return atom(&self.func_manager.title(func_id)),
+            SexprKind::ValueCall { ref exprs, .. } => return call(self.text(sexpr_id), &mut exprs.iter()),
+            SexprKind::StructDef { id } => {
+                items.extend(vec![atom("struct"), atom(&self.udt_manager.name[id])]);
+                items.extend(typed_names(&self.udt_manager.args[id], &self.udt_manager.sgntr[id]));
+            }
+            SexprKind::StructInit { id, ref exprs } => return call(&self.udt_manager.name[id], &mut exprs.iter()),
+            SexprKind::EnumDef { id } => {
+                items.extend(vec![atom("enum"), atom(&self.udt_manager.name[id])]);
+                for variant in self.udt_manager.variants[id].iter() {
+                    let mut fields = vec![atom(&variant.name)];
+                    fields.extend(typed_names(&variant.args, &variant.sgntr));
+                    items.push(Tree::List(fields));
+                }
+            }
+            SexprKind::EnumInit { id, variant, ref exprs } => return call(&self.udt_manager.variants[id][variant].name, &mut exprs.iter()),
+            SexprKind::Match { expr, ref arms, .. } => {
+                items.extend(vec![atom("match"), self.ast_tree(expr)]);
+                for arm in arms {
+                    items.push(match arm.variant {
+                        Some(ref variant) => Tree::List(Some(variant).into_iter().chain(arm.bindings.iter()).map(|name| atom(name)).collect()),
+                        None => atom("_"),
+                    });
+                    items.push(self.ast_tree(arm.body));
+                }
+            }
+            SexprKind::StructGet { expr, ref field, .. } => {
+                items.extend(vec![atom("get-field"), self.ast_tree(expr), atom(field)]);
+            }
+            SexprKind::StructSet { expr, ref field, value, .. } => {
+                items.extend(vec![atom("set-field"), self.ast_tree(expr), atom(field), self.ast_tree(value)]);
+            }
+            SexprKind::List { ref elements } => return call("list", &mut elements.iter()),
+            SexprKind::ListGet { list, index } => return call("list-get", &mut [list, index].iter()),
+            SexprKind::ListSet { list, index, elem } => return call("list-set", &mut [list, index, elem].iter()),
+            SexprKind::ListLen { list } => return call("list-len", &mut [list].iter()),
+            SexprKind::ListPush { list, elem } => return call("list-push", &mut [list, elem].iter()),
+            SexprKind::ListMap { func, target, list } => {
+                let func = match target {
+                    Some(MapTarget::Func { func_id, .. }) => atom(&self.func_manager.title(func_id)),
+                    _ => self.ast_tree(func),
+                };
+                items.extend(vec![atom("list-map"), func, self.ast_tree(list)]);
+            }
+            SexprKind::Format { ref exprs } => return call("format", &mut exprs.iter()),
+            SexprKind::BuiltIn { ref exprs, .. } => return call(self.text(sexpr_id), &mut exprs.iter()),
+            SexprKind::Other { ref opt_exprs } => return call(self.text(sexpr_id), &mut opt_exprs.iter().flatten()),
+            SexprKind::StringLiteral | SexprKind::IntegerLiteral | SexprKind::RealLiteral
+                | SexprKind::BooleLiteral | SexprKind::Identifier => return atom(self.text(sexpr_id)),
+        }
+        Tree::List(items)
+    }
+}
+
+/// the program's s-exprs re-printed as s-expressions
+pub fn dump_ast(m: &Manager) -> String {
+    let trees = m.top_level_sexprs
+        .iter()
+        .map(|&sexpr_id| (m.ast_tree(sexpr_id), false))
+        .collect::<Vec<(Tree, bool)>>();
+    render_all(&trees)
+}
+
+
+/// every token with where it is and what it says
+pub fn dump_tokens(source: &str, tokens: &[Token]) -> String {
+    tokens.iter()
+        .map(|token| {
+            let (line, col) = token.position(source);
+            format!("{:>4}:{:<3} {:>5}+{:<3} {:<8} {}\n", line, col, token.begin(), token.len(), format!("{:?}", token.kind), token.get_text(source))
+        })
+        .collect()
+}
+
+fn dump_scope(m: &Manager, scope_id: ScopeId, depth: usize, out: &mut String) {
+    let scope = m.scope(scope_id);
+    let indent = "    ".repeat(depth);
+    out.push_str(&format!("{}scope {}{}\n", indent, scope_id.index, if scope.is_func_def { " (function)" } else { "" }));
+    let mut variables = scope.declared_variables.iter().collect::<Vec<_>>();
+    variables.sort_by_key(|&(_, &var_id)| var_id);
+    for (name, &var_id) in variables {
+        let typ = scope.variable_types[var_id].as_ref().map_or("unknown".to_string(), |typ| typ.to_string());
+        out.push_str(&format!("{}    {}: {}\n", indent, name, typ));
+    }
+    for &func_id in scope.declared_functions.iter() {
+        // the variables from around it that it gets a copy of
+        let captures = m.func_manager.captures[func_id]
+            .iter()
+            .map(|&(scope_index, var_id)| {
+                let captured = m.scope(ScopeId::from(scope_index));
+                captured.declared_variables.iter().find(|&(_, &id)| id == var_id).map_or("?".to_string(), |(name, _)| name.clone())
+            })
+            .collect::<Vec<String>>();
+        if captures.is_empty() {
+            out.push_str(&format!("{}    func {}\n", indent, m.func_manager.title(func_id)));
+        } else {
+            out.push_str(&format!("{}    func {} (captures {})\n", indent, m.func_manager.title(func_id), captures.join(", ")));
+        }
+    }
+    for &struct_id in scope.declared_structs.iter() {
+        out.push_str(&format!("{}    struct {}\n", indent, m.udt_manager.name[struct_id]));
+    }
+    for &enum_id in scope.declared_enums.iter() {
+        out.push_str(&format!("{}    enum {}\n", indent, m.udt_manager.name[enum_id]));
+    }
+    for &child in scope.children.iter() {
+        dump_scope(m, child, depth + 1, out);
+    }
+}
+
+/// every scope with what is bound in it, children nested under their parents
+pub fn dump_scopes(m: &Manager) -> String {
+    let mut out = String::new();
+    // the global scope and function scopes have no parent
+    for index in (0..m.all_scopes.len()).filter(|&index| m.all_scopes[index].parent.is_none()) {
+        dump_scope(m, index.into(), 0, &mut out);
+    }
+    out
+}
+
+fn dump_type(m: &Manager, sexpr_id: SexprId, depth: usize, out: &mut String) {
+    const SUMMARY_LEN: usize = 48;
+    let mut summary = m.ast_tree(sexpr_id).flat();
+    if summary.chars().count() > SUMMARY_LEN {
+        summary = format!("{}...", summary.chars().take(SUMMARY_LEN - 3).collect::<String>());
+    }
+    out.push_str(&format!("{}{} : {}\n", "    ".repeat(depth), summary, m.result_type(sexpr_id)));
+    for child in m.children(sexpr_id) {
+        dump_type(m, child, depth + 1, out);
+    }
+}
+
+/// the type of every s-expr, nested the way the s-exprs are
+pub fn dump_types(m: &Manager) -> String {
+    let mut out = String::new();
+    for &sexpr_id in m.top_level_sexprs.iter() {
+        dump_type(m, sexpr_id, 0, &mut out);
+    }
+    out
+}
+
+fn dump_stmnts(m: &Manager, stmnts: &[MirStmnt], depth: usize, out: &mut String) {
+    let indent = "    ".repeat(depth);
+    for stmnt in stmnts {
+        match *stmnt {
+            MirStmnt::Malloc { at, ref width } => out.push_str(&format!("{}{} = malloc {}\n", indent, at, width)),
+            MirStmnt::WriteAtFixed { at, ref expr } => out.push_str(&format!("{}{} = {}\n", indent, at, expr)),
+            MirStmnt::Pack { at, ref expr } => out.push_str(&format!("{}{} = pack {}\n", indent, at, expr)),
+            MirStmnt::ObjSet { ref base, offset, ref expr } => out.push_str(&format!("{}obj[{}+{}] = {}\n", indent, base, offset, expr)),
+            MirStmnt::Exec { ref fmt_str, ref handles } => {
+                let handles = handles.iter().map(|handle| handle.to_string()).collect::<Vec<String>>();
+                out.push_str(&format!("{}exec {}\n", indent, vec_fmt(fmt_str, &handles).trim_end().replace('\n', " : ")));
+            }
+            MirStmnt::Format { at, ref pieces, .. } => {
+                let pieces = pieces.iter().map(|(piece, typ)| format!("{}: {}", piece, typ)).collect::<Vec<String>>();
+                out.push_str(&format!("{}{} = format({})\n", indent, at, pieces.join(", ")));
+            }
+            MirStmnt::InvokeFunc { call_site_id, func_id, ref args, result } => {
+                let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+                let result = result.map_or(String::new(), |result| format!("{} = ", result));
+                out.push_str(&format!("{}{}call {}({}) from site {}\n", indent, result, m.func_manager.title(func_id), args.join(", "), call_site_id));
+            }
+            MirStmnt::MakeClosure { at, func_id, ref captures } => {
+                let captures = captures.iter().map(|capture| capture.to_string()).collect::<Vec<String>>();
+                out.push_str(&format!("{}{} = closure {}[{}]\n", indent, at, m.func_manager.title(func_id), captures.join(", ")));
+            }
+            MirStmnt::Restart { func_id, ref predicate } => out.push_str(&format!("{}restart {} if {}\n", indent, m.func_manager.title(func_id), predicate)),
+            MirStmnt::Free { ref base, width } => out.push_str(&format!("{}free {} words at {}\n", indent, width, base)),
+            MirStmnt::InvokeValue { call_site_id, ref callee, ref typ, ref args, result } => {
+                let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+                let result = result.map_or(String::new(), |result| format!("{} = ", result));
+                out.push_str(&format!("{}{}call {} as {}({}) from site {}\n", indent, result, callee, typ, args.join(", "), call_site_id));
+            }
+            MirStmnt::IfBlock { ref predicate, ref if_stmnts, ref else_stmnts } => {
+                out.push_str(&format!("{}if {}\n", indent, predicate));
+                dump_stmnts(m, if_stmnts, depth + 1, out);
+                out.push_str(&format!("{}else\n", indent));
+                dump_stmnts(m, else_stmnts, depth + 1, out);
+                out.push_str(&format!("{}end\n", indent));
+            }
+            MirStmnt::WhileBlock { ref predicate_stmnts, ref predicate, ref body } => {
+                if !predicate_stmnts.is_empty() {
+                    // these run before every check, so they go with the loop
+                    out.push_str(&format!("{}before each check\n", indent));
+                    dump_stmnts(m, predicate_stmnts, depth + 1, out);
+                }
+                out.push_str(&format!("{}while {}\n", indent, predicate));
+                dump_stmnts(m, body, depth + 1, out);
+                out.push_str(&format!("{}end\n", indent));
+            }
+        }
+    }
+}
+
+/// the MIR statements of the main program and of every function
+pub fn dump_mir(m: &Manager, mir: &MirProgram) -> String {
+    let mut out = format!("main ({} globals)\n", mir.global_count);
+    dump_stmnts(m, &mir.main, 1, &mut out);
+    out.push_str(&format!("    result {}\n", mir.result));
+    for func in mir.funcs.iter() {
+        out.push_str(&format!("func {} ({} arguments, frame of {}, {})\n", m.func_manager.title(func.func_id), func.arg_count, func.frame_size, func.returns));
+        dump_stmnts(m, &func.body, 1, &mut out);
+        out.push_str(&format!("    result {}\n", func.result));
+    }
+    for (func_id, call_sites) in mir.inlined.iter() {
+        let sites = if call_sites.len() == 1 { "site" } else { "sites" };
+        out.push_str(&format!("func {} inlined at call {} {}\n", m.func_manager.title(*func_id), sites, call_sites.iter().map(usize::to_string).collect::<Vec<String>>().join(", ")));
+    }
+    out
+}
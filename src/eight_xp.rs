@@ -0,0 +1,162 @@
+use util::{Error, ErrorCode};
+
+const SIGNATURE: &[u8] = b"**TI83F*\x1A\x0A\x00";
+const COMMENT_LEN: usize = 42;
+const NAME_LEN: usize = 8;
+/// the size of a variable entry's header, counted from the byte after itself up to the variable's data
+const VAR_HEADER_LEN: u16 = 13;
+const PROGRAM_TYPE_ID: u8 = 0x05;
+
+/// check that a name is one the calculator allows for programs:
+/// 1 to 8 uppercase letters, digits or θ, not starting with a digit
+pub fn check_program_name(name: &str) -> Result<(), Error> {
+    let count = name.chars().count();
+    if count == 0 || count > NAME_LEN {
+        return Err(Error::new_zero(ErrorCode::BadProgramName, format!("program name `{}` must be between 1 and {} characters long", name, NAME_LEN)));
+    }
+    if name.chars().next().is_some_and(|ch| ch.is_ascii_digit()) {
+        return Err(Error::new_zero(ErrorCode::BadProgramName, format!("program name `{}` can not start with a digit", name)));
+    }
+    if !name.chars().all(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit() || ch == 'θ') {
+        return Err(Error::new_zero(ErrorCode::BadProgramName, format!("program name `{}` may only hold uppercase letters, digits and θ", name)));
+    }
+    Ok(())
+}
+
+/// make a valid program name out of something like a file name, dropping whatever the calculator won't allow
+pub fn program_name_from(text: &str) -> String {
+    let name = text
+        .chars()
+        .map(|ch| ch.to_ascii_uppercase())
+        .filter(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit() || *ch == 'θ')
+        .skip_while(|ch| ch.is_ascii_digit())
+        .take(NAME_LEN)
+        .collect::<String>();
+    if name.is_empty() {
+        "PROGRAM".to_string()
+    } else {
+        name
+    }
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Result<u16, Error> {
+    match bytes.get(at..at + 2) {
+        Some(pair) => Ok(pair[0] as u16 | (pair[1] as u16) << 8),
+        None => Err(Error::new_zero(ErrorCode::Malformed8xp, format!("unexpected end of file at byte {}", at))),
+    }
+}
+
+fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.push((value & 0xFF) as u8);
+    bytes.push((value >> 8) as u8);
+}
+
+/// wrap the program's tokens in a .8xp file
+pub fn make_8xp(name: &str, tokens: &[u8]) -> Result<Vec<u8>, Error> {
+    check_program_name(name)?;
+    // a program's data is its length in bytes followed by its tokens
+    let data_len = tokens.len() + 2;
+    // the whole variable entry is the header's length, the header, then the data's length again and the data
+    if 2 + VAR_HEADER_LEN as usize + 2 + data_len > 0xFFFF {
+        return Err(Error::new_zero(ErrorCode::ProgramTooLarge, format!("program is too large to fit in a single variable ({} bytes of tokens)", tokens.len())));
+    }
+
+    let mut entry = vec![];
+    push_u16(&mut entry, VAR_HEADER_LEN);
+    push_u16(&mut entry, data_len as u16);
+    entry.push(PROGRAM_TYPE_ID);
+    let mut name_bytes = name
+        .chars()
+        .map(|ch| if ch == 'θ' { 0x5B } else { ch as u8 })
+        .collect::<Vec<u8>>();
+    name_bytes.resize(NAME_LEN, 0);
+    entry.extend_from_slice(&name_bytes);
+    entry.push(0x00); // version
+    entry.push(0x00); // flag: not archived
+    push_u16(&mut entry, data_len as u16);
+    push_u16(&mut entry, tokens.len() as u16);
+    entry.extend_from_slice(tokens);
+
+    let mut file = vec![];
+    file.extend_from_slice(SIGNATURE);
+    let mut comment = b"compiled by BasicTranspilation".to_vec();
+    comment.resize(COMMENT_LEN, 0);
+    file.extend_from_slice(&comment);
+    push_u16(&mut file, entry.len() as u16);
+    file.extend_from_slice(&entry);
+    // the checksum is the sum of the data section's bytes
+    let checksum = entry.iter().fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16));
+    push_u16(&mut file, checksum);
+    Ok(file)
+}
+
+/// pull the name and tokens of the first program out of a .8xp file
+pub fn read_8xp(file: &[u8]) -> Result<(String, Vec<u8>), Error> {
+    if !file.starts_with(SIGNATURE) {
+        return Err(Error::new_zero(ErrorCode::Malformed8xp, "not a .8xp file: missing the **TI83F* signature".to_string()));
+    }
+    let data_start = SIGNATURE.len() + COMMENT_LEN + 2;
+    let data_len = read_u16(file, data_start - 2)? as usize;
+    let data = file.get(data_start..data_start + data_len)
+        .ok_or_else(|| Error::new_zero(ErrorCode::Malformed8xp, format!("the file claims {} bytes of data, but is only {} bytes long", data_len, file.len())))?;
+    let checksum = data.iter().fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16));
+    if read_u16(file, data_start + data_len)? != checksum {
+        return Err(Error::new_zero(ErrorCode::Malformed8xp, "the file's checksum does not match its contents".to_string()));
+    }
+
+    let header_len = read_u16(data, 0)? as usize;
+    let type_id = *data.get(4).ok_or_else(|| Error::new_zero(ErrorCode::Malformed8xp, "variable entry is missing its type".to_string()))?;
+    if type_id != PROGRAM_TYPE_ID && type_id != PROGRAM_TYPE_ID + 1 {
+        return Err(Error::new_zero(ErrorCode::Malformed8xp, format!("variable is not a program (type id 0x{:02X})", type_id)));
+    }
+    let name = data.get(5..5 + NAME_LEN)
+        .ok_or_else(|| Error::new_zero(ErrorCode::Malformed8xp, "variable entry is missing its name".to_string()))?
+        .iter()
+        .take_while(|&&byte| byte != 0)
+        .map(|&byte| if byte == 0x5B { 'θ' } else { byte as char })
+        .collect::<String>();
+    // the header is followed by a second copy of the data length, then the data itself,
+    // which begins with the number of bytes of tokens
+    let tokens_start = 2 + header_len + 2;
+    let tokens_len = read_u16(data, tokens_start)? as usize;
+    let tokens = data.get(tokens_start + 2..tokens_start + 2 + tokens_len)
+        .ok_or_else(|| Error::new_zero(ErrorCode::Malformed8xp, "the program's tokens run past the end of the file".to_string()))?;
+    Ok((name, tokens.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_bytes_of_a_small_program() {
+        // Disp "A"
+        let tokens = [0xDE, 0x2A, 0x41, 0x2A];
+        let mut expected = b"**TI83F*\x1A\x0A\x00".to_vec();
+        let mut comment = b"compiled by BasicTranspilation".to_vec();
+        comment.resize(42, 0);
+        expected.extend_from_slice(&comment);
+        // the entry's length
+        expected.extend_from_slice(&[23, 0]);
+        // the header: its length, the data's length, the type, the padded name, the version and the archived flag
+        expected.extend_from_slice(&[13, 0, 6, 0, 0x05, b'A', b'B', 0, 0, 0, 0, 0, 0, 0x00, 0x00]);
+        // the data: its length again, then the tokens' length and the tokens
+        expected.extend_from_slice(&[6, 0, 4, 0]);
+        expected.extend_from_slice(&tokens);
+        // the sum of the entry's bytes
+        expected.extend_from_slice(&[0x18, 0x02]);
+        assert_eq!(make_8xp("AB", &tokens).unwrap(), expected);
+    }
+
+    #[test]
+    fn largest_program_that_fits() {
+        // 17 bytes of entry around the data, which is 2 bytes of length and then the tokens
+        let largest = 0xFFFF - 17 - 2;
+        let file = make_8xp("BIG", &vec![0x2A; largest]).unwrap();
+        let entry_len = read_u16(&file, SIGNATURE.len() + COMMENT_LEN).unwrap();
+        assert_eq!(entry_len, 0xFFFF);
+        assert_eq!(read_8xp(&file).unwrap().1.len(), largest);
+        let error = make_8xp("BIG", &vec![0x2A; largest + 1]).unwrap_err();
+        assert_eq!(error.code, ErrorCode::ProgramTooLarge);
+    }
+}
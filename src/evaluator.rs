@@ -0,0 +1,596 @@
+use sexpr::{SexprId, SexprKind, MapTarget};
+use type_checker::Type;
+use manager::Manager;
+use mir::MirProgram;
+use number::Number;
+use simulator::{TiRand, round_to_precision, round_like, format_number};
+use util::{Error, ErrorCode};
+
+use std::collections::{HashMap, HashSet};
+
+/// the heap stands in for ⌊OBJ, which holds at most 999 words and starts with one already in it
+const MAX_HEAP_WORDS: usize = 998;
+/// ⌊ARGS starts with one word in it too, and every call that isn't inlined pushes at least its call site id,
+/// so calls can't nest any deeper than this
+const MAX_ARGS_WORDS: usize = 998;
+/// the stack `evaluate` has to be run with: it recurses for every call, so it needs room for calls nested as deep as ⌊ARGS lets them
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// a value of the s-expression language
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Void,
+    /// ints and reals are both kept the way the calculator keeps them
+    Num(f64),
+    Boole(bool),
+    Str(String),
+    /// a reference to a struct on the heap, the same as a pointer into ⌊OBJ
+    Struct{id: usize, at: usize},
+    /// a reference to a list's header on the heap: its length, then where its elements start
+    List{at: usize},
+    /// a reference to an enum on the heap: which variant it is, then that variant's fields
+    Enum{id: usize, at: usize},
+    /// a reference to a function's record on the heap: which function it is, then what it captured
+    Func{at: usize},
+}
+
+impl Value {
+    fn num(&self) -> f64 {
+        match *self {
+            Value::Num(x) => x,
+            ref value => panic!("type checking let a {:?} through where a number was expected", value),
+        }
+    }
+    fn boole(&self) -> bool {
+        match *self {
+            Value::Boole(b) => b,
+            ref value => panic!("type checking let a {:?} through where a boole was expected", value),
+        }
+    }
+}
+
+/// the digits of a number as the calculator stores it, split at the decimal point
+fn decimal_digits(x: f64) -> (String, String) {
+    if x == 0.0 {
+        return ("0".to_string(), String::new());
+    }
+    let scientific = format!("{:.13e}", x.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let exponent = exponent[1..].parse::<i32>().unwrap();
+    let digits = mantissa.replace('.', "");
+    let digits = digits.trim_end_matches('0');
+    if exponent < 0 {
+        ("0".to_string(), format!("{}{}", "0".repeat((-exponent - 1) as usize), digits))
+    } else {
+        let whole_len = exponent as usize + 1;
+        if digits.len() <= whole_len {
+            (format!("{}{}", digits, "0".repeat(whole_len - digits.len())), String::new())
+        } else {
+            (digits[..whole_len].to_string(), digits[whole_len..].to_string())
+        }
+    }
+}
+
+/// write a value the way `format` does on the calculator
+fn stringify(m: &Manager, value: &Value, typ: &Type) -> String {
+    match (typ, value) {
+        (Type::String, Value::Str(text)) => text.clone(),
+        (Type::Boole, &Value::Boole(b)) => if b { "true" } else { "false" }.to_string(),
+        (Type::Int, &Value::Num(x)) | (Type::Real, &Value::Num(x)) => {
+            let sign = if x < 0.0 { "-" } else { "" };
+            let (whole, fraction) = decimal_digits(x);
+            if let Type::Real = *typ {
+                format!("{}{}.{}", sign, whole, if fraction.is_empty() { "0" } else { &fraction })
+            } else {
+                // an int with a fraction still only shows its whole part
+                format!("{}{}", sign, whole)
+            }
+        }
+        (Type::Func(..), _) => "<Func>".to_string(),
+        (Type::CustomType(name, id), _) => format!("<{} {}>", if m.udt_manager.is_enum(*id) { "Enum" } else { "Struct" }, name),
+        (_, _) if typ.is_void() => "void".to_string(),
+        (typ, value) => panic!("value {:?} does not have type {}", value, typ),
+    }
+}
+
+/// write a value the way `Disp` shows it
+fn display(m: &Manager, value: &Value) -> String {
+    match *value {
+        Value::Void => "void".to_string(),
+        Value::Num(x) => format_number(x),
+        // the calculator has no booleans, only 1 and 0
+        Value::Boole(b) => if b { "1" } else { "0" }.to_string(),
+        Value::Str(ref text) => text.clone(),
+        Value::Struct { id, .. } => format!("<Struct {}>", m.udt_manager.name[id]),
+        Value::List { .. } => unreachable!("type checking refuses to print lists"),
+        Value::Enum { id, .. } => format!("<Enum {}>", m.udt_manager.name[id]),
+        Value::Func { .. } => "<Func>".to_string(),
+    }
+}
+
+/// what was left behind after running a program
+pub struct Evaluation {
+    /// every line the program showed with `print` or `scroll-print`
+    pub display: Vec<String>,
+    /// the value of the last top level s-expr, which the tests hold the compiled program's Ans to
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub result: Value,
+    /// the error the program stopped on, if it didn't finish
+    pub error: Option<Error>,
+}
+
+/// the variables of a single call, keyed by (scope index, variable id)
+type Frame = HashMap<(usize, usize), Value>;
+
+struct Evaluator<'a> {
+    m: &'a Manager,
+    /// what the compiled program would be, for the frames that calls push onto ⌊ARGS
+    mir: &'a MirProgram,
+    heap: Vec<Value>,
+    rand: TiRand,
+    display: Vec<String>,
+    /// how many words the calls being run have pushed onto ⌊ARGS
+    args_words: usize,
+    /// the calls that functions make to themselves as the last thing they do, by s-expr index
+    tail_calls: HashSet<usize>,
+    /// the arguments of a tail call, for the function to start over with
+    restart: Option<Vec<Value>>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn error<T>(&self, sexpr_id: SexprId, why: &str) -> Result<T, Error> {
+        Err(Error::new(ErrorCode::RunTime, why.to_string(), self.m.sexpr(sexpr_id).token))
+    }
+
+    /// keep a number inside what the calculator can hold
+    fn checked(&self, sexpr_id: SexprId, x: f64) -> Result<Value, Error> {
+        if !x.is_finite() || x.abs() >= 1e100 {
+            return self.error(sexpr_id, "ERR:OVERFLOW");
+        }
+        Ok(Value::Num(round_to_precision(x)))
+    }
+
+    fn variable(&self, sexpr_id: SexprId, name: &str) -> (usize, usize) {
+        let (scope_id, var_id) = self.m.resolve_variable(self.m.scope_of(sexpr_id), name).expect("unresolved variable in evaluation");
+        (scope_id.index, var_id)
+    }
+
+    fn eval_all<I: IntoIterator<Item = SexprId>>(&mut self, sexpr_ids: I, frame: &mut Frame) -> Result<Vec<Value>, Error> {
+        sexpr_ids.into_iter().map(|sexpr_id| self.eval(sexpr_id, frame)).collect()
+    }
+
+    fn eval(&mut self, sexpr_id: SexprId, frame: &mut Frame) -> Result<Value, Error> {
+        let value = self.eval_kind(sexpr_id, frame)?;
+        // the calculator can't grow ⌊OBJ past this, so neither can we
+        if self.heap.len() > MAX_HEAP_WORDS {
+            return self.error(sexpr_id, "ERR:INVALID DIM");
+        }
+        Ok(value)
+    }
+
+    fn eval_kind(&mut self, sexpr_id: SexprId, frame: &mut Frame) -> Result<Value, Error> {
+        let kind = self.m.kind(sexpr_id).clone();
+        Ok(match kind {
+            SexprKind::Declare { ref variable_pattern, expr, body, .. } => {
+                let value = self.eval(expr, frame)?;
+                frame.insert(self.variable(body, variable_pattern), value);
+                self.eval(body, frame)?
+            }
+            SexprKind::Assign { ref variable_pattern, expr } => {
+                let value = self.eval(expr, frame)?;
+                frame.insert(self.variable(sexpr_id, variable_pattern), value.clone());
+                value
+            }
+            SexprKind::IfSwitch { predicate, if_branch, else_branch } => {
+                if self.eval(predicate, frame)?.boole() {
+                    self.eval(if_branch, frame)?
+                } else {
+                    self.eval(else_branch, frame)?
+                }
+            }
+            SexprKind::WhileLoop { predicate, body } => {
+                let mut value = match *self.m.result_type(sexpr_id) {
+                    Type::Int | Type::Real => Value::Num(0.0),
+                    Type::Boole => Value::Boole(false),
+                    Type::String => Value::Str(String::new()),
+                    _ => Value::Void,
+                };
+                while self.eval(predicate, frame)?.boole() {
+                    value = self.eval(body, frame)?;
+                }
+                value
+            }
+            SexprKind::Block { ref statements } => {
+                let mut value = Value::Void;
+                for &statement in statements {
+                    value = self.eval(statement, frame)?;
+                }
+                value
+            }
+            SexprKind::FuncDef { .. } | SexprKind::StructDef { .. } | SexprKind::EnumDef { .. } => Value::Void,
+            SexprKind::FuncCall { ref exprs, .. } if self.tail_calls.contains(&sexpr_id.index) => {
+                // the function starts over in the same frame, like the compiled program does
+                self.restart = Some(self.eval_all(exprs.iter().cloned(), frame)?);
+                Value::Void
+            }
+            SexprKind::FuncCall { func_id, ref exprs, .. } => {
+                let args = self.eval_all(exprs.iter().cloned(), frame)?;
+                if self.m.func_manager.captures[func_id].is_empty() {
+                    self.call(sexpr_id, func_id, args)?
+                } else {
+                    // the values it captures are put in a record for the call, which is given back when nothing came after it
+                    let env = self.closure(func_id, frame);
+                    let (func_id, callee_frame) = self.closure_frame(&env, args);
+                    let result = self.run(sexpr_id, func_id, callee_frame, self.pushed_by_call(func_id))?;
+                    if let Value::Func { at } = env {
+                        if self.heap.len() == at + 1 + self.m.func_manager.captures[func_id].len() {
+                            self.heap.truncate(at);
+                        }
+                    }
+                    result
+                }
+            }
+            SexprKind::ValueCall { ref exprs, .. } => {
+                let args = self.eval_all(exprs.iter().cloned(), frame)?;
+                // the variable is read after the arguments, which could assign to it
+                let key = self.variable(sexpr_id, self.m.text(sexpr_id));
+                let callee = frame.get(&key).cloned().expect("variable was read before it was declared");
+                self.call_value(sexpr_id, &callee, args)?
+            }
+            SexprKind::Lambda { func_id } | SexprKind::FuncRef { func_id } => self.closure(func_id, frame),
+            SexprKind::StructInit { id, ref exprs } => {
+                let fields = self.eval_all(exprs.iter().cloned(), frame)?;
+                let at = self.heap.len();
+                self.heap.extend(fields);
+                Value::Struct { id, at }
+            }
+            SexprKind::EnumInit { id, variant, ref exprs } => {
+                let fields = self.eval_all(exprs.iter().cloned(), frame)?;
+                let at = self.heap.len();
+                self.heap.push(Value::Num(variant as f64));
+                self.heap.extend(fields);
+                Value::Enum { id, at }
+            }
+            SexprKind::Match { id, expr, ref arms } => {
+                let id = id.expect("enum id should have been logged in typechecking");
+                let at = match self.eval(expr, frame)? {
+                    Value::Enum { at, .. } => at,
+                    value => panic!("type checking let a {:?} through where an enum was expected", value),
+                };
+                let variant = self.heap[at].num() as usize;
+                // type checking made sure some arm takes every variant
+                let arm = arms.iter()
+                    .find(|arm| arm.variant.as_ref().is_none_or(|name| self.m.udt_manager.variant_index(id, name) == Some(variant)))
+                    .expect("no arm of a checked match took the variant");
+                for (index, binding) in arm.bindings.iter().enumerate() {
+                    let value = self.heap[at + 1 + index].clone();
+                    frame.insert(self.variable(arm.body, binding), value);
+                }
+                self.eval(arm.body, frame)?
+            }
+            SexprKind::StructGet { id, expr, ref field } => {
+                let offset = self.m.udt_manager.get_field_offset(id.expect("struct id should have been logged in typechecking"), field);
+                match self.eval(expr, frame)? {
+                    Value::Struct { at, .. } => self.heap[at + offset].clone(),
+                    value => panic!("type checking let a {:?} through where a struct was expected", value),
+                }
+            }
+            SexprKind::StructSet { id, expr, ref field, value } => {
+                let offset = self.m.udt_manager.get_field_offset(id.expect("struct id should have been logged in typechecking"), field);
+                let base = self.eval(expr, frame)?;
+                let value = self.eval(value, frame)?;
+                match base {
+                    Value::Struct { at, .. } => self.heap[at + offset] = value,
+                    value => panic!("type checking let a {:?} through where a struct was expected", value),
+                }
+                Value::Void
+            }
+            SexprKind::List { ref elements } => {
+                let elements = self.eval_all(elements.iter().cloned(), frame)?;
+                self.new_list(elements)
+            }
+            SexprKind::ListGet { list, index } => {
+                let values = self.eval_all(vec![list, index], frame)?;
+                let slot = self.list_slot(sexpr_id, &values[0], &values[1])?;
+                self.heap[slot].clone()
+            }
+            SexprKind::ListSet { list, index, elem } => {
+                let mut values = self.eval_all(vec![list, index, elem], frame)?;
+                let slot = self.list_slot(sexpr_id, &values[0], &values[1])?;
+                self.heap[slot] = values.pop().unwrap();
+                Value::Void
+            }
+            SexprKind::ListLen { list } => {
+                let at = self.eval_list(list, frame)?;
+                Value::Num(self.list_header(at).0 as f64)
+            }
+            SexprKind::ListPush { list, elem } => {
+                let at = self.eval_list(list, frame)?;
+                let elem = self.eval(elem, frame)?;
+                let (len, mut start) = self.list_header(at);
+                // the elements grow in place when nothing comes after them, and are moved to the end when something does
+                if start + len != self.heap.len() {
+                    let elements = self.heap[start..start + len].to_vec();
+                    start = self.heap.len();
+                    self.heap.extend(elements);
+                    self.heap[at + 1] = Value::Num(start as f64);
+                }
+                self.heap.push(elem);
+                self.heap[at] = Value::Num((len + 1) as f64);
+                Value::Void
+            }
+            SexprKind::ListMap { target, func, list } => {
+                let callee = match target {
+                    Some(MapTarget::Value { .. }) => Some(self.eval(func, frame)?),
+                    _ => None,
+                };
+                let at = self.eval_list(list, frame)?;
+                let (len, start) = self.list_header(at);
+                let mapped = self.new_list(vec![Value::Void; len]);
+                let mapped_start = self.list_header(self.list_at(&mapped)).1;
+                for index in 0..len {
+                    let elem = self.heap[start + index].clone();
+                    let value = match target.expect("list-map target should have been settled in type checking") {
+                        MapTarget::Func { func_id, .. } => self.call(sexpr_id, func_id, vec![elem])?,
+                        MapTarget::Value { .. } => self.call_value(sexpr_id, callee.as_ref().expect("list-map through a value without the value"), vec![elem])?,
+                        MapTarget::StructInit { id } => {
+                            self.heap.push(elem);
+                            Value::Struct { id, at: self.heap.len() - 1 }
+                        }
+                        MapTarget::BuiltIn { id } => self.builtin(sexpr_id, id, vec![elem])?,
+                    };
+                    self.heap[mapped_start + index] = value;
+                }
+                mapped
+            }
+            SexprKind::Format { ref exprs } => {
+                let mut text = String::new();
+                for &expr in exprs {
+                    let value = self.eval(expr, frame)?;
+                    text.push_str(&stringify(self.m, &value, self.m.result_type(expr)));
+                }
+                Value::Str(text)
+            }
+            SexprKind::BuiltIn { id, ref exprs } => {
+                let args = self.eval_all(exprs.iter().cloned(), frame)?;
+                self.builtin(sexpr_id, id, args)?
+            }
+            SexprKind::Other { .. } => panic!("we should not be evaluating a SexprKind::Other (it should have been realized in type checking)"),
+            SexprKind::Identifier => {
+                let key = self.variable(sexpr_id, self.m.text(sexpr_id));
+                frame.get(&key).cloned().expect("variable was read before it was declared")
+            }
+            SexprKind::RealLiteral | SexprKind::IntegerLiteral => {
+                let x = Number::parse(self.m.text(sexpr_id)).expect("the lexer let through a mangled number literal").value();
+                self.checked(sexpr_id, x)?
+            }
+            SexprKind::StringLiteral => {
+                let text = self.m.text(sexpr_id);
+                Value::Str(text[1..text.len() - 1].to_string())
+            }
+            SexprKind::BooleLiteral => Value::Boole(self.m.text(sexpr_id) == "true"),
+        })
+    }
+
+    /// call a user defined function with these arguments
+    fn call(&mut self, sexpr_id: SexprId, func_id: usize, args: Vec<Value>) -> Result<Value, Error> {
+        let callee_frame = self.arguments_frame(func_id, args);
+        self.run(sexpr_id, func_id, callee_frame, self.pushed_by_call(func_id))
+    }
+
+    /// the words a direct call to this function pushes onto ⌊ARGS: its frame and the call site id, or none when it was inlined
+    fn pushed_by_call(&self, func_id: usize) -> usize {
+        self.mir.frame_size(func_id).map_or(0, |frame_size| frame_size + 1)
+    }
+
+    /// a frame for a call to this function, holding just its arguments
+    fn arguments_frame(&self, func_id: usize, args: Vec<Value>) -> Frame {
+        let scope_index = self.m.scope_of(self.m.func_manager.body[func_id]).index;
+        // arguments are bound first, so their variable ids line up with their positions
+        args.into_iter().enumerate().map(|(var_id, arg)| ((scope_index, var_id), arg)).collect()
+    }
+
+    /// run the body of a function in the frame made for it, which takes up `pushed` words of ⌊ARGS
+    fn run(&mut self, sexpr_id: SexprId, func_id: usize, mut callee_frame: Frame, pushed: usize) -> Result<Value, Error> {
+        // the calculator can't augment ⌊ARGS past this
+        if self.args_words + pushed > MAX_ARGS_WORDS {
+            return self.error(sexpr_id, "ERR:INVALID DIM");
+        }
+        let body = self.m.func_manager.body[func_id];
+        let scope_index = self.m.scope_of(body).index;
+        self.args_words += pushed;
+        let result = loop {
+            let result = self.eval(body, &mut callee_frame);
+            match self.restart.take() {
+                // a call to itself at the end, so go again with the new arguments
+                Some(args) if result.is_ok() => {
+                    for (var_id, arg) in args.into_iter().enumerate() {
+                        callee_frame.insert((scope_index, var_id), arg);
+                    }
+                }
+                _ => break result,
+            }
+        };
+        self.args_words -= pushed;
+        result
+    }
+
+    /// put a record of a function and the values it captures on the heap
+    fn closure(&mut self, func_id: usize, frame: &Frame) -> Value {
+        let captures = self.m.func_manager.captures[func_id]
+            .iter()
+            .map(|key| frame.get(key).cloned().expect("captured variable was read before it was declared"))
+            .collect::<Vec<Value>>();
+        let at = self.heap.len();
+        self.heap.push(Value::Num(func_id as f64));
+        self.heap.extend(captures);
+        Value::Func { at }
+    }
+
+    /// call whichever function a function value's record says, giving it what the record captured
+    fn call_value(&mut self, sexpr_id: SexprId, callee: &Value, args: Vec<Value>) -> Result<Value, Error> {
+        let arg_count = args.len();
+        let (func_id, callee_frame) = self.closure_frame(callee, args);
+        // the frame is big enough for any function of its type
+        let pushed = self.mir.value_frame_size(self.m, &self.m.func_manager.func_type(func_id), arg_count) + 1;
+        self.run(sexpr_id, func_id, callee_frame, pushed)
+    }
+
+    /// the function a function value's record says, with a frame of the arguments and what the record captured
+    fn closure_frame(&self, callee: &Value, args: Vec<Value>) -> (usize, Frame) {
+        let at = match *callee {
+            Value::Func { at } => at,
+            ref value => panic!("type checking let a {:?} through where a function was expected", value),
+        };
+        let func_id = self.heap[at].num() as usize;
+        let mut callee_frame = self.arguments_frame(func_id, args);
+        for (index, key) in self.m.func_manager.captures[func_id].iter().enumerate() {
+            callee_frame.insert(*key, self.heap[at + 1 + index].clone());
+        }
+        (func_id, callee_frame)
+    }
+
+    /// put a list on the heap the way the compiled program does: a header, then the elements
+    fn new_list(&mut self, elements: Vec<Value>) -> Value {
+        let at = self.heap.len();
+        self.heap.push(Value::Num(elements.len() as f64));
+        self.heap.push(Value::Num((at + 2) as f64));
+        self.heap.extend(elements);
+        Value::List { at }
+    }
+
+    fn list_at(&self, value: &Value) -> usize {
+        match *value {
+            Value::List { at } => at,
+            ref value => panic!("type checking let a {:?} through where a list was expected", value),
+        }
+    }
+
+    fn eval_list(&mut self, list: SexprId, frame: &mut Frame) -> Result<usize, Error> {
+        let value = self.eval(list, frame)?;
+        Ok(self.list_at(&value))
+    }
+
+    /// the length of the list whose header is at `at`, and where its elements start
+    fn list_header(&self, at: usize) -> (usize, usize) {
+        (self.heap[at].num() as usize, self.heap[at + 1].num() as usize)
+    }
+
+    /// where on the heap an element of a list is, which has to be inside of the list
+    fn list_slot(&self, sexpr_id: SexprId, list: &Value, index: &Value) -> Result<usize, Error> {
+        let (len, start) = self.list_header(self.list_at(list));
+        let index = index.num();
+        if index < 0.0 || index >= len as f64 {
+            return self.error(sexpr_id, "ERR:INVALID DIM");
+        }
+        Ok(start + index as usize)
+    }
+
+    /// run a builtin the same way its code template runs on the calculator
+    fn builtin(&mut self, sexpr_id: SexprId, id: usize, args: Vec<Value>) -> Result<Value, Error> {
+        let name = self.m.builtin_manager.name[id].as_str();
+        let num = |index: usize| args[index].num();
+        Ok(match name {
+            "equals" => Value::Boole(args[0] == args[1]),
+            "not-equals" => Value::Boole(args[0] != args[1]),
+            "lesser" => Value::Boole(num(0) < num(1)),
+            "lesser-equal" => Value::Boole(num(0) <= num(1)),
+            "greater" => Value::Boole(num(0) > num(1)),
+            "greater-equal" => Value::Boole(num(0) >= num(1)),
+            "not" => Value::Boole(!args[0].boole()),
+            "and" => Value::Boole(args[0].boole() && args[1].boole()),
+            "or" => Value::Boole(args[0].boole() || args[1].boole()),
+            "xor" => Value::Boole(args[0].boole() != args[1].boole()),
+            "add" => Value::Num(round_like(num(0) + num(1), num(0).abs().max(num(1).abs()))),
+            "sub" => Value::Num(round_like(num(0) - num(1), num(0).abs().max(num(1).abs()))),
+            "mul" => self.checked(sexpr_id, num(0) * num(1))?,
+            "div" => {
+                if num(1) == 0.0 {
+                    return self.error(sexpr_id, "ERR:DIVIDE BY 0");
+                }
+                self.checked(sexpr_id, num(0) / num(1))?
+            }
+            "rem" => {
+                let (a, b) = (num(0), num(1));
+                if b == 0.0 {
+                    return self.error(sexpr_id, "ERR:DIVIDE BY 0");
+                }
+                let whole_dividend = match self.m.builtin_manager.in_types[id][0] {
+                    Type::Int => a.trunc() == a,
+                    _ => true,
+                };
+                if !whole_dividend || b.trunc() != b {
+                    return self.error(sexpr_id, "ERR:DOMAIN");
+                }
+                // the remainder of the whole part, plus whatever fraction there was
+                Value::Num(round_like(a.trunc() % b + round_like(a.fract(), a), a.abs()))
+            }
+            "pow" => {
+                let (a, b) = (num(0), num(1));
+                if a == 0.0 && b < 0.0 {
+                    return self.error(sexpr_id, "ERR:DIVIDE BY 0");
+                }
+                if a == 0.0 && b == 0.0 {
+                    return self.error(sexpr_id, "ERR:DOMAIN");
+                }
+                if a < 0.0 && b.fract() != 0.0 {
+                    return self.error(sexpr_id, "ERR:NONREAL ANS");
+                }
+                self.checked(sexpr_id, a.powf(b))?
+            }
+            "nat-exp" => self.checked(sexpr_id, num(0).exp())?,
+            "nat-log" => {
+                if num(0) < 0.0 {
+                    return self.error(sexpr_id, "ERR:NONREAL ANS");
+                }
+                if num(0) == 0.0 {
+                    return self.error(sexpr_id, "ERR:DOMAIN");
+                }
+                self.checked(sexpr_id, num(0).ln())?
+            }
+            "rand-uniform" => Value::Num(self.rand.next()),
+            "rand-int" => {
+                let (low, high) = (num(0), num(1));
+                Value::Num(self.rand.int(low, high))
+            }
+            "print" | "scroll-print" => {
+                self.display.push(display(self.m, &args[0]));
+                Value::Void
+            }
+            "pause" => Value::Void,
+            name => panic!("the evaluator does not know the builtin `{}`", name),
+        })
+    }
+}
+
+/// run a type checked program directly, without going through TI-84 Basic, on a thread with `STACK_SIZE` of stack.
+/// the program's MIR says how much of ⌊ARGS each call would take up once compiled
+pub fn evaluate(m: &Manager, mir: &MirProgram) -> Evaluation {
+    let mut evaluator = Evaluator {
+        m,
+        mir,
+        heap: vec![],
+        rand: TiRand::new(),
+        display: vec![],
+        args_words: 0,
+        tail_calls: (0..m.func_manager.count)
+            .filter(|&func_id| !m.func_manager.is_generic(func_id))
+            .flat_map(|func_id| m.self_tail_calls(func_id))
+            .map(|sexpr_id| sexpr_id.index)
+            .collect(),
+        restart: None,
+    };
+    let mut globals = Frame::new();
+    let mut result = Value::Void;
+    let mut error = None;
+    for &sexpr_id in m.top_level_sexprs.iter() {
+        match evaluator.eval(sexpr_id, &mut globals) {
+            Ok(value) => result = value,
+            Err(why) => {
+                error = Some(why);
+                break;
+            }
+        }
+    }
+    Evaluation { display: evaluator.display, result, error }
+}
@@ -0,0 +1,181 @@
+use tokenizer::{Token, TokenKind};
+use util::{Error, ErrorCode};
+
+/// the widest a line gets before a list is broken up over several lines
+const WIDTH: usize = 80;
+const INDENT: usize = 4;
+
+/// a program as nested lists of atoms, the only structure the layout cares about
+#[derive(Debug, Clone)]
+pub enum Tree {
+    Atom(String),
+    List(Vec<Tree>),
+    /// a comment, and whether it trails the code before it on the same line
+    Comment(String, bool),
+}
+impl Tree {
+    /// comments have to keep their lines, so anything holding one can't be put on one line
+    fn has_comment(&self) -> bool {
+        match self {
+            Tree::Atom(_) => false,
+            Tree::List(items) => items.iter().any(Tree::has_comment),
+            Tree::Comment(..) => true,
+        }
+    }
+    /// the whole tree on one line
+    pub fn flat(&self) -> String {
+        match self {
+            Tree::Atom(text) | Tree::Comment(text, _) => text.to_string(),
+            Tree::List(items) => format!("({})", join(items.iter().map(Tree::flat))),
+        }
+    }
+}
+
+/// join with spaces, except that type separators stick to the name before them
+fn join(items: impl Iterator<Item = String>) -> String {
+    let mut joined = String::new();
+    for item in items {
+        if !joined.is_empty() && item != ":" {
+            joined.push(' ');
+        }
+        joined.push_str(&item);
+    }
+    joined
+}
+
+/// how many items after the head stay on the head's line when a list is broken up,
+/// and whether the list is always broken up when there is anything after them
+fn header(tree: &[Tree]) -> (usize, bool) {
+    // a function type is written as a list, but it goes with the name before it like any other type
+    let is_type = |item: &Tree| match item {
+        Tree::Atom(_) => true,
+        Tree::List(items) => matches!(items.first(), Some(Tree::Atom(head)) if head == "->"),
+        Tree::Comment(..) => false,
+    };
+    let leading_atoms = tree.iter().skip(1).take_while(|item| is_type(item)).count();
+    // a declare's name can carry a type annotation, which stays with it
+    let annotated = matches!(tree.get(2), Some(Tree::Atom(separator)) if separator == ":");
+    match tree.first() {
+        Some(Tree::Atom(head)) => match &head[..] {
+            "declare" if annotated => (4, true),
+            "declare" => (2, true),
+            "while" | "match" => (1, true),
+            "func" => (leading_atoms, true),
+            "lambda" => (leading_atoms, false),
+            "block" => (0, true),
+            "if" | "assign" | "enum" => (1, false),
+            "set-field" => (2, false),
+            "struct" => (leading_atoms, false),
+            _ => (0, false),
+        },
+        _ => (0, false),
+    }
+}
+
+fn render(tree: &Tree, indent: usize, out: &mut String) {
+    let items = match tree {
+        Tree::Atom(text) | Tree::Comment(text, _) => return out.push_str(text),
+        Tree::List(items) => items,
+    };
+    let (header_len, always_break) = header(items);
+    // a comment ends the head's line early
+    let first_comment = items.iter().position(Tree::has_comment).unwrap_or(items.len());
+    let header_end = (header_len + 1).min(first_comment);
+    let flat = tree.flat();
+    if first_comment == items.len() && (!always_break || items.len() == header_end) && indent + flat.len() <= WIDTH {
+        return out.push_str(&flat);
+    }
+    // a match's arms keep each pattern on the same line as its body
+    let paired = matches!(items.first(), Some(Tree::Atom(head)) if head == "match");
+    out.push('(');
+    out.push_str(&join(items[..header_end].iter().map(Tree::flat)));
+    for (index, item) in items.iter().enumerate().skip(header_end) {
+        if let Tree::Comment(text, true) = item {
+            if index != 0 {
+                out.push(' ');
+                out.push_str(text);
+                continue;
+            }
+        }
+        let arm_item = items[header_end..index].iter().filter(|item| !matches!(item, Tree::Comment(..))).count();
+        if paired && arm_item % 2 == 1 && !item.has_comment() && !items[index - 1].has_comment() {
+            out.push(' ');
+            render(item, indent + INDENT, out);
+            continue;
+        }
+        out.push('\n');
+        out.push_str(&" ".repeat(indent + INDENT));
+        render(item, indent + INDENT, out);
+    }
+    out.push('\n');
+    out.push_str(&" ".repeat(indent));
+    out.push(')');
+}
+
+/// lay out top level trees, each starting on its own line,
+/// with a blank line before the ones that are marked for it
+pub fn render_all(trees: &[(Tree, bool)]) -> String {
+    let mut out = String::new();
+    for (tree, blank_line_before) in trees {
+        if let Tree::Comment(text, true) = tree {
+            if out.ends_with('\n') {
+                out.pop();
+                out.push(' ');
+                out.push_str(text);
+                out.push('\n');
+                continue;
+            }
+        }
+        if *blank_line_before && !out.is_empty() {
+            out.push('\n');
+        }
+        render(tree, 0, &mut out);
+        out.push('\n');
+    }
+    out
+}
+
+/// reformat source text, keeping its comments and the blank lines between top level s-exprs.
+/// the tokens have to include the comments
+pub fn format_source(source: &str, tokens: &[Token]) -> Result<String, Error> {
+    let mut trees = vec![];
+    let mut stack: Vec<(Token, Vec<Tree>)> = vec![];
+    let mut last_end = 0;
+    let mut blank_line_before = false;
+    for &token in tokens {
+        let text = token.get_text(source);
+        if stack.is_empty() {
+            blank_line_before = source[last_end..token.begin()].matches('\n').count() > 1;
+        }
+        match token.kind {
+            TokenKind::LParen => stack.push((token, vec![])),
+            TokenKind::RParen => {
+                let (_, items) = stack.pop()
+                    .ok_or_else(|| Error::new(ErrorCode::UnpairedParen, "un-paired closing parenthesis".to_string(), token))?;
+                let list = Tree::List(items);
+                match stack.last_mut() {
+                    Some((_, parent)) => parent.push(list),
+                    None => trees.push((list, blank_line_before)),
+                }
+            }
+            _ => {
+                let atom = if token.kind == TokenKind::Comment {
+                    // a comment that shares its line with the code before it stays on that line
+                    let trailing = last_end != 0 && !source[last_end..token.begin()].contains('\n');
+                    Tree::Comment(text.trim_end().to_string(), trailing)
+                } else {
+                    Tree::Atom(text.to_string())
+                };
+                match stack.last_mut() {
+                    Some((_, parent)) => parent.push(atom),
+                    None => trees.push((atom, blank_line_before)),
+                }
+            }
+        }
+        last_end = token.begin() + token.len();
+    }
+    if let Some(&(open, _)) = stack.first() {
+        return Err(Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), open));
+    }
+    Ok(render_all(&trees))
+}
@@ -0,0 +1,719 @@
+//! filling in the argument and return types that function definitions leave out,
+//! from how their bodies use the arguments and from what their call sites give them
+
+use manager::Manager;
+use sexpr::{SexprId, SexprKind, MatchArm};
+use scope::ScopeId;
+use tokenizer::Token;
+use type_checker::{Type, FutureType};
+use util::{Error, ErrorCode};
+
+use std::collections::HashMap;
+
+/// a type that may not be known yet
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Known(Type),
+    Var(usize),
+}
+
+/// a type variable, standing in for a type that was left out or that can't be decided yet
+#[derive(Debug)]
+struct Var {
+    /// variables that have been unified share the lowest of their ids as their root
+    parent: usize,
+    /// what the variable is the type of, for pointing it out in errors
+    what: String,
+    /// the type it turned out to be, and the use that decided it
+    found: Option<(Type, Token)>,
+}
+
+struct Inference<'a> {
+    m: &'a Manager,
+    vars: Vec<Var>,
+    /// the argument types and return type of every function
+    params: Vec<Vec<Ty>>,
+    outs: Vec<Ty>,
+    /// variable types, keyed by scope and variable index
+    variables: HashMap<(usize, usize), Ty>,
+}
+
+impl<'a> Inference<'a> {
+    fn new(m: &'a Manager) -> Inference<'a> {
+        let mut inference = Inference { m, vars: vec![], params: vec![], outs: vec![], variables: HashMap::new() };
+        for func_id in 0..m.func_manager.count {
+            let name = &m.func_manager.name[func_id];
+            let params = m.func_manager.in_types[func_id]
+                .iter()
+                .zip(m.func_manager.args[func_id].iter())
+                .map(|(typ, arg)| inference.slot_for(typ, format!("argument `{}` of `{}`", arg, name)))
+                .collect();
+            let out = inference.slot_for(&m.func_manager.out_type[func_id], format!("the return type of `{}`", name));
+            inference.params.push(params);
+            inference.outs.push(out);
+        }
+        inference
+    }
+
+    /// a part of a signature: its type, or a variable standing in for it when it was left out
+    fn slot_for(&mut self, typ: &FutureType, what: String) -> Ty {
+        match typ.known() {
+            Some(typ) => Ty::Known(typ.clone()),
+            None => self.fresh(what),
+        }
+    }
+
+    fn fresh(&mut self, what: String) -> Ty {
+        let id = self.vars.len();
+        self.vars.push(Var { parent: id, what, found: None });
+        Ty::Var(id)
+    }
+
+    fn find(&self, var: usize) -> usize {
+        let parent = self.vars[var].parent;
+        if parent == var { var } else { self.find(parent) }
+    }
+
+    /// the known type, or the root variable of an unknown one
+    fn resolve(&self, ty: &Ty) -> Ty {
+        match *ty {
+            Ty::Known(ref typ) => Ty::Known(typ.clone()),
+            Ty::Var(var) => {
+                let root = self.find(var);
+                match self.vars[root].found {
+                    Some((ref typ, _)) => Ty::Known(typ.clone()),
+                    None => Ty::Var(root),
+                }
+            }
+        }
+    }
+
+    /// decide that a root variable is `typ` because of the use at `at`
+    fn settle(&mut self, root: usize, typ: Type, at: Token) -> Result<(), Error> {
+        match self.vars[root].found {
+            None => {
+                self.vars[root].found = Some((typ, at));
+                Ok(())
+            }
+            Some((ref known, known_at)) if Type::is_not(known, &typ) => {
+                Err(Error::new(ErrorCode::TypeMismatch, format!("{} is used as both {} and {}", self.vars[root].what, known, typ), at)
+                    .with_label(known_at, format!("this uses it as {}", known))
+                    .with_note(format!("here it is used as {}", typ)))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// make two types the same, `at` being the use that requires it
+    fn unify(&mut self, ty0: &Ty, ty1: &Ty, at: Token) -> Result<(), Error> {
+        let root = |ty: &Ty| match *ty {
+            Ty::Var(var) => Some(self.find(var)),
+            Ty::Known(_) => None,
+        };
+        match (root(ty0), root(ty1), ty0, ty1) {
+            // a plain mismatch between known types is left for the type checker to report
+            (None, None, _, _) => Ok(()),
+            (Some(var), None, _, &Ty::Known(ref typ)) | (None, Some(var), &Ty::Known(ref typ), _) => self.settle(var, typ.clone(), at),
+            (Some(var0), Some(var1), _, _) if var0 == var1 => Ok(()),
+            (Some(var0), Some(var1), _, _) => {
+                let (root, child) = (var0.min(var1), var0.max(var1));
+                if let Some((typ, found_at)) = self.vars[child].found.clone() {
+                    self.settle(root, typ, found_at)?;
+                }
+                self.vars[child].parent = root;
+                Ok(())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// whether a value of type `ty` could be given where `expected` is wanted
+    fn compatible(&self, expected: &Ty, ty: &Ty) -> bool {
+        match (self.resolve(expected), self.resolve(ty)) {
+            (Ty::Known(ref expected), Ty::Known(ref typ)) => !Type::is_not(expected, typ),
+            _ => true,
+        }
+    }
+
+    /// unify the type of a value with the type of what it is stored in.
+    /// an integer literal can be stored as a real, which the type checker takes care of
+    fn unify_stored(&mut self, expr: SexprId, ty: &Ty, expected: &Ty) -> Result<(), Error> {
+        let is_int_literal = matches!(*self.m.kind(expr), SexprKind::IntegerLiteral);
+        if is_int_literal && self.resolve(expected) == Ty::Known(Type::Real) {
+            return Ok(());
+        }
+        self.unify(ty, expected, self.m.sexpr(expr).token)
+    }
+
+    fn variable(&mut self, scope_id: ScopeId, name: &str) -> Ty {
+        let (scope_id, var_id) = self.m.resolve_variable(scope_id, name).expect("unresolved variable in inference");
+        if let Some(ty) = self.variables.get(&(scope_id.index, var_id)) {
+            return ty.clone();
+        }
+        let ty = match self.m.scope(scope_id).variable_types[var_id] {
+            Some(ref typ) => Ty::Known(typ.clone()),
+            None => self.fresh(format!("`{}`", name)),
+        };
+        self.variables.insert((scope_id.index, var_id), ty.clone());
+        ty
+    }
+
+    /// the functions visible from this scope with this name
+    fn visible_funcs(&self, scope_id: ScopeId, name: &str) -> Vec<usize> {
+        let scope = self.m.scope(scope_id);
+        let mut funcs = scope.declared_functions
+            .iter()
+            .cloned()
+            .filter(|&func_id| self.m.func_manager.name[func_id] == name)
+            .collect::<Vec<usize>>();
+        if let Some(parent) = scope.parent {
+            funcs.extend(self.visible_funcs(parent, name));
+        }
+        funcs
+    }
+
+    /// the structs visible from this scope with this name
+    fn visible_structs(&self, scope_id: ScopeId, name: &str) -> Vec<usize> {
+        let scope = self.m.scope(scope_id);
+        let mut structs = scope.declared_structs
+            .iter()
+            .cloned()
+            .filter(|&struct_id| self.m.udt_manager.name[struct_id] == name)
+            .collect::<Vec<usize>>();
+        if let Some(parent) = scope.parent {
+            structs.extend(self.visible_structs(parent, name));
+        }
+        structs
+    }
+
+    /// the enums visible from this scope with a variant of this name, with the index of that variant
+    fn visible_variants(&self, scope_id: ScopeId, name: &str) -> Vec<(usize, usize)> {
+        let scope = self.m.scope(scope_id);
+        let mut variants = scope.declared_enums
+            .iter()
+            .filter_map(|&enum_id| self.m.udt_manager.variant_index(enum_id, name).map(|variant| (enum_id, variant)))
+            .collect::<Vec<(usize, usize)>>();
+        if let Some(parent) = scope.parent {
+            variants.extend(self.visible_variants(parent, name));
+        }
+        variants
+    }
+
+    fn enum_type(&self, enum_id: usize) -> Type {
+        Type::CustomType(self.m.udt_manager.name[enum_id].to_string(), enum_id)
+    }
+
+    /// the enum that a matched expression must be: the one it is known to be,
+    /// or otherwise the only one with the variant that the first pattern names
+    fn matched_enum(&mut self, sexpr_id: SexprId, expr: SexprId, ty: &Ty, arms: &[MatchArm]) -> Result<Option<usize>, Error> {
+        if let Ty::Known(typ) = self.resolve(ty) {
+            return Ok(match typ {
+                Type::CustomType(_, enum_id) if self.m.udt_manager.is_enum(enum_id) => Some(enum_id),
+                _ => None,
+            });
+        }
+        let candidates = match arms.iter().find_map(|arm| arm.variant.as_ref()) {
+            Some(name) => self.visible_variants(self.m.scope_of(sexpr_id), name),
+            None => vec![],
+        };
+        if candidates.len() != 1 {
+            return Ok(None);
+        }
+        let enum_id = candidates[0].0;
+        self.unify(ty, &Ty::Known(self.enum_type(enum_id)), self.m.sexpr(expr).token)?;
+        Ok(Some(enum_id))
+    }
+
+    /// the struct that an expression with a field must be: the one it is known to be,
+    /// or otherwise the only struct that has such a field
+    fn struct_with_field(&mut self, expr: SexprId, ty: &Ty, field: &str) -> Result<Option<usize>, Error> {
+        if let Ty::Known(typ) = self.resolve(ty) {
+            return Ok(match typ {
+                Type::CustomType(_, struct_id) if self.m.udt_manager.args[struct_id].iter().any(|arg| arg == field) => Some(struct_id),
+                _ => None,
+            });
+        }
+        let candidates = (0..self.m.udt_manager.name.len())
+            .filter(|&struct_id| self.m.udt_manager.args[struct_id].iter().any(|arg| arg == field))
+            .collect::<Vec<usize>>();
+        if candidates.len() != 1 {
+            return Ok(None);
+        }
+        let struct_id = candidates[0];
+        let typ = Type::CustomType(self.m.udt_manager.name[struct_id].to_string(), struct_id);
+        self.unify(ty, &Ty::Known(typ), self.m.sexpr(expr).token)?;
+        Ok(Some(struct_id))
+    }
+
+    fn field_type(&self, struct_id: usize, field: &str) -> Ty {
+        let offset = self.m.udt_manager.get_field_offset(struct_id, field);
+        Ty::Known(self.m.udt_manager.sgntr[struct_id][offset].unwrap_clone())
+    }
+
+    /// a variable for each type parameter in a generic function's signature, the same one each time it comes up.
+    /// a type that only has a parameter inside of it, like `list<T>`, can be anything as far as inference goes
+    fn pick_generics(&mut self, ty: &Ty, picked: &mut HashMap<String, Ty>) -> Ty {
+        match *ty {
+            Ty::Known(Type::Generic(ref name)) => {
+                if !picked.contains_key(name) {
+                    let var = self.fresh(format!("type parameter `{}`", name));
+                    picked.insert(name.to_string(), var);
+                }
+                picked[name].clone()
+            }
+            Ty::Known(ref typ) if typ.is_generic() => self.fresh(format!("`{}`", typ)),
+            _ => ty.clone(),
+        }
+    }
+
+    /// a function as a value, which is only known once its whole signature is
+    fn func_value(&mut self, func_id: usize) -> Ty {
+        let params = self.params[func_id].iter().map(|param| self.resolve(param)).collect::<Vec<Ty>>();
+        let out = self.resolve(&self.outs[func_id]);
+        let mut types = vec![];
+        for ty in params.into_iter().chain(Some(out)) {
+            match ty {
+                Ty::Known(typ) => types.push(typ),
+                Ty::Var(_) => return self.fresh(format!("`{}`", self.m.func_manager.title(func_id))),
+            }
+        }
+        let out = types.pop().unwrap();
+        Ty::Known(Type::Func(types, Box::new(out)))
+    }
+
+    /// the body of a function gives back what the function returns
+    fn infer_func_body(&mut self, func_id: usize, token: Token) -> Result<(), Error> {
+        let m = self.m;
+        let body = m.func_manager.body[func_id];
+        let body_ty = self.infer(body)?;
+        // point at what the body ends with, which is what it returns
+        let at = match *m.kind(body) {
+            SexprKind::Block { ref statements } => statements.back().map_or(token, |&last| m.sexpr(last).token),
+            _ => token,
+        };
+        let out = self.outs[func_id].clone();
+        self.unify(&body_ty, &out, at)
+    }
+
+    /// a call to a function, struct initializer or builtin that has not been resolved yet.
+    /// when only one of them could take these arguments, the arguments have to be what it takes
+    fn infer_call(&mut self, sexpr_id: SexprId, exprs: &[SexprId]) -> Result<Ty, Error> {
+        let args = exprs.iter().map(|&expr| self.infer(expr)).collect::<Result<Vec<Ty>, Error>>()?;
+        let tokens = exprs.iter().map(|&expr| self.m.sexpr(expr).token).collect::<Vec<Token>>();
+        let scope_id = self.m.scope_of(sexpr_id);
+        let name = self.m.text(sexpr_id);
+        if self.m.resolve_variable(scope_id, name).is_some() {
+            // calling a function value
+            let callee = self.variable(scope_id, name);
+            return match self.resolve(&callee) {
+                Ty::Known(Type::Func(ref params, ref out)) if params.len() == args.len() => {
+                    for ((param, arg), &token) in params.iter().zip(args.iter()).zip(tokens.iter()) {
+                        self.unify(arg, &Ty::Known(param.clone()), token)?;
+                    }
+                    Ok(Ty::Known((**out).clone()))
+                }
+                _ => Ok(self.fresh(format!("the result of `{}`", name))),
+            };
+        }
+        self.infer_call_with(scope_id, name, &args, &tokens)
+    }
+
+    /// what calling `name` with arguments of these types gives, `tokens` being where each argument is
+    fn infer_call_with(&mut self, scope_id: ScopeId, name: &str, args: &[Ty], tokens: &[Token]) -> Result<Ty, Error> {
+
+        // everything the call could be, as what it takes and what it gives
+        let mut candidates = vec![];
+        for func_id in self.visible_funcs(scope_id, name) {
+            if self.m.func_manager.is_generic(func_id) {
+                // every call picks its own types for the type parameters
+                let mut picked = HashMap::new();
+                let params = self.params[func_id].clone().iter().map(|param| self.pick_generics(param, &mut picked)).collect();
+                let out = self.pick_generics(&self.outs[func_id].clone(), &mut picked);
+                candidates.push((params, out));
+            } else {
+                candidates.push((self.params[func_id].clone(), self.outs[func_id].clone()));
+            }
+        }
+        for struct_id in self.visible_structs(scope_id, name) {
+            let params = self.m.udt_manager.sgntr[struct_id].iter().map(|typ| Ty::Known(typ.unwrap_clone())).collect();
+            candidates.push((params, Ty::Known(Type::CustomType(name.to_string(), struct_id))));
+        }
+        for (enum_id, variant) in self.visible_variants(scope_id, name) {
+            let params = self.m.udt_manager.variants[enum_id][variant].sgntr.iter().map(|typ| Ty::Known(typ.unwrap_clone())).collect();
+            candidates.push((params, Ty::Known(self.enum_type(enum_id))));
+        }
+        let builtins = &self.m.builtin_manager;
+        for builtin_id in (0..builtins.name.len()).filter(|&builtin_id| builtins.name[builtin_id] == name) {
+            let params = builtins.in_types[builtin_id].iter().cloned().map(Ty::Known).collect();
+            candidates.push((params, Ty::Known(builtins.out_type[builtin_id].clone())));
+        }
+        candidates.retain(|(params, _)| params.len() == args.len());
+        // with only one thing to call, its uses have to agree with each other even when they don't fit it,
+        // so that a clash with what was inferred from another use can point at both
+        if candidates.len() != 1 {
+            candidates.retain(|(params, _)| params.iter().zip(args.iter()).all(|(param, arg)| self.compatible(param, arg)));
+        }
+
+        if candidates.len() == 1 {
+            let (params, out) = candidates.pop().unwrap();
+            for ((param, arg), &token) in params.iter().zip(args.iter()).zip(tokens.iter()) {
+                self.unify(arg, param, token)?;
+            }
+            return Ok(out);
+        }
+        // either it can't be decided yet, or it is an error that the type checker will point out.
+        // the result might still be known when every candidate gives the same type
+        let outs = candidates.iter().map(|(_, out)| self.resolve(out)).collect::<Vec<Ty>>();
+        match outs.first() {
+            Some(Ty::Known(typ)) if outs.iter().all(|out| *out == Ty::Known(typ.clone())) => Ok(Ty::Known(typ.clone())),
+            _ => Ok(self.fresh(format!("the result of `{}`", name))),
+        }
+    }
+
+    /// the type of a list's elements, when the list is known to be one
+    fn element_of(&mut self, list: SexprId, ty: &Ty) -> Ty {
+        match self.resolve(ty) {
+            Ty::Known(Type::List(element_type)) => Ty::Known(*element_type),
+            _ => self.fresh(format!("an element of `{}`", self.m.text(list))),
+        }
+    }
+
+    /// an element being put into a list: it has to be what the list holds,
+    /// and a list that isn't known yet holds what is put into it
+    fn infer_element(&mut self, list: SexprId, list_ty: &Ty, elem: SexprId) -> Result<(), Error> {
+        let elem_ty = self.infer(elem)?;
+        match (self.resolve(list_ty), self.resolve(&elem_ty)) {
+            (Ty::Known(Type::List(element_type)), _) => self.unify_stored(elem, &elem_ty, &Ty::Known(*element_type)),
+            // an integer literal could just as well be going into a list of reals
+            _ if matches!(*self.m.kind(elem), SexprKind::IntegerLiteral) => Ok(()),
+            (Ty::Var(_), Ty::Known(typ)) => self.unify(list_ty, &Ty::Known(Type::List(Box::new(typ))), self.m.sexpr(list).token),
+            _ => Ok(()),
+        }
+    }
+
+    fn infer(&mut self, sexpr_id: SexprId) -> Result<Ty, Error> {
+        let m = self.m;
+        let token = m.sexpr(sexpr_id).token;
+        let kind = m.kind(sexpr_id).clone();
+        Ok(match kind {
+            SexprKind::Declare { ref variable_pattern, ref annotation, expr, body } => {
+                let expr_ty = self.infer(expr)?;
+                let var_ty = match *annotation {
+                    Some(ref annotation) => {
+                        let annotated = Ty::Known(annotation.unwrap_clone());
+                        self.unify_stored(expr, &expr_ty, &annotated)?;
+                        annotated
+                    }
+                    None => expr_ty,
+                };
+                let (scope_id, var_id) = m.resolve_variable(m.scope_of(body), variable_pattern).expect("unresolved variable in inference");
+                self.variables.insert((scope_id.index, var_id), var_ty);
+                self.infer(body)?
+            }
+            SexprKind::Assign { ref variable_pattern, expr } => {
+                let var_ty = self.variable(m.scope_of(sexpr_id), variable_pattern);
+                let expr_ty = self.infer(expr)?;
+                self.unify_stored(expr, &expr_ty, &var_ty)?;
+                var_ty
+            }
+            SexprKind::IfSwitch { predicate, if_branch, else_branch } => {
+                let predicate_ty = self.infer(predicate)?;
+                self.unify(&predicate_ty, &Ty::Known(Type::Boole), m.sexpr(predicate).token)?;
+                let if_ty = self.infer(if_branch)?;
+                let else_ty = self.infer(else_branch)?;
+                self.unify(&if_ty, &else_ty, m.sexpr(else_branch).token)?;
+                if_ty
+            }
+            SexprKind::WhileLoop { predicate, body } => {
+                let predicate_ty = self.infer(predicate)?;
+                self.unify(&predicate_ty, &Ty::Known(Type::Boole), m.sexpr(predicate).token)?;
+                self.infer(body)?
+            }
+            SexprKind::Block { ref statements } => {
+                let mut ty = Ty::Known(Type::Void);
+                for &statement in statements {
+                    ty = self.infer(statement)?;
+                }
+                ty
+            }
+            // generic functions have every type spelled out, and their bodies are checked as each instance
+            SexprKind::FuncDef { func_id } if m.func_manager.is_generic(func_id) => Ty::Known(Type::Void),
+            SexprKind::FuncDef { func_id } => {
+                self.infer_func_body(func_id, token)?;
+                Ty::Known(Type::Void)
+            }
+            SexprKind::Lambda { func_id } => {
+                self.infer_func_body(func_id, token)?;
+                self.func_value(func_id)
+            }
+            SexprKind::StructDef { .. } | SexprKind::EnumDef { .. } => Ty::Known(Type::Void),
+            SexprKind::Match { expr, ref arms, .. } => {
+                let expr_ty = self.infer(expr)?;
+                let enum_id = self.matched_enum(sexpr_id, expr, &expr_ty, arms)?;
+                let mut arms_ty: Option<Ty> = None;
+                for arm in arms {
+                    // the bindings have the types of their fields, when the pattern fits the variant
+                    let variant = enum_id.and_then(|enum_id| arm.variant.as_ref().and_then(|name| m.udt_manager.variant_index(enum_id, name)).map(|variant| &m.udt_manager.variants[enum_id][variant]));
+                    if let Some(variant) = variant.filter(|variant| variant.sgntr.len() == arm.bindings.len()) {
+                        for (binding, typ) in arm.bindings.iter().zip(variant.sgntr.iter()) {
+                            let (scope_id, var_id) = m.resolve_variable(m.scope_of(arm.body), binding).expect("unresolved variable in inference");
+                            self.variables.insert((scope_id.index, var_id), Ty::Known(typ.unwrap_clone()));
+                        }
+                    }
+                    let body_ty = self.infer(arm.body)?;
+                    match arms_ty {
+                        Some(ref arms_ty) => self.unify(&body_ty, arms_ty, m.sexpr(arm.body).token)?,
+                        None => arms_ty = Some(body_ty),
+                    }
+                }
+                arms_ty.unwrap_or(Ty::Known(Type::Void))
+            }
+            SexprKind::StructGet { expr, ref field, .. } => {
+                let expr_ty = self.infer(expr)?;
+                match self.struct_with_field(expr, &expr_ty, field)? {
+                    Some(struct_id) => self.field_type(struct_id, field),
+                    None => self.fresh(format!("field `{}`", field)),
+                }
+            }
+            SexprKind::StructSet { expr, ref field, value, .. } => {
+                let expr_ty = self.infer(expr)?;
+                let value_ty = self.infer(value)?;
+                if let Some(struct_id) = self.struct_with_field(expr, &expr_ty, field)? {
+                    let field_ty = self.field_type(struct_id, field);
+                    self.unify(&value_ty, &field_ty, m.sexpr(value).token)?;
+                }
+                Ty::Known(Type::Void)
+            }
+            SexprKind::List { ref elements } => {
+                let mut first: Option<Ty> = None;
+                for &element in elements {
+                    let ty = self.infer(element)?;
+                    match first {
+                        Some(ref first) => self.unify_stored(element, &ty, first)?,
+                        None => first = Some(ty),
+                    }
+                }
+                match first.map(|ty| self.resolve(&ty)) {
+                    Some(Ty::Known(typ)) => Ty::Known(Type::List(Box::new(typ))),
+                    _ => self.fresh("a list".to_string()),
+                }
+            }
+            SexprKind::ListGet { list, index } => {
+                let list_ty = self.infer(list)?;
+                let index_ty = self.infer(index)?;
+                self.unify(&index_ty, &Ty::Known(Type::Int), m.sexpr(index).token)?;
+                self.element_of(list, &list_ty)
+            }
+            SexprKind::ListSet { list, index, elem } => {
+                let list_ty = self.infer(list)?;
+                let index_ty = self.infer(index)?;
+                self.unify(&index_ty, &Ty::Known(Type::Int), m.sexpr(index).token)?;
+                self.infer_element(list, &list_ty, elem)?;
+                Ty::Known(Type::Void)
+            }
+            SexprKind::ListLen { list } => {
+                self.infer(list)?;
+                Ty::Known(Type::Int)
+            }
+            SexprKind::ListPush { list, elem } => {
+                let list_ty = self.infer(list)?;
+                self.infer_element(list, &list_ty, elem)?;
+                Ty::Known(Type::Void)
+            }
+            SexprKind::ListMap { func, list, .. } => {
+                let list_ty = self.infer(list)?;
+                let element_ty = self.element_of(list, &list_ty);
+                let is_name = matches!(*m.kind(func), SexprKind::Identifier) && m.resolve_variable(m.scope_of(func), m.text(func)).is_none();
+                let out_ty = if is_name {
+                    self.infer_call_with(m.scope_of(sexpr_id), m.text(func), &[element_ty], &[m.sexpr(list).token])?
+                } else {
+                    let func_ty = self.infer(func)?;
+                    match self.resolve(&func_ty) {
+                        Ty::Known(Type::Func(ref params, ref out)) if params.len() == 1 => {
+                            self.unify(&element_ty, &Ty::Known(params[0].clone()), m.sexpr(list).token)?;
+                            Ty::Known((**out).clone())
+                        }
+                        _ => func_ty,
+                    }
+                };
+                match self.resolve(&out_ty) {
+                    Ty::Known(typ) => Ty::Known(Type::List(Box::new(typ))),
+                    _ => self.fresh(format!("the result of mapping `{}`", m.text(func))),
+                }
+            }
+            SexprKind::Format { ref exprs } => {
+                for &expr in exprs {
+                    self.infer(expr)?;
+                }
+                Ty::Known(Type::String)
+            }
+            SexprKind::Other { ref opt_exprs } => {
+                let exprs = opt_exprs.as_ref().expect("other kind opt_exprs should be full while inferring types");
+                self.infer_call(sexpr_id, &exprs.iter().cloned().collect::<Vec<SexprId>>())?
+            }
+            SexprKind::Identifier if m.resolve_variable(m.scope_of(sexpr_id), m.text(sexpr_id)).is_some() => self.variable(m.scope_of(sexpr_id), m.text(sexpr_id)),
+            // a function used as a value
+            SexprKind::Identifier => match self.visible_funcs(m.scope_of(sexpr_id), m.text(sexpr_id))[..] {
+                [func_id] if !m.func_manager.is_generic(func_id) => self.func_value(func_id),
+                _ => self.fresh(format!("`{}`", m.text(sexpr_id))),
+            },
+            SexprKind::RealLiteral => Ty::Known(Type::Real),
+            SexprKind::IntegerLiteral => Ty::Known(Type::Int),
+            SexprKind::StringLiteral => Ty::Known(Type::String),
+            SexprKind::BooleLiteral => Ty::Known(Type::Boole),
+            SexprKind::FuncCall { .. } | SexprKind::StructInit { .. } | SexprKind::EnumInit { .. } | SexprKind::BuiltIn { .. } |
+            SexprKind::FuncRef { .. } | SexprKind::ValueCall { .. } => panic!("inferring types for an expression that should not be resolved yet"),
+        })
+    }
+
+    /// where every function signature stands: each part known, or the variable it is waiting on
+    fn signatures(&self) -> Vec<Ty> {
+        self.params.iter().flatten().chain(self.outs.iter()).map(|ty| self.resolve(ty)).collect()
+    }
+}
+
+impl Manager {
+    /// infer the types that function definitions leave out.
+    /// the whole program is gone over until nothing new is learned, since a use can decide a type
+    /// that makes it possible to decide another one earlier on
+    pub fn infer_signatures(&mut self) -> Result<(), Vec<Error>> {
+        let has_unknowns = self.func_manager.in_types
+            .iter()
+            .flatten()
+            .chain(self.func_manager.out_type.iter())
+            .any(|typ| typ.known().is_none());
+        if !has_unknowns {
+            return Ok(());
+        }
+
+        let (params, outs) = {
+            let mut inference = Inference::new(self);
+            loop {
+                let before = inference.signatures();
+                inference.variables.clear();
+                for func_id in 0..self.func_manager.count {
+                    let body = self.func_manager.body[func_id];
+                    for (arg, param) in self.func_manager.args[func_id].iter().zip(inference.params[func_id].clone()) {
+                        let (scope_id, var_id) = self.resolve_variable(self.scope_of(body), arg).expect("unbound function argument");
+                        inference.variables.insert((scope_id.index, var_id), param);
+                    }
+                }
+                for &sexpr_id in self.top_level_sexprs.iter() {
+                    inference.infer(sexpr_id).map_err(|error| vec![error])?;
+                }
+                if inference.signatures() == before {
+                    break;
+                }
+            }
+            let params = inference.params.iter().map(|params| params.iter().map(|ty| inference.resolve(ty)).collect::<Vec<Ty>>()).collect::<Vec<Vec<Ty>>>();
+            let outs = inference.outs.iter().map(|ty| inference.resolve(ty)).collect::<Vec<Ty>>();
+            (params, outs)
+        };
+
+        let mut errors = vec![];
+        for func_id in 0..self.func_manager.count {
+            let body = self.func_manager.body[func_id];
+            for (index, param) in params[func_id].iter().enumerate() {
+                let arg = self.func_manager.args[func_id][index].clone();
+                match *param {
+                    Ty::Known(ref typ) if self.func_manager.in_types[func_id][index].known().is_none() => {
+                        self.func_manager.in_types[func_id][index] = FutureType::Complete(typ.clone());
+                        let (scope_id, var_id) = self.resolve_variable(self.scope_of(body), &arg).expect("unbound function argument");
+                        self.scope_mut(scope_id).variable_types[var_id] = Some(typ.clone());
+                    }
+                    Ty::Known(_) => {}
+                    Ty::Var(_) => {
+                        let at = self.func_manager.in_types[func_id][index].token();
+                        errors.push(Error::new(ErrorCode::CannotInferType, format!("could not infer the type of argument `{}` of `{}`", arg, self.func_manager.name[func_id]), at)
+                            .with_note(format!("give it a type, like `{}: int`", arg)));
+                    }
+                }
+            }
+            match outs[func_id] {
+                Ty::Known(ref typ) => self.func_manager.out_type[func_id] = FutureType::Complete(typ.clone()),
+                Ty::Var(_) => {
+                    let at = self.func_manager.out_type[func_id].token();
+                    errors.push(Error::new(ErrorCode::CannotInferType, format!("could not infer the return type of `{}`", self.func_manager.name[func_id]), at)
+                        .with_note("give it one after the arguments, like `-> int`".to_string()));
+                }
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {tokenizer, parser, scoping};
+
+    /// everything up to and including inference, which has to get that far
+    fn infer(source: &str) -> Result<Manager, Vec<Error>> {
+        let mut m = Manager::new(source.to_string());
+        let tokens = tokenizer::tokenize(&m.source).expect("should lex");
+        parser::generate_global_sexprs(&mut m, tokens).expect("should parse");
+        m.initialize_type_info().expect("should have its types");
+        scoping::create_all_scopes(&mut m).expect("should scope");
+        m.infer_signatures()?;
+        Ok(m)
+    }
+
+    /// each function's signature after inference, like `sum: int int -> int`
+    fn signatures(source: &str) -> Vec<String> {
+        let m = infer(source).unwrap_or_else(|errors| panic!("{}", errors.iter().map(|error| error.why.as_str()).collect::<Vec<&str>>().join("\n")));
+        let show = |typ: &FutureType| typ.known().map_or("?".to_string(), Type::to_string);
+        (0..m.func_manager.count)
+            .map(|func_id| {
+                let params = m.func_manager.in_types[func_id].iter().map(show).collect::<Vec<String>>().join(" ");
+                format!("{}: {} -> {}", m.func_manager.name[func_id], params, show(&m.func_manager.out_type[func_id]))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn return_type_from_a_call_site() {
+        // nothing in the body says what `nothing` gives back, only what its caller does with it
+        let source = "(func nothing x: int (block x))\n(func wrap -> int (nothing 1))";
+        assert_eq!(signatures(source), vec!["nothing: int -> int", "wrap:  -> int"]);
+        let source = "(func pass v (block v))\n(print (add (pass 1.5) 2.0))";
+        assert_eq!(signatures(source), vec!["pass: real -> real"]);
+    }
+
+    #[test]
+    fn argument_type_from_a_later_caller() {
+        // `first` says nothing about `x`, and `greet` only calls it further down
+        let source = "(func first x (second x))\n(func second y (block y))\n(func greet -> string (first \"hi\"))";
+        assert_eq!(signatures(source), vec!["first: string -> string", "second: string -> string", "greet:  -> string"]);
+    }
+
+    #[test]
+    fn mutual_recursion_settles_over_several_rounds() {
+        // two structs have an `x`, so `get-field p x` can't be worked out until the call at the end says what `p` is,
+        // and only the round after that can say what `even` and `odd` return
+        let source = "\
+(struct point x: int y: int)
+(struct pair x: string y: string)
+(func even p n (if (equals n 0) (get-field p x) (odd p (sub n 1))))
+(func odd q n (if (equals n 0) (get-field q x) (even q (sub n 1))))
+(print (even (point 1 2) 3))";
+        assert_eq!(signatures(source), vec!["even: point int -> int", "odd: point int -> int"]);
+    }
+
+    #[test]
+    fn conflict_is_one_error_labelling_both_uses() {
+        let source = "(func both y (block (add y 1) (format \"a\" (and y true))))";
+        let errors = match infer(source) {
+            Ok(_) => panic!("should not infer"),
+            Err(errors) => errors,
+        };
+        assert_eq!(errors.len(), 1);
+        let error = &errors[0];
+        assert_eq!(error.code, ErrorCode::TypeMismatch);
+        assert_eq!(error.why, "argument `y` of `both` is used as both int and boole");
+        // the `y` given to `and`, then the `y` given to `add`
+        let and_y = source.find("and y").unwrap() + 4;
+        let add_y = source.find("add y").unwrap() + 4;
+        assert_eq!(error.ats.iter().map(|at| at.begin()).collect::<Vec<usize>>(), vec![and_y]);
+        assert_eq!(error.labels.iter().map(|&(at, ref message)| (at.begin(), message.as_str())).collect::<Vec<(usize, &str)>>(), vec![(add_y, "this uses it as int")]);
+    }
+}
@@ -1,3 +1,4 @@
+// the crate is named after the project
 #![allow(non_snake_case)]
 
 extern crate take_mut;
@@ -10,103 +11,481 @@ mod type_checker;
 mod dependencies;
 mod sexpr;
 mod scoping;
-mod builder;
+mod inference;
+mod code_gen;
+mod ti_tokens;
+mod eight_xp;
 mod mir;
-mod optimize;
+mod simulator;
+mod evaluator;
 mod manager;
+mod number;
+mod formatter;
+mod dump;
+#[cfg(test)]
+mod tests;
 
 use std::env;
 use std::fs::File;
 use std::path::Path;
 use std::io::prelude::*;
 use std::io::stdout;
+use std::process::exit;
+use std::thread;
 
-fn read_arguments() -> (String, Option<String>, bool) {
-    // actual version
-    // env::args().nth(1).ok_or(Error::new(ErrorKind::NotFound, "missing command line argument"))?;
-    let in_path = env::args().nth(1).unwrap_or("test.txt".to_owned());
+const USAGE: &str = "\
+usage: BasicTranspilation <command> [options] FILE
+
+commands:
+    build        compile FILE to TI-84 Basic
+    check        parse, scope and type check FILE without compiling it
+    run          run FILE directly, without compiling it
+    dump-ast     print FILE's s-expressions as the type checker left them
+    dump-mir     print FILE's medium internal representation
+    fmt          print FILE's source laid out the standard way
+    simulate     run a .8xp file, or TI-84 Basic source text, on a simulated calculator
+    decompile    print the TI-84 Basic source held in a .8xp file
+
+options:
+    -o, --output FILE         write the output to FILE instead of stdout
+    --emit=text|8xp           what build writes (default: 8xp when FILE ends in .8xp, otherwise text)
+    --program-name=NAME       the name of the program in a .8xp file (default: from FILE's name)
+    --inline-threshold=N      put functions of at most N statements in place of every call (default: 4);
+                              one called from a single place is put there whatever N is
+    --error-format=human|json how errors are shown on stderr
+    --dump=STAGE,...          print what the stages make as they finish: tokens, ast, scopes, types and mir
+    -d, --debug               dump every stage
+    -h, --help                print this message";
+
+/// the program we were given has errors, or couldn't be read or written
+const EXIT_FAILURE: i32 = 1;
+/// we were called the wrong way
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Command {
+    Build,
+    Check,
+    Run,
+    DumpAst,
+    DumpMir,
+    Fmt,
+    Simulate,
+    Decompile,
+}
+
+#[derive(Debug, PartialEq)]
+enum Emit {
+    Text,
+    EightXp,
+}
+
+/// a stage whose output can be printed as it finishes
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Dump {
+    Tokens,
+    Ast,
+    Scopes,
+    Types,
+    Mir,
+}
+const ALL_DUMPS: [Dump; 5] = [Dump::Tokens, Dump::Ast, Dump::Scopes, Dump::Types, Dump::Mir];
+
+/// how errors are shown: the human readout, or a JSON object per line for editors
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+struct Arguments {
+    command: Command,
+    in_path: String,
+    maybe_out_path: Option<String>,
+    dumps: Vec<Dump>,
+    emit: Emit,
+    maybe_program_name: Option<String>,
+    error_format: ErrorFormat,
+    inline_threshold: usize,
+}
+
+/// stop because we were called the wrong way
+fn usage_error(why: String) -> ! {
+    eprintln!("error: {}\n\nrun with --help to see how to use this", why);
+    exit(EXIT_USAGE)
+}
+
+fn read_arguments(args: Vec<String>) -> Arguments {
+    if args.is_empty() {
+        eprintln!("{}", USAGE);
+        exit(EXIT_USAGE);
+    }
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        exit(0);
+    }
+    let command = match args[0].as_ref() {
+        "build" => Command::Build,
+        "check" => Command::Check,
+        "run" => Command::Run,
+        "dump-ast" => Command::DumpAst,
+        "dump-mir" => Command::DumpMir,
+        "fmt" => Command::Fmt,
+        "simulate" => Command::Simulate,
+        "decompile" => Command::Decompile,
+        other => usage_error(format!("unknown command `{}`", other)),
+    };
+    let mut maybe_in_path = None;
     let mut maybe_out_path: Option<String> = None;
-    let mut debug = false;
-    for arg in env::args().into_iter().skip(2) {
+    let mut dumps = vec![];
+    let mut maybe_emit = None;
+    let mut maybe_program_name = None;
+    let mut error_format = ErrorFormat::Human;
+    let mut inline_threshold = mir::DEFAULT_INLINE_THRESHOLD;
+    let mut rest = args.into_iter().skip(1);
+    while let Some(arg) = rest.next() {
         match arg.as_ref() {
-            "-D" | "-d" if !debug => {
-                debug = true;
+            "-d" | "-D" | "--debug" => {
+                dumps.extend_from_slice(&ALL_DUMPS);
+            }
+            "-o" | "--output" => {
+                maybe_out_path = Some(rest.next().unwrap_or_else(|| usage_error(format!("`{}` needs a file to write to", arg))));
+            }
+            "--emit=text" => {
+                maybe_emit = Some(Emit::Text);
+            }
+            "--emit=8xp" => {
+                maybe_emit = Some(Emit::EightXp);
+            }
+            "--error-format=human" => {
+                error_format = ErrorFormat::Human;
+            }
+            "--error-format=json" => {
+                error_format = ErrorFormat::Json;
+            }
+            _ if arg.starts_with("--dump=") => {
+                for stage in arg["--dump=".len()..].split(',') {
+                    dumps.push(match stage {
+                        "tokens" => Dump::Tokens,
+                        "ast" => Dump::Ast,
+                        "scopes" => Dump::Scopes,
+                        "types" => Dump::Types,
+                        "mir" => Dump::Mir,
+                        _ => usage_error(format!("there is no `{}` stage to dump", stage)),
+                    });
+                }
+            }
+            _ if arg.starts_with("--output=") => {
+                maybe_out_path = Some(arg["--output=".len()..].to_string());
+            }
+            _ if arg.starts_with("--program-name=") => {
+                maybe_program_name = Some(arg["--program-name=".len()..].to_string());
+            }
+            _ if arg.starts_with("--inline-threshold=") => {
+                let threshold = &arg["--inline-threshold=".len()..];
+                inline_threshold = threshold.parse().unwrap_or_else(|_| usage_error(format!("`{}` is not a number of statements", threshold)));
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                usage_error(format!("unknown option `{}`", arg));
+            }
+            _ if maybe_in_path.is_some() => {
+                usage_error(format!("unexpected argument `{}`: only one file can be given", arg));
             }
             _ => {
-                maybe_out_path = Some(arg.clone());
+                maybe_in_path = Some(arg.clone());
             }
         }
     }
-    (in_path, maybe_out_path, debug)
+    let in_path = maybe_in_path.unwrap_or_else(|| usage_error("missing the file to work on".to_string()));
+    // writing to a .8xp file means we want the binary format, unless we were told otherwise
+    let emit = maybe_emit.unwrap_or(
+        if maybe_out_path.as_ref().is_some_and(|path| path.to_lowercase().ends_with(".8xp")) { Emit::EightXp } else { Emit::Text }
+    );
+    Arguments { command, in_path, maybe_out_path, dumps, emit, maybe_program_name, error_format, inline_threshold }
 }
 
-fn read_file(path: String) -> Result<String, std::io::Error> {
+/// stop because a file couldn't be read or written
+fn io_error(doing: &str, path: &str, error: std::io::Error) -> ! {
+    eprintln!("error: could not {} `{}`: {}", doing, path, error);
+    exit(EXIT_FAILURE)
+}
+
+fn read_file(path: &str) -> String {
     let mut source = String::new();
-    let mut file = match File::open(path) {
-        Ok(f)   => f,
-        Err(e)  => {
-            return Err(e);
-        }
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .unwrap_or_else(|error| io_error("read", path, error));
+    source
+}
+
+fn read_bytes(path: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .unwrap_or_else(|error| io_error("read", path, error));
+    bytes
+}
+
+fn write_or_display(maybe_path: &Option<String>, bytes: &[u8]) {
+    let result = match maybe_path {
+        Some(ref path) => File::create(Path::new(path)).and_then(|mut file| file.write_all(bytes)),
+        None => stdout().write_all(bytes),
     };
-    match file.read_to_string(&mut source) {
-        Ok(_)   => {},
-        Err(e)  => {
-            return Err(e);
-        }
+    result.unwrap_or_else(|error| io_error("write", maybe_path.as_deref().unwrap_or("stdout"), error));
+}
+
+/// show an error the way we were asked to, on stderr so it never mixes with what we write to stdout
+fn show_error(error: &util::Error, m: &manager::Manager, error_format: ErrorFormat) {
+    match error_format {
+        ErrorFormat::Human => eprintln!("{}\n", error.get_readout(m)),
+        ErrorFormat::Json => eprintln!("{}", error.get_json(m)),
     }
-    return Ok(source);
 }
 
-fn write_or_display(maybe_path: Option<String>, text: String) -> Result<(), std::io::Error> {
-    let mut out_writer: Box<Write> = match maybe_path {
-        Some(ref x) => Box::new(File::create(&Path::new(x))?),
-        None => Box::new(stdout()),
-    };
-    out_writer.write(text.as_bytes())?;
-    Ok(())
+/// print every diagnostic a stage gave back, returning whether any of them were errors
+fn report_errors(stage: util::Stage, result: Result<(), Vec<util::Error>>, m: &manager::Manager, error_format: ErrorFormat) -> bool {
+    match result {
+        Ok(()) => false,
+        Err(errors) => {
+            let failed = errors.iter().any(util::Error::is_error);
+            for error in errors {
+                show_error(&error.in_stage(stage), m, error_format);
+            }
+            failed
+        }
+    }
 }
 
-fn main() {
-    let (in_path, maybe_out_path, debug) = read_arguments();
+/// show the error from a stage after analysis and stop
+fn fail(stage: util::Stage, error: util::Error, m: &manager::Manager, error_format: ErrorFormat) -> ! {
+    show_error(&error.in_stage(stage), m, error_format);
+    exit(EXIT_FAILURE)
+}
 
-    println!("compiling {in_path} to {out}\nwith flags: debug = {debug}\n------------------------------------",
-        in_path = in_path,
-        out = maybe_out_path.as_ref().map(|x| &**x).unwrap_or("stdout"),
-        debug = debug
-    );
+/// a manager with no source, for pointing at errors in files that aren't programs of ours
+fn empty_manager(file_name: &str) -> manager::Manager {
+    let mut m = manager::Manager::new(String::new());
+    m.file_name = file_name.to_string();
+    m
+}
 
-    let source = read_file(in_path)
-        .map_err(|error| return println!("{}", error))
-        .unwrap();
+/// print a stage's output if we were asked to
+fn dump(args: &Arguments, stage: Dump, show: impl FnOnce() -> String) {
+    if args.dumps.contains(&stage) {
+        println!("=== {} ===\n{}", format!("{:?}", stage).to_lowercase(), show());
+    }
+}
 
+/// parse, scope and type check a program, showing every error found before giving up
+fn analyze(args: &Arguments, source: String) -> manager::Manager {
+    let error_format = args.error_format;
     let mut m = manager::Manager::new(source);
+    m.file_name = args.in_path.clone();
+
+    // without the right tokens there is nothing sensible to parse
+    let tokens = tokenizer::tokenize(&m.source).unwrap_or_else(|errors| {
+        report_errors(util::Stage::Lexing, Err(errors), &m, error_format);
+        exit(EXIT_FAILURE)
+    });
+    dump(args, Dump::Tokens, || dump::dump_tokens(&m.source, &tokens));
+
+    let result = parser::generate_global_sexprs(&mut m, tokens);
+    let parsed = result.is_ok();
+    let mut failed = report_errors(util::Stage::Parsing, result, &m, error_format);
+
+    // nothing after this can work with types that don't exist
+    let result = m.initialize_type_info();
+    if report_errors(util::Stage::TypeInfo, result, &m, error_format) {
+        exit(EXIT_FAILURE);
+    }
+    if parsed {
+        dump(args, Dump::Ast, || dump::dump_ast(&m));
+    }
+
+    // both of these skip past the top level s-exprs that fail, so keep going to find what else is wrong
+    let result = scoping::create_all_scopes(&mut m);
+    failed |= report_errors(util::Stage::Scoping, result, &m, error_format);
+
+    let result = type_checker::type_check_all(&mut m);
+    if result.is_ok() && !failed {
+        // the variables in the scopes only have types once type checking is done
+        dump(args, Dump::Scopes, || dump::dump_scopes(&m));
+        dump(args, Dump::Types, || dump::dump_types(&m));
+    }
+    failed |= report_errors(util::Stage::TypeChecking, result, &m, error_format);
+
+    // warnings never stop compilation, but they are worth seeing either way
+    for warning in m.warnings.iter() {
+        show_error(warning, &m, error_format);
+    }
+    if failed {
+        exit(EXIT_FAILURE);
+    }
+    m
+}
+
+/// `decompile FILE`: print the TI-84 Basic source held in a .8xp file
+fn decompile(args: Arguments) {
+    let file = read_bytes(&args.in_path);
+
+    // none of these errors point into source code, so there is nothing for a manager to underline
+    let m = empty_manager(&args.in_path);
+    let (name, text) = eight_xp::read_8xp(&file)
+        .and_then(|(name, tokens)| Ok((name, ti_tokens::detokenize_basic(&tokens)?)))
+        .unwrap_or_else(|error| fail(util::Stage::Output, error, &m, args.error_format));
+
+    if args.maybe_out_path.is_none() {
+        println!("prgm{}\n------------------------------------", name);
+    }
+    write_or_display(&args.maybe_out_path, format!("{}\n", text).as_bytes());
+}
+
+/// `simulate FILE`: run a .8xp file, or TI-84 Basic source text, and show what it displays
+fn simulate(args: Arguments) {
+    let file = read_bytes(&args.in_path);
+
+    let m = empty_manager(&args.in_path);
+    let text = if args.in_path.to_lowercase().ends_with(".8xp") {
+        eight_xp::read_8xp(&file).and_then(|(_, tokens)| ti_tokens::detokenize_basic(&tokens))
+    } else {
+        String::from_utf8(file).map_err(|_| util::Error::new_zero(util::ErrorCode::UnreadableFile, format!("{} is not valid UTF-8", args.in_path)))
+    }.unwrap_or_else(|error| fail(util::Stage::Output, error, &m, args.error_format));
+
+    let simulation = simulator::simulate(&text)
+        .unwrap_or_else(|error| fail(util::Stage::Output, error, &m, args.error_format));
+    for line in simulation.display {
+        println!("{}", line);
+    }
+    if let Some(error) = simulation.error {
+        eprintln!("------------------------------------\n{}", error);
+        exit(EXIT_FAILURE);
+    }
+}
+
+/// `run FILE`: run a program directly, without compiling it to TI-84 Basic
+fn run(args: Arguments) {
+    // the evaluator recurses for every call, deeper than the main thread's stack goes
+    thread::Builder::new()
+        .stack_size(evaluator::STACK_SIZE)
+        .spawn(move || run_here(args))
+        .unwrap_or_else(|error| io_error("start a thread to run", "the program", error))
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
 
-    let tokens = tokenizer::tokenize(&m.source);
+fn run_here(args: Arguments) {
+    let source = read_file(&args.in_path);
+    let mut m = analyze(&args, source);
+    // the program is run as it would be compiled, so its calls take up as much of ⌊ARGS as they would on the calculator
+    let mir = mir::make_all_mir(&mut m, args.inline_threshold)
+        .unwrap_or_else(|error| fail(util::Stage::Mir, error, &m, args.error_format));
 
-    parser::generate_global_sexprs(&mut m, tokens)
-        .map_err(|error| return println!("Error lexing:\n{}", error.get_readout(&m))).unwrap();
+    let evaluation = evaluator::evaluate(&m, &mir);
+    for line in evaluation.display {
+        println!("{}", line);
+    }
+    if let Some(error) = evaluation.error {
+        if args.error_format == ErrorFormat::Human {
+            eprintln!("------------------------------------");
+        }
+        show_error(&error, &m, args.error_format);
+        exit(EXIT_FAILURE);
+    }
+}
 
-    m.initialize_type_info()
-        .map_err(|error| return println!("Error in initializing the user defined type info:\n{}", error.get_readout(&m))).unwrap();
+/// `fmt FILE`: lay the source out the standard way
+fn fmt(args: Arguments) {
+    let mut m = manager::Manager::new(read_file(&args.in_path));
+    m.file_name = args.in_path.clone();
+    let tokens = tokenizer::tokenize_keeping_comments(&m.source).unwrap_or_else(|errors| {
+        report_errors(util::Stage::Lexing, Err(errors), &m, args.error_format);
+        exit(EXIT_FAILURE)
+    });
+    let formatted = formatter::format_source(&m.source, &tokens)
+        .unwrap_or_else(|error| fail(util::Stage::Parsing, error, &m, args.error_format));
+    write_or_display(&args.maybe_out_path, formatted.as_bytes());
+}
 
-    scoping::create_all_scopes(&mut m)
-        .map_err(|error| return println!("Error in scoping:\n{}", error.get_readout(&m))).unwrap();
+/// `dump-ast FILE`: print the s-exprs as the type checker left them
+fn dump_ast(args: Arguments) {
+    let source = read_file(&args.in_path);
+    let m = analyze(&args, source);
+    write_or_display(&args.maybe_out_path, dump::dump_ast(&m).as_bytes());
+}
 
-    type_checker::type_check_all(&mut m)
-        .map_err(|error| return println!("Error in type checking:\n{}", error.get_readout(&m))).unwrap();
+/// `dump-mir FILE`: print the medium internal representation
+fn dump_mir(args: Arguments) {
+    let source = read_file(&args.in_path);
+    let mut m = analyze(&args, source);
+    let mir = mir::make_all_mir(&mut m, args.inline_threshold)
+        .unwrap_or_else(|error| fail(util::Stage::Mir, error, &m, args.error_format));
+    write_or_display(&args.maybe_out_path, dump::dump_mir(&m, &mir).as_bytes());
+}
 
-    mir::make_all_mir(&mut m)
-        .map_err(|error| return println!("Error in generation medium internal representation:\n{}", error.get_readout(&m))).unwrap();
+/// `check FILE`: stop after type checking
+fn check(args: Arguments) {
+    let source = read_file(&args.in_path);
+    analyze(&args, source);
+}
 
-    let text = "".into(); // builder::build_global_sexprs(&mut m)
-   //     .map_err(|error| return println!("Error in building:\n{}", error.get_readout(&m))).unwrap();
+/// `build FILE`: compile to TI-84 Basic, as text or a .8xp file
+fn build(args: Arguments) {
+    let error_format = args.error_format;
 
-    if debug {
-        println!("{:#?}", m)
+    // tools reading json want nothing but the errors on stderr
+    let chatty = error_format == ErrorFormat::Human;
+    if chatty {
+        eprintln!("compiling {in_path} to {out}\nwith flags: dump = {dumps:?}, emit = {emit:?}\n------------------------------------",
+            in_path = args.in_path,
+            out = args.maybe_out_path.as_deref().unwrap_or("stdout"),
+            dumps = args.dumps,
+            emit = args.emit
+        );
     }
 
-    write_or_display(maybe_out_path, text)
-        .map_err(|error| return println!("{}", error)).unwrap();
+    // the program is named after the input file unless we were given a name
+    let program_name = args.maybe_program_name.clone().unwrap_or_else(|| eight_xp::program_name_from(
+        Path::new(&args.in_path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("")
+    ));
+
+    let source = read_file(&args.in_path);
 
-    println!("------------------------------------\ncompilation was successful");
+    let mut m = analyze(&args, source);
+
+    let mir = mir::make_all_mir(&mut m, args.inline_threshold)
+        .unwrap_or_else(|error| fail(util::Stage::Mir, error, &m, error_format));
+    dump(&args, Dump::Mir, || dump::dump_mir(&m, &mir));
+
+    let text = code_gen::build_program(&m, &mir)
+        .unwrap_or_else(|error| fail(util::Stage::CodeGen, error, &m, error_format));
+
+    let bytes = match args.emit {
+        Emit::Text => text.into_bytes(),
+        Emit::EightXp => {
+            // a trailing newline would only leave an empty last line on the calculator
+            ti_tokens::tokenize_basic(text.trim_end_matches('\n'))
+                .and_then(|tokens| eight_xp::make_8xp(&program_name, &tokens))
+                .unwrap_or_else(|error| fail(util::Stage::Output, error, &m, error_format))
+        }
+    };
+
+    write_or_display(&args.maybe_out_path, &bytes);
+
+    if chatty {
+        eprintln!("------------------------------------\ncompilation was successful");
+    }
+}
+
+fn main() {
+    let args = read_arguments(env::args().skip(1).collect());
+    match args.command {
+        Command::Build => build(args),
+        Command::Check => check(args),
+        Command::Run => run(args),
+        Command::DumpAst => dump_ast(args),
+        Command::DumpMir => dump_mir(args),
+        Command::Fmt => fmt(args),
+        Command::Simulate => simulate(args),
+        Command::Decompile => decompile(args),
+    }
 }
@@ -3,13 +3,14 @@ use type_checker::Type;
 use type_checker::FutureType;
 use sexpr::{SexprId, Sexpr, SexprKind};
 use scope::{Scope, ScopeId};
-use util::Error;
+use util::{Error, ErrorCode};
 
 use std::cell::{RefCell, Ref, RefMut};
+use std::collections::HashMap;
 
 
 
-const LABELS: [&str; 702] = ["A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z", "AA", "AB", "AC", "AD", "AE", "AF", "AG", "AH", "AI", "AJ", "AK", "AL", "AM", "AN", "AO", "AP", "AQ", "AR", "AS", "AT", "AU", "AV", "AW", "AX", "AY", "AZ", "BA", "BB", "BC", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BK", "BL", "BM", "BN", "BO", "BP", "BQ", "BR", "BS", "BT", "BU", "BV", "BW", "BX", "BY", "BZ", "CA", "CB", "CC", "CD", "CE", "CF", "CG", "CH", "CI", "CJ", "CK", "CL", "CM", "CN", "CO", "CP", "CQ", "CR", "CS", "CT", "CU", "CV", "CW", "CX", "CY", "CZ", "DA", "DB", "DC", "DD", "DE", "DF", "DG", "DH", "DI", "DJ", "DK", "DL", "DM", "DN", "DO", "DP", "DQ", "DR", "DS", "DT", "DU", "DV", "DW", "DX", "DY", "DZ", "EA", "EB", "EC", "ED", "EE", "EF", "EG", "EH", "EI", "EJ", "EK", "EL", "EM", "EN", "EO", "EP", "EQ", "ER", "ES", "ET", "EU", "EV", "EW", "EX", "EY", "EZ", "FA", "FB", "FC", "FD", "FE", "FF", "FG", "FH", "FI", "FJ", "FK", "FL", "FM", "FN", "FO", "FP", "FQ", "FR", "FS", "FT", "FU", "FV", "FW", "FX", "FY", "FZ", "GA", "GB", "GC", "GD", "GE", "GF", "GG", "GH", "GI", "GJ", "GK", "GL", "GM", "GN", "GO", "GP", "GQ", "GR", "GS", "GT", "GU", "GV", "GW", "GX", "GY", "GZ", "HA", "HB", "HC", "HD", "HE", "HF", "HG", "HH", "HI", "HJ", "HK", "HL", "HM", "HN", "HO", "HP", "HQ", "HR", "HS", "HT", "HU", "HV", "HW", "HX", "HY", "HZ", "IA", "IB", "IC", "ID", "IE", "IF", "IG", "IH", "II", "IJ", "IK", "IL", "IM", "IN", "IO", "IP", "IQ", "IR", "IS", "IT", "IU", "IV", "IW", "IX", "IY", "IZ", "JA", "JB", "JC", "JD", "JE", "JF", "JG", "JH", "JI", "JJ", "JK", "JL", "JM", "JN", "JO", "JP", "JQ", "JR", "JS", "JT", "JU", "JV", "JW", "JX", "JY", "JZ", "KA", "KB", "KC", "KD", "KE", "KF", "KG", "KH", "KI", "KJ", "KK", "KL", "KM", "KN", "KO", "KP", "KQ", "KR", "KS", "KT", "KU", "KV", "KW", "KX", "KY", "KZ", "LA", "LB", "LC", "LD", "LE", "LF", "LG", "LH", "LI", "LJ", "LK", "LL", "LM", "LN", "LO", "LP", "LQ", "LR", "LS", "LT", "LU", "LV", "LW", "LX", "LY", "LZ", "MA", "MB", "MC", "MD", "ME", "MF", "MG", "MH", "MI", "MJ", "MK", "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA", "NB", "NC", "ND", "NE", "NF", "NG", "NH", "NI", "NJ", "NK", "NL", "NM", "NN", "NO", "NP", "NQ", "NR", "NS", "NT", "NU", "NV", "NW", "NX", "NY", "NZ", "OA", "OB", "OC", "OD", "OE", "OF", "OG", "OH", "OI", "OJ", "OK", "OL", "OM", "ON", "OO", "OP", "OQ", "OR", "OS", "OT", "OU", "OV", "OW", "OX", "OY", "OZ", "PA", "PB", "PC", "PD", "PE", "PF", "PG", "PH", "PI", "PJ", "PK", "PL", "PM", "PN", "PO", "PP", "PQ", "PR", "PS", "PT", "PU", "PV", "PW", "PX", "PY", "PZ", "QA", "QB", "QC", "QD", "QE", "QF", "QG", "QH", "QI", "QJ", "QK", "QL", "QM", "QN", "QO", "QP", "QQ", "QR", "QS", "QT", "QU", "QV", "QW", "QX", "QY", "QZ", "RA", "RB", "RC", "RD", "RE", "RF", "RG", "RH", "RI", "RJ", "RK", "RL", "RM", "RN", "RO", "RP", "RQ", "RR", "RS", "RT", "RU", "RV", "RW", "RX", "RY", "RZ", "SA", "SB", "SC", "SD", "SE", "SF", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SP", "SQ", "SR", "SS", "ST", "SU", "SV", "SW", "SX", "SY", "SZ", "TA", "TB", "TC", "TD", "TE", "TF", "TG", "TH", "TI", "TJ", "TK", "TL", "TM", "TN", "TO", "TP", "TQ", "TR", "TS", "TT", "TU", "TV", "TW", "TX", "TY", "TZ", "UA", "UB", "UC", "UD", "UE", "UF", "UG", "UH", "UI", "UJ", "UK", "UL", "UM", "UN", "UO", "UP", "UQ", "UR", "US", "UT", "UU", "UV", "UW", "UX", "UY", "UZ", "VA", "VB", "VC", "VD", "VE", "VF", "VG", "VH", "VI", "VJ", "VK", "VL", "VM", "VN", "VO", "VP", "VQ", "VR", "VS", "VT", "VU", "VV", "VW", "VX", "VY", "VZ", "WA", "WB", "WC", "WD", "WE", "WF", "WG", "WH", "WI", "WJ", "WK", "WL", "WM", "WN", "WO", "WP", "WQ", "WR", "WS", "WT", "WU", "WV", "WW", "WX", "WY", "WZ", "XA", "XB", "XC", "XD", "XE", "XF", "XG", "XH", "XI", "XJ", "XK", "XL", "XM", "XN", "XO", "XP", "XQ", "XR", "XS", "XT", "XU", "XV", "XW", "XX", "XY", "XZ", "YA", "YB", "YC", "YD", "YE", "YF", "YG", "YH", "YI", "YJ", "YK", "YL", "YM", "YN", "YO", "YP", "YQ", "YR", "YS", "YT", "YU", "YV", "YW", "YX", "YY", "YZ", "ZA", "ZB", "ZC", "ZD", "ZE", "ZF", "ZG", "ZH", "ZI", "ZJ", "ZK", "ZL", "ZM", "ZN", "ZO", "ZP", "ZQ", "ZR", "ZS", "ZT", "ZU", "ZV", "ZW", "ZX", "ZY", "ZZ"];
+pub const LABELS: [&str; 702] = ["A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z", "AA", "AB", "AC", "AD", "AE", "AF", "AG", "AH", "AI", "AJ", "AK", "AL", "AM", "AN", "AO", "AP", "AQ", "AR", "AS", "AT", "AU", "AV", "AW", "AX", "AY", "AZ", "BA", "BB", "BC", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BK", "BL", "BM", "BN", "BO", "BP", "BQ", "BR", "BS", "BT", "BU", "BV", "BW", "BX", "BY", "BZ", "CA", "CB", "CC", "CD", "CE", "CF", "CG", "CH", "CI", "CJ", "CK", "CL", "CM", "CN", "CO", "CP", "CQ", "CR", "CS", "CT", "CU", "CV", "CW", "CX", "CY", "CZ", "DA", "DB", "DC", "DD", "DE", "DF", "DG", "DH", "DI", "DJ", "DK", "DL", "DM", "DN", "DO", "DP", "DQ", "DR", "DS", "DT", "DU", "DV", "DW", "DX", "DY", "DZ", "EA", "EB", "EC", "ED", "EE", "EF", "EG", "EH", "EI", "EJ", "EK", "EL", "EM", "EN", "EO", "EP", "EQ", "ER", "ES", "ET", "EU", "EV", "EW", "EX", "EY", "EZ", "FA", "FB", "FC", "FD", "FE", "FF", "FG", "FH", "FI", "FJ", "FK", "FL", "FM", "FN", "FO", "FP", "FQ", "FR", "FS", "FT", "FU", "FV", "FW", "FX", "FY", "FZ", "GA", "GB", "GC", "GD", "GE", "GF", "GG", "GH", "GI", "GJ", "GK", "GL", "GM", "GN", "GO", "GP", "GQ", "GR", "GS", "GT", "GU", "GV", "GW", "GX", "GY", "GZ", "HA", "HB", "HC", "HD", "HE", "HF", "HG", "HH", "HI", "HJ", "HK", "HL", "HM", "HN", "HO", "HP", "HQ", "HR", "HS", "HT", "HU", "HV", "HW", "HX", "HY", "HZ", "IA", "IB", "IC", "ID", "IE", "IF", "IG", "IH", "II", "IJ", "IK", "IL", "IM", "IN", "IO", "IP", "IQ", "IR", "IS", "IT", "IU", "IV", "IW", "IX", "IY", "IZ", "JA", "JB", "JC", "JD", "JE", "JF", "JG", "JH", "JI", "JJ", "JK", "JL", "JM", "JN", "JO", "JP", "JQ", "JR", "JS", "JT", "JU", "JV", "JW", "JX", "JY", "JZ", "KA", "KB", "KC", "KD", "KE", "KF", "KG", "KH", "KI", "KJ", "KK", "KL", "KM", "KN", "KO", "KP", "KQ", "KR", "KS", "KT", "KU", "KV", "KW", "KX", "KY", "KZ", "LA", "LB", "LC", "LD", "LE", "LF", "LG", "LH", "LI", "LJ", "LK", "LL", "LM", "LN", "LO", "LP", "LQ", "LR", "LS", "LT", "LU", "LV", "LW", "LX", "LY", "LZ", "MA", "MB", "MC", "MD", "ME", "MF", "MG", "MH", "MI", "MJ", "MK", "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA", "NB", "NC", "ND", "NE", "NF", "NG", "NH", "NI", "NJ", "NK", "NL", "NM", "NN", "NO", "NP", "NQ", "NR", "NS", "NT", "NU", "NV", "NW", "NX", "NY", "NZ", "OA", "OB", "OC", "OD", "OE", "OF", "OG", "OH", "OI", "OJ", "OK", "OL", "OM", "ON", "OO", "OP", "OQ", "OR", "OS", "OT", "OU", "OV", "OW", "OX", "OY", "OZ", "PA", "PB", "PC", "PD", "PE", "PF", "PG", "PH", "PI", "PJ", "PK", "PL", "PM", "PN", "PO", "PP", "PQ", "PR", "PS", "PT", "PU", "PV", "PW", "PX", "PY", "PZ", "QA", "QB", "QC", "QD", "QE", "QF", "QG", "QH", "QI", "QJ", "QK", "QL", "QM", "QN", "QO", "QP", "QQ", "QR", "QS", "QT", "QU", "QV", "QW", "QX", "QY", "QZ", "RA", "RB", "RC", "RD", "RE", "RF", "RG", "RH", "RI", "RJ", "RK", "RL", "RM", "RN", "RO", "RP", "RQ", "RR", "RS", "RT", "RU", "RV", "RW", "RX", "RY", "RZ", "SA", "SB", "SC", "SD", "SE", "SF", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SP", "SQ", "SR", "SS", "ST", "SU", "SV", "SW", "SX", "SY", "SZ", "TA", "TB", "TC", "TD", "TE", "TF", "TG", "TH", "TI", "TJ", "TK", "TL", "TM", "TN", "TO", "TP", "TQ", "TR", "TS", "TT", "TU", "TV", "TW", "TX", "TY", "TZ", "UA", "UB", "UC", "UD", "UE", "UF", "UG", "UH", "UI", "UJ", "UK", "UL", "UM", "UN", "UO", "UP", "UQ", "UR", "US", "UT", "UU", "UV", "UW", "UX", "UY", "UZ", "VA", "VB", "VC", "VD", "VE", "VF", "VG", "VH", "VI", "VJ", "VK", "VL", "VM", "VN", "VO", "VP", "VQ", "VR", "VS", "VT", "VU", "VV", "VW", "VX", "VY", "VZ", "WA", "WB", "WC", "WD", "WE", "WF", "WG", "WH", "WI", "WJ", "WK", "WL", "WM", "WN", "WO", "WP", "WQ", "WR", "WS", "WT", "WU", "WV", "WW", "WX", "WY", "WZ", "XA", "XB", "XC", "XD", "XE", "XF", "XG", "XH", "XI", "XJ", "XK", "XL", "XM", "XN", "XO", "XP", "XQ", "XR", "XS", "XT", "XU", "XV", "XW", "XX", "XY", "XZ", "YA", "YB", "YC", "YD", "YE", "YF", "YG", "YH", "YI", "YJ", "YK", "YL", "YM", "YN", "YO", "YP", "YQ", "YR", "YS", "YT", "YU", "YV", "YW", "YX", "YY", "YZ", "ZA", "ZB", "ZC", "ZD", "ZE", "ZF", "ZG", "ZH", "ZI", "ZJ", "ZK", "ZL", "ZM", "ZN", "ZO", "ZP", "ZQ", "ZR", "ZS", "ZT", "ZU", "ZV", "ZW", "ZX", "ZY", "ZZ"];
 
 #[derive(Debug)]
 pub struct UserDefTypeManager {
@@ -17,7 +18,18 @@ pub struct UserDefTypeManager {
     pub name: Vec<String>,
     pub args: Vec<Vec<String>>,
     pub sgntr: Vec<Vec<FutureType>>,
+    /// the variants of each enum, empty for structs
+    pub variants: Vec<Vec<Variant>>,
 }
+
+/// one of the shapes that a value of an enum can take, which has fields like a struct
+#[derive(Debug)]
+pub struct Variant {
+    pub name: String,
+    pub args: Vec<String>,
+    pub sgntr: Vec<FutureType>,
+}
+
 impl UserDefTypeManager {
     pub fn new() -> UserDefTypeManager {
         UserDefTypeManager {
@@ -25,21 +37,43 @@ impl UserDefTypeManager {
             name: vec![],
             args: vec![],
             sgntr: vec![],
+            variants: vec![],
         }
     }
     /// declare a user defined type
     pub fn declare_type(&mut self, token: Token, name: String, arguments: Vec<String>, proto_signature: Vec<FutureType>) -> Result<usize, Error> {
         use util::has_unique_elements;
         if !has_unique_elements(arguments.iter()) {
-            return Err(Error::new("struct contains duplicate argument names".to_string(), token));
+            return Err(Error::new(ErrorCode::DuplicateName, "struct contains duplicate argument names".to_string(), token));
         }
         let id = self.count;
         self.name.push(name);
         self.args.push(arguments);
         self.sgntr.push(proto_signature);
+        self.variants.push(vec![]);
         self.count += 1;
         Ok(id)
     }
+    /// declare an enum, which has no fields of its own, only those of its variants
+    pub fn declare_enum(&mut self, token: Token, name: String, variants: Vec<Variant>) -> Result<usize, Error> {
+        use util::has_unique_elements;
+        if !has_unique_elements(variants.iter().map(|variant| &variant.name)) {
+            return Err(Error::new(ErrorCode::DuplicateName, "enum contains duplicate variant names".to_string(), token));
+        }
+        if let Some(variant) = variants.iter().find(|variant| !has_unique_elements(variant.args.iter())) {
+            return Err(Error::new(ErrorCode::DuplicateName, format!("variant `{}` contains duplicate field names", variant.name), token));
+        }
+        let id = self.declare_type(token, name, vec![], vec![])?;
+        self.variants[id] = variants;
+        Ok(id)
+    }
+    pub fn is_enum(&self, id: usize) -> bool {
+        !self.variants[id].is_empty()
+    }
+    /// the index of an enum's variant, which is also its discriminant
+    pub fn variant_index(&self, enum_id: usize, name: &str) -> Option<usize> {
+        self.variants[enum_id].iter().position(|variant| variant.name == name)
+    }
     pub fn get_field_offset(&self, struct_id: usize, field: &str) -> usize {
         self.args[struct_id]
             .iter()
@@ -57,6 +91,15 @@ pub struct FuncManager {
     pub out_type: Vec<FutureType>,
     pub body: Vec<SexprId>,
     pub call_site_count: usize,
+    /// the type parameters of generic functions, empty for the others
+    pub type_params: Vec<Vec<String>>,
+    /// what the type parameters are for each instance of a generic function, empty for the others
+    pub type_args: Vec<Vec<Type>>,
+    /// the instance of a generic function for each set of type arguments
+    pub instances: HashMap<(usize, Vec<Type>), usize>,
+    /// the variables from around a lambda that it uses, by scope index and variable id.
+    /// a lambda's value holds them in this order, after its function id
+    pub captures: Vec<Vec<(usize, usize)>>,
 }
 impl FuncManager {
     pub fn new() -> FuncManager {
@@ -68,25 +111,45 @@ impl FuncManager {
             out_type: vec![],
             body: vec![],
             call_site_count: 0,
+            type_params: vec![],
+            type_args: vec![],
+            instances: HashMap::new(),
+            captures: vec![],
         }
     }
     /// declare a function with the following properties
     /// return its id
-    pub fn declare_func(&mut self, name: String, arguments: Vec<String>, proto_signature: Vec<FutureType>, proto_out_type: FutureType, body: SexprId) -> Result<usize, Error> {
+    pub fn declare_func(&mut self, name: String, type_params: Vec<String>, arguments: Vec<String>, proto_signature: Vec<FutureType>, proto_out_type: FutureType, body: SexprId) -> Result<usize, Error> {
         let id = self.count;
         self.name.push(name);
+        self.type_params.push(type_params);
+        self.type_args.push(vec![]);
         self.args.push(arguments);
         self.in_types.push(proto_signature);
         self.out_type.push(proto_out_type);
         self.body.push(body);
+        self.captures.push(vec![]);
         //TODO give all functions their labels later
         //self.func_labels.push(lbl);
         self.count += 1;
         Ok(id)
     }
-    /// get the id for the function body
-    pub fn func_body(&self, id: usize) -> SexprId {
-        self.body[id]
+    /// generic functions are only compiled as their instances
+    pub fn is_generic(&self, id: usize) -> bool {
+        !self.type_params[id].is_empty()
+    }
+    /// the type of the function as a value
+    pub fn func_type(&self, id: usize) -> Type {
+        Type::Func(self.in_types[id].iter().map(FutureType::unwrap_clone).collect(), Box::new(self.out_type[id].unwrap_clone()))
+    }
+    /// the function's name, with its type parameters or, for an instance, its type arguments
+    pub fn title(&self, id: usize) -> String {
+        let types = if self.is_generic(id) {
+            self.type_params[id].clone()
+        } else {
+            self.type_args[id].iter().map(Type::to_string).collect()
+        };
+        if types.is_empty() { self.name[id].clone() } else { format!("{}<{}>", self.name[id], types.join(",")) }
     }
 
     /*/// call a closure on a mutable reference to the function's body
@@ -137,7 +200,7 @@ impl BuiltinManager {
                     s.out_type.push(out_type);
                     s.handle.push(handle.to_string());
                     s.code.push(
-                        if code.len() == 0 { String::new() }
+                        if code.is_empty() { String::new() }
                             else { format!("{}\n", code) }
                     );
                 }
@@ -172,6 +235,10 @@ pub struct Manager {
     pub sexpr_scopes: Vec<ScopeId>,
     pub top_level_sexprs: Vec<SexprId>,
     pub all_scopes: Vec<Scope>,
+    /// the name of the file the source came from, for pointing at errors
+    pub file_name: String,
+    /// problems that don't stop compilation, reported alongside the program
+    pub warnings: Vec<Error>,
 }
 impl Manager {
     pub fn new(source: String) -> Manager {
@@ -185,65 +252,185 @@ impl Manager {
             sexpr_scopes: vec![],
             top_level_sexprs: vec![],
             all_scopes: vec![],
-
+            file_name: "<source>".to_string(),
+            warnings: vec![],
         }
     }
-    pub fn lookup_user_def_type(name: &str, type_names: &Vec<String>) -> Option<Type> {
+    pub fn lookup_user_def_type(name: &str, type_names: &[String]) -> Option<Type> {
         type_names
             .iter()
-            .position(|ref type_name| **type_name == name)
+            .position(|type_name| *type_name == name)
             .map(|index| Type::CustomType(name.to_string(), index))
     }
     /// upgrade all FutureTypes into a concrete type
-    pub fn initialize_type_info(&mut self) -> Result<(), Error> {
-        fn upgrade_all<'a, 'b>(types_to_upgrade: impl Iterator<Item = &'a mut FutureType>, source: &str, names_of_types: &'b Vec<String>) -> Result<(), Error> {
-            types_to_upgrade
-                .map(|elem: &mut FutureType| elem.upgrade(source, names_of_types))
-                .collect::<Result<Vec<()>, Error>>()?;
-            Ok(())
+    pub fn initialize_type_info(&mut self) -> Result<(), Vec<Error>> {
+        fn upgrade_all<'a, 'b>(types_to_upgrade: impl Iterator<Item = &'a mut FutureType>, source: &str, names_of_types: &'b Vec<String>, generics: &[String]) -> Result<(), Vec<Error>> {
+            let errors = types_to_upgrade
+                .filter_map(|elem: &mut FutureType| elem.upgrade(source, names_of_types, generics).err())
+                .collect::<Vec<Error>>();
+            if errors.is_empty() { Ok(()) } else { Err(errors) }
         }
-        let types_to_upgrade =
-            self.udt_manager.sgntr
+        upgrade_all(self.udt_manager.sgntr.iter_mut().flatten(), &self.source, &self.udt_manager.name, &[])?;
+        let variant_types = self.udt_manager.variants.iter_mut().flatten().flat_map(|variant| variant.sgntr.iter_mut());
+        upgrade_all(variant_types, &self.source, &self.udt_manager.name, &[])?;
+        // a function's types can use its type parameters, and so can the annotations in its body
+        let mut errors = vec![];
+        for func_id in 0..self.func_manager.count {
+            let generics = self.func_manager.type_params[func_id].clone();
+            let types_to_upgrade = self.func_manager.in_types[func_id]
                 .iter_mut()
-                .flatten()
-                .chain(
-                    self.func_manager.in_types
-                        .iter_mut()
-                        .flatten()
-                )
-                .chain(
-                    self.func_manager.out_type
-                        .iter_mut()
-                );
-        upgrade_all(types_to_upgrade, &self.source, &self.udt_manager.name)
+                .chain(Some(&mut self.func_manager.out_type[func_id]));
+            if let Err(more) = upgrade_all(types_to_upgrade, &self.source, &self.udt_manager.name, &generics) {
+                errors.extend(more);
+            }
+            if !generics.is_empty() {
+                self.upgrade_annotations(self.func_manager.body[func_id], &generics, &mut errors);
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        // and the rest of the annotations on declares
+        let mut annotations = self.all_sexprs
+            .iter()
+            .map(|sexpr| sexpr.borrow_mut())
+            .collect::<Vec<RefMut<'_, Sexpr>>>();
+        let annotations = annotations
+            .iter_mut()
+            .filter_map(|sexpr| match *sexpr.kind {
+                SexprKind::Declare { annotation: Some(ref mut annotation), .. } => Some(annotation),
+                _ => None,
+            });
+        upgrade_all(annotations, &self.source, &self.udt_manager.name, &[])
+    }
+    fn upgrade_annotations(&self, sexpr_id: SexprId, generics: &[String], errors: &mut Vec<Error>) {
+        if let SexprKind::Declare { annotation: Some(ref mut annotation), .. } = *self.sexpr_mut(sexpr_id).kind {
+            if let Err(error) = annotation.upgrade(&self.source, &self.udt_manager.name, generics) {
+                errors.push(error);
+            }
+        }
+        for child in self.children(sexpr_id) {
+            self.upgrade_annotations(child, generics, errors);
+        }
     }
-    /// declare a call site that calls the function with the given id
-    /// return the call site id (to be used by the functions to return)
-    pub fn declare_call_site(&mut self, _id: usize) -> Result<usize, Error> {
-        //TODO id can be used in optimizing
+    /// the s-exprs directly inside of this one
+    pub fn children(&self, sexpr_id: SexprId) -> Vec<SexprId> {
+        match *self.kind(sexpr_id) {
+            SexprKind::Declare { expr, body, .. } => vec![expr, body],
+            SexprKind::Assign { expr, .. } => vec![expr],
+            SexprKind::IfSwitch { predicate, if_branch, else_branch } => vec![predicate, if_branch, else_branch],
+            SexprKind::WhileLoop { predicate, body } => vec![predicate, body],
+            SexprKind::FuncDef { func_id } | SexprKind::Lambda { func_id } => vec![self.func_manager.body[func_id]],
+            SexprKind::StructGet { expr, .. } => vec![expr],
+            SexprKind::StructSet { expr, value, .. } => vec![expr, value],
+            SexprKind::ListGet { list, index } => vec![list, index],
+            SexprKind::ListSet { list, index, elem } => vec![list, index, elem],
+            SexprKind::ListLen { list } => vec![list],
+            SexprKind::ListMap { func, list, .. } => vec![func, list],
+            SexprKind::ListPush { list, elem } => vec![list, elem],
+            SexprKind::Match { expr, ref arms, .. } => Some(expr).into_iter().chain(arms.iter().map(|arm| arm.body)).collect(),
+            SexprKind::Block { statements: ref exprs } | SexprKind::List { elements: ref exprs } | SexprKind::FuncCall { ref exprs, .. } | SexprKind::StructInit { ref exprs, .. }
+                | SexprKind::EnumInit { ref exprs, .. } | SexprKind::ValueCall { ref exprs, .. } | SexprKind::Format { ref exprs } | SexprKind::BuiltIn { ref exprs, .. } => exprs.iter().cloned().collect(),
+            SexprKind::Other { ref opt_exprs } => opt_exprs.iter().flatten().cloned().collect(),
+            SexprKind::StructDef { .. } | SexprKind::EnumDef { .. } | SexprKind::FuncRef { .. } | SexprKind::StringLiteral | SexprKind::IntegerLiteral | SexprKind::RealLiteral
+                | SexprKind::BooleLiteral | SexprKind::Identifier => vec![],
+        }
+    }
+    /// copy an s-expr and everything inside of it, putting types in for the type parameters in annotations.
+    /// this is how generic functions get a body for each of their instances
+    pub fn copy_sexpr(&mut self, sexpr_id: SexprId, bindings: &HashMap<String, Type>) -> SexprId {
+        let (mut kind, token) = {
+            let sexpr = self.sexpr(sexpr_id);
+            ((*sexpr.kind).clone(), sexpr.token)
+        };
+        {
+            let mut copy = |id: &mut SexprId| *id = self.copy_sexpr(*id, bindings);
+            match kind {
+                SexprKind::Declare { ref mut annotation, ref mut expr, ref mut body, .. } => {
+                    if let Some(FutureType::Complete(ref mut typ)) = annotation {
+                        *typ = typ.substitute(bindings);
+                    }
+                    copy(expr);
+                    copy(body);
+                }
+                SexprKind::Assign { ref mut expr, .. } | SexprKind::StructGet { ref mut expr, .. }
+                    | SexprKind::ListLen { list: ref mut expr } => copy(expr),
+                SexprKind::ListMap { ref mut func, ref mut list, .. } => {
+                    copy(func);
+                    copy(list);
+                }
+                SexprKind::IfSwitch { ref mut predicate, ref mut if_branch, ref mut else_branch } => {
+                    copy(predicate);
+                    copy(if_branch);
+                    copy(else_branch);
+                }
+                SexprKind::WhileLoop { ref mut predicate, ref mut body } => {
+                    copy(predicate);
+                    copy(body);
+                }
+                SexprKind::StructSet { ref mut expr, ref mut value, .. } => {
+                    copy(expr);
+                    copy(value);
+                }
+                SexprKind::ListGet { ref mut list, ref mut index } => {
+                    copy(list);
+                    copy(index);
+                }
+                SexprKind::ListSet { ref mut list, ref mut index, ref mut elem } => {
+                    copy(list);
+                    copy(index);
+                    copy(elem);
+                }
+                SexprKind::ListPush { ref mut list, ref mut elem } => {
+                    copy(list);
+                    copy(elem);
+                }
+                SexprKind::Match { ref mut expr, ref mut arms, .. } => {
+                    copy(expr);
+                    arms.iter_mut().for_each(|arm| copy(&mut arm.body));
+                }
+                SexprKind::Block { statements: ref mut exprs } | SexprKind::List { elements: ref mut exprs } | SexprKind::FuncCall { ref mut exprs, .. } | SexprKind::StructInit { ref mut exprs, .. }
+                    | SexprKind::EnumInit { ref mut exprs, .. } | SexprKind::ValueCall { ref mut exprs, .. } | SexprKind::Format { ref mut exprs } | SexprKind::BuiltIn { ref mut exprs, .. } => exprs.iter_mut().for_each(copy),
+                SexprKind::Other { ref mut opt_exprs } => opt_exprs.iter_mut().flatten().for_each(copy),
+                // a function defined inside of another is shared by its copies, and generic functions can't hold lambdas
+                SexprKind::FuncDef { .. } | SexprKind::Lambda { .. } | SexprKind::FuncRef { .. } | SexprKind::StructDef { .. } | SexprKind::EnumDef { .. } | SexprKind::StringLiteral | SexprKind::IntegerLiteral
+                    | SexprKind::RealLiteral | SexprKind::BooleLiteral | SexprKind::Identifier => {}
+            }
+        }
+        let scope = self.sexpr_scopes[sexpr_id.index];
+        self.all_sexprs.push(RefCell::new(Sexpr::new(Box::new(kind), token)));
+        self.sexpr_scopes.push(scope);
+        self.sexpr_result_types.push(Type::Void);
+        (self.all_sexprs.len() - 1).into()
+    }
+    /// declare a call site that calls the function with the given id, or None for a call through a function value
+    /// return the call site id (to be used by the functions to return).
+    /// inlining also declares new ones for the calls in each copy of a function's body
+    pub fn declare_call_site(&mut self, _id: Option<usize>) -> Result<usize, Error> {
         let call_site_id = self.func_manager.call_site_count;
         self.func_manager.call_site_count += 1;
         Ok(call_site_id)
     }
 
     /// borrow the corresponding s-expr
-    pub fn sexpr(&self, sexpr_id: SexprId) -> Ref<Sexpr> { self.all_sexprs[sexpr_id.index].borrow() }
+    pub fn sexpr(&self, sexpr_id: SexprId) -> Ref<'_, Sexpr> { self.all_sexprs[sexpr_id.index].borrow() }
     /// mutable borrow the corresponding s-expr
-    pub fn sexpr_mut(&self, sexpr_id: SexprId) -> RefMut<Sexpr> { self.all_sexprs[sexpr_id.index].borrow_mut() }
+    pub fn sexpr_mut(&self, sexpr_id: SexprId) -> RefMut<'_, Sexpr> { self.all_sexprs[sexpr_id.index].borrow_mut() }
     /// get the text of the head from this s-expr's token
     pub fn text(&self, sexpr_id: SexprId) -> &str { self.sexpr(sexpr_id).token.get_text(&self.source) }
-    /// get the kind of this sexpr
-    pub fn kind(&self, sexpr_id: SexprId) -> &SexprKind {
-        use std::ops::Deref;
-        &Box::deref(&self.sexpr(sexpr_id).kind)
+    /// borrow the kind of this sexpr
+    pub fn kind(&self, sexpr_id: SexprId) -> Ref<'_, SexprKind> {
+        Ref::map(self.sexpr(sexpr_id), |sexpr| &*sexpr.kind)
     }
+    /// get the type this s-expr evaluates to (only valid after type checking)
+    pub fn result_type(&self, sexpr_id: SexprId) -> &Type { &self.sexpr_result_types[sexpr_id.index] }
     /// get the id of this s-expr's scope
     pub fn scope_of(&self, sexpr_id: SexprId) -> ScopeId { self.sexpr_scopes[sexpr_id.index] }
     ///returns a copy of its identifier if it is a valid one, otherwise returns an error
     pub fn get_ident(&self, sexpr_id: SexprId) -> Result<String, Error> {
         match *self.sexpr(sexpr_id).kind {
             SexprKind::Identifier => Ok(self.text(sexpr_id).to_string()),
-            _ => Err(Error::new(format!("not a valid identifier"), self.sexpr(sexpr_id).token))
+            _ => Err(Error::new(ErrorCode::NotAnIdentifier, "not a valid identifier".to_string(), self.sexpr(sexpr_id).token))
         }
     }
 
@@ -260,7 +447,7 @@ impl Manager {
     pub fn bind_variable(&mut self, scope_id: ScopeId, name: String, opt_type: Option<&Type>) {
         let bound_count = self.scope(scope_id).variable_types.len();
         self.scope_mut(scope_id).declared_variables.insert(name, bound_count);
-        self.scope_mut(scope_id).variable_types.push(opt_type.map(|t: &Type| t.clone()));
+        self.scope_mut(scope_id).variable_types.push(opt_type.cloned());
     }
     /// resolve a variable name into the scope id and variable index
     pub fn resolve_variable(&self, scope_id: ScopeId, name: &str) -> Option<(ScopeId, usize)> {
@@ -272,35 +459,68 @@ impl Manager {
         }
     }
 
-    pub fn inform_var_type(&mut self, scope_id: ScopeId, name: &str, var_type: &Type) {
+    /// give a variable the type of the value it is given, which has to agree with the type it already has, if any.
+    /// `at` is where that value comes from
+    pub fn inform_var_type(&mut self, scope_id: ScopeId, name: &str, var_type: &Type, at: Token) -> Result<(), Error> {
         let (scope_id, var_id) = self.resolve_variable(scope_id, name).expect("could not find variable when creating types");
-        if self.scope(scope_id).variable_types[var_id].is_some() {
-            panic!("conflicting types for variable with name: {}, trying to set to type: {}, found: {:?}, and in {:?}",
-                   name,
-                   var_type,
-                   self.scope(scope_id).variable_types[var_id].as_ref(),
-                   scope_id)
+        if let Some(ref known_type) = self.scope(scope_id).variable_types[var_id] {
+            if Type::is_not(known_type, var_type) {
+                return Err(Error::new(ErrorCode::TypeMismatch, format!("`{}` is declared as {} but is given a value of type {}", name, known_type, var_type), at));
+            }
+            return Ok(());
         }
         self.scope_mut(scope_id).variable_types[var_id] = Some(var_type.clone());
+        Ok(())
     }
 
     pub fn bind_func(&mut self, scope_id: ScopeId, func_id: usize) { self.scope_mut(scope_id).declared_functions.push(func_id); }
     pub fn resolve_func(&self, scope_id: ScopeId, name: &str, signature: &[Type]) -> Option<usize> {
         for &id in self.scope(scope_id).declared_functions.iter() {
-            if *self.func_manager.name[id] == *name && Type::check_futures_signature(&self.func_manager.in_types[id], signature) {
+            let matches = if self.func_manager.is_generic(id) {
+                Type::bind_generics(&self.func_manager.in_types[id], signature).is_some()
+            } else {
+                Type::check_futures_signature(&self.func_manager.in_types[id], signature)
+            };
+            if *self.func_manager.name[id] == *name && matches {
                 return Some(id);
             }
         }
         self.scope(scope_id).parent.and_then(|parent| self.resolve_func(parent, name, signature))
     }
 
+    /// every function visible from this scope with this name, nearest first
+    pub fn funcs_named(&self, scope_id: ScopeId, name: &str) -> Vec<usize> {
+        let mut funcs = self.scope(scope_id).declared_functions
+            .iter()
+            .cloned()
+            .filter(|&id| self.func_manager.name[id] == name)
+            .collect::<Vec<usize>>();
+        if let Some(parent) = self.scope(scope_id).parent {
+            funcs.extend(self.funcs_named(parent, name));
+        }
+        funcs
+    }
+
     pub fn bind_struct_init(&mut self, scope_id: ScopeId, struct_id: usize) { self.scope_mut(scope_id).declared_structs.push(struct_id); }
     pub fn resolve_struct_init(&self, scope_id: ScopeId, name: &str, signature: &[Type]) -> Option<usize> {
         for &id in self.scope(scope_id).declared_structs.iter() {
-            if *self.udt_manager.name[id] == *name && Type::check_futures_signature(&self.func_manager.in_types[id], signature) {
+            if *self.udt_manager.name[id] == *name && Type::check_futures_signature(&self.udt_manager.sgntr[id], signature) {
                 return Some(id);
             }
         }
         self.scope(scope_id).parent.and_then(|parent| self.resolve_struct_init(parent, name, signature))
     }
+
+    pub fn bind_enum(&mut self, scope_id: ScopeId, enum_id: usize) { self.scope_mut(scope_id).declared_enums.push(enum_id); }
+    /// resolve the name of a variant into the id of its enum and its index there
+    pub fn resolve_variant(&self, scope_id: ScopeId, name: &str, signature: &[Type]) -> Option<(usize, usize)> {
+        for &id in self.scope(scope_id).declared_enums.iter() {
+            if let Some(variant) = self.udt_manager.variant_index(id, name) {
+                if Type::check_futures_signature(&self.udt_manager.variants[id][variant].sgntr, signature) {
+                    return Some((id, variant));
+                }
+            }
+        }
+        self.scope(scope_id).parent.and_then(|parent| self.resolve_variant(parent, name, signature))
+    }
 }
\ No newline at end of file
@@ -1,98 +1,1084 @@
-use sexpr::{Sexpr, SexprId, SexprKind};
-use util::Error;
+use sexpr::{SexprId, SexprKind, MapTarget};
+use type_checker::Type;
+use util::{Error, vec_fmt};
 use manager::Manager;
+use number::Number;
 
-pub struct MirExpr {
-    fmt_str: String,
-    handles: Vec<MirExpr>,
+use std::collections::HashMap;
+use std::fmt;
+
+/// somewhere that a single numeric value can live at run time
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MirPlace {
+    /// a slot of the global frame, only used by top level code
+    Global(usize),
+    /// a slot of the current function's frame on top of ⌊ARGS (slot 0 is the call site id)
+    Local(usize),
+}
+
+impl fmt::Display for MirPlace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MirPlace::Global(index) => write!(f, "g{}", index),
+            MirPlace::Local(index) => write!(f, "l{}", index),
+        }
+    }
+}
+
+/// an expression without side effects.
+/// string typed expressions always evaluate to a string, everything else evaluates to a number
+#[derive(Debug, Clone)]
+pub enum MirExpr {
+    /// the value of a void typed expression: nothing can be read from it
+    Void,
+    /// a numeric literal
+    Num(String),
+    /// a string literal (without the quotes)
+    Str(String),
+    /// read the number stored at a place
+    Read(MirPlace),
+    /// read a word out of the ⌊OBJ heap
+    ObjGet{base: Box<MirExpr>, offset: usize},
+    /// turn a packed string handle into the string it points to inside of Str0
+    Unpack(Box<MirExpr>),
+    /// fill in a builtin's handle template
+    Fmt{fmt_str: String, handles: Vec<MirExpr>},
 }
 impl MirExpr {
-    fn empty() -> MirExpr { MirExpr{ fmt_str: String::new(), handles: vec![] }}
+    fn empty() -> MirExpr { MirExpr::Void }
+    /// true if reading this expression gives the same answer no matter what runs before it
+    pub fn is_constant(&self) -> bool {
+        matches!(*self, MirExpr::Void | MirExpr::Num(_) | MirExpr::Str(_))
+    }
 }
 
+impl MirExpr {
+    /// the same expression, reading from other places
+    fn map_places(&self, map: &dyn Fn(MirPlace) -> MirPlace) -> MirExpr {
+        match *self {
+            MirExpr::Read(place) => MirExpr::Read(map(place)),
+            MirExpr::ObjGet { ref base, offset } => MirExpr::ObjGet { base: Box::new(base.map_places(map)), offset },
+            MirExpr::Unpack(ref handle) => MirExpr::Unpack(Box::new(handle.map_places(map))),
+            MirExpr::Fmt { ref fmt_str, ref handles } => MirExpr::Fmt { fmt_str: fmt_str.clone(), handles: handles.iter().map(|handle| handle.map_places(map)).collect() },
+            ref expr => expr.clone(),
+        }
+    }
+}
+
+impl fmt::Display for MirExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MirExpr::Void => write!(f, "void"),
+            MirExpr::Num(ref num) => write!(f, "{}", num),
+            MirExpr::Str(ref string) => write!(f, "\"{}\"", string),
+            MirExpr::Read(place) => write!(f, "{}", place),
+            MirExpr::ObjGet { ref base, offset } => write!(f, "obj[{}+{}]", base, offset),
+            MirExpr::Unpack(ref handle) => write!(f, "unpack({})", handle),
+            MirExpr::Fmt { ref fmt_str, ref handles } => write!(f, "{}", vec_fmt(fmt_str, &handles.iter().map(MirExpr::to_string).collect::<Vec<String>>())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum MirStmnt {
-    Malloc{width: usize},
-    WriteAtFixed{at: usize, expr: MirExpr},
-    InvokeFunc{call_site_id: usize, func_id: usize},
-    IfBlock{predicate: MirExpr, if_stmnts: Vec<MirStmnt>, if_expr: MirExpr, else_stmnts: Vec<MirStmnt>, else_expr: MirExpr},
-    WhileBlock{},
+    /// reserve `width` words at the end of ⌊OBJ, writing the index of the first one to `at`
+    Malloc{at: MirPlace, width: MirExpr},
+    /// write a number to a place
+    WriteAtFixed{at: MirPlace, expr: MirExpr},
+    /// append a string to Str0, writing the packed handle of the appended string to `at`
+    Pack{at: MirPlace, expr: MirExpr},
+    /// write a number into a word of the ⌊OBJ heap
+    ObjSet{base: MirExpr, offset: usize, expr: MirExpr},
+    /// fill in and run a builtin's code template
+    Exec{fmt_str: String, handles: Vec<MirExpr>},
+    /// stringify each piece onto the end of Str0, writing the packed handle of the whole thing to `at`
+    /// `utils` are scratch places for the stringification
+    Format{at: MirPlace, utils: (MirPlace, MirPlace), pieces: Vec<(MirExpr, Type)>},
+    /// push a frame holding the (numeric) arguments, jump to the function, and come back
+    /// the result is popped off of ⌊RES into `result`
+    InvokeFunc{call_site_id: usize, func_id: usize, args: Vec<MirExpr>, result: Option<MirPlace>},
+    /// reserve a record in ⌊OBJ saying which function this is, followed by the values it captured,
+    /// writing the index of the record to `at`
+    MakeClosure{at: MirPlace, func_id: usize, captures: Vec<MirExpr>},
+    /// go back to the top of the function when `predicate` holds, its arguments having been rewritten for a call to itself
+    Restart{func_id: usize, predicate: MirExpr},
+    /// give back the `width` words of ⌊OBJ starting at `base`, if nothing has been put after them since
+    Free{base: MirExpr, width: usize},
+    /// like `InvokeFunc`, but for whichever function of type `typ` the record that `callee` points at says.
+    /// the callee gets the record as a hidden argument right after the others
+    InvokeValue{call_site_id: usize, callee: MirExpr, typ: Type, args: Vec<MirExpr>, result: Option<MirPlace>},
+    IfBlock{predicate: MirExpr, if_stmnts: Vec<MirStmnt>, else_stmnts: Vec<MirStmnt>},
+    /// `predicate_stmnts` must run before every check of the predicate
+    /// when there are any, they end by saving the predicate, which is then just a read of that place
+    WhileBlock{predicate_stmnts: Vec<MirStmnt>, predicate: MirExpr, body: Vec<MirStmnt>},
+}
+
+impl MirStmnt {
+    /// the same statement, using other places
+    fn map_places(&self, map: &dyn Fn(MirPlace) -> MirPlace) -> MirStmnt {
+        let exprs = |exprs: &[MirExpr]| exprs.iter().map(|expr| expr.map_places(map)).collect::<Vec<MirExpr>>();
+        let stmnts = |stmnts: &[MirStmnt]| stmnts.iter().map(|stmnt| stmnt.map_places(map)).collect::<Vec<MirStmnt>>();
+        match *self {
+            MirStmnt::Malloc { at, ref width } => MirStmnt::Malloc { at: map(at), width: width.map_places(map) },
+            MirStmnt::WriteAtFixed { at, ref expr } => MirStmnt::WriteAtFixed { at: map(at), expr: expr.map_places(map) },
+            MirStmnt::Pack { at, ref expr } => MirStmnt::Pack { at: map(at), expr: expr.map_places(map) },
+            MirStmnt::ObjSet { ref base, offset, ref expr } => MirStmnt::ObjSet { base: base.map_places(map), offset, expr: expr.map_places(map) },
+            MirStmnt::Exec { ref fmt_str, ref handles } => MirStmnt::Exec { fmt_str: fmt_str.clone(), handles: exprs(handles) },
+            MirStmnt::Format { at, utils, ref pieces } => MirStmnt::Format {
+                at: map(at),
+                utils: (map(utils.0), map(utils.1)),
+                pieces: pieces.iter().map(|(piece, typ)| (piece.map_places(map), typ.clone())).collect(),
+            },
+            MirStmnt::InvokeFunc { call_site_id, func_id, ref args, result } => MirStmnt::InvokeFunc { call_site_id, func_id, args: exprs(args), result: result.map(map) },
+            MirStmnt::MakeClosure { at, func_id, ref captures } => MirStmnt::MakeClosure { at: map(at), func_id, captures: exprs(captures) },
+            MirStmnt::InvokeValue { call_site_id, ref callee, ref typ, ref args, result } => MirStmnt::InvokeValue {
+                call_site_id,
+                callee: callee.map_places(map),
+                typ: typ.clone(),
+                args: exprs(args),
+                result: result.map(map),
+            },
+            MirStmnt::Restart { func_id, ref predicate } => MirStmnt::Restart { func_id, predicate: predicate.map_places(map) },
+            MirStmnt::Free { ref base, width } => MirStmnt::Free { base: base.map_places(map), width },
+            MirStmnt::IfBlock { ref predicate, ref if_stmnts, ref else_stmnts } => MirStmnt::IfBlock {
+                predicate: predicate.map_places(map),
+                if_stmnts: stmnts(if_stmnts),
+                else_stmnts: stmnts(else_stmnts),
+            },
+            MirStmnt::WhileBlock { ref predicate_stmnts, ref predicate, ref body } => MirStmnt::WhileBlock {
+                predicate_stmnts: stmnts(predicate_stmnts),
+                predicate: predicate.map_places(map),
+                body: stmnts(body),
+            },
+        }
+    }
+}
+
+/// visit every statement, including the ones nested in blocks
+fn walk_stmnts(stmnts: &[MirStmnt], visit: &mut dyn FnMut(&MirStmnt)) {
+    for stmnt in stmnts {
+        visit(stmnt);
+        match *stmnt {
+            MirStmnt::IfBlock { ref if_stmnts, ref else_stmnts, .. } => {
+                walk_stmnts(if_stmnts, visit);
+                walk_stmnts(else_stmnts, visit);
+            }
+            MirStmnt::WhileBlock { ref predicate_stmnts, ref body, .. } => {
+                walk_stmnts(predicate_stmnts, visit);
+                walk_stmnts(body, visit);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// swap the call at this call site for the body of the function it calls, with its frame moved by `map`.
+/// false if the call isn't in these statements
+fn replace_call(stmnts: &mut Vec<MirStmnt>, call_site_id: usize, callee: &MirFunc, map: &dyn Fn(MirPlace) -> MirPlace) -> bool {
+    for index in 0..stmnts.len() {
+        let found = match stmnts[index] {
+            MirStmnt::InvokeFunc { call_site_id: site, ref args, result, .. } if site == call_site_id => Some(expand_call(callee, map, args, result)),
+            MirStmnt::IfBlock { ref mut if_stmnts, ref mut else_stmnts, .. } => {
+                if replace_call(if_stmnts, call_site_id, callee, map) || replace_call(else_stmnts, call_site_id, callee, map) {
+                    return true;
+                }
+                None
+            }
+            MirStmnt::WhileBlock { ref mut predicate_stmnts, ref mut body, .. } => {
+                if replace_call(predicate_stmnts, call_site_id, callee, map) || replace_call(body, call_site_id, callee, map) {
+                    return true;
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some(expanded) = found {
+            stmnts.splice(index..index + 1, expanded);
+            return true;
+        }
+    }
+    false
+}
+
+/// the body of a function with its frame moved by `map`, taking these arguments and leaving its result at `result`
+fn expand_call(callee: &MirFunc, map: &dyn Fn(MirPlace) -> MirPlace, args: &[MirExpr], result: Option<MirPlace>) -> Vec<MirStmnt> {
+    let mut stmnts = args
+        .iter()
+        .enumerate()
+        .map(|(index, arg)| MirStmnt::WriteAtFixed { at: map(MirPlace::Local(index + 1)), expr: arg.clone() })
+        .collect::<Vec<MirStmnt>>();
+    stmnts.extend(callee.body.iter().map(|stmnt| stmnt.map_places(map)));
+    if let Some(at) = result {
+        stmnts.push(MirStmnt::WriteAtFixed { at, expr: callee.result.map_places(map) });
+    }
+    stmnts
+}
+
+/// give every call in these statements a new call site
+fn renumber_call_sites(m: &mut Manager, stmnts: &mut [MirStmnt]) -> Result<(), Error> {
+    for stmnt in stmnts.iter_mut() {
+        match *stmnt {
+            MirStmnt::InvokeFunc { ref mut call_site_id, func_id, .. } => *call_site_id = m.declare_call_site(Some(func_id))?,
+            MirStmnt::InvokeValue { ref mut call_site_id, .. } => *call_site_id = m.declare_call_site(None)?,
+            MirStmnt::IfBlock { ref mut if_stmnts, ref mut else_stmnts, .. } => {
+                renumber_call_sites(m, if_stmnts)?;
+                renumber_call_sites(m, else_stmnts)?;
+            }
+            MirStmnt::WhileBlock { ref mut predicate_stmnts, ref mut body, .. } => {
+                renumber_call_sites(m, predicate_stmnts)?;
+                renumber_call_sites(m, body)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// how many statements, counting its result, a function can have and still be put in place of every call to it
+pub const DEFAULT_INLINE_THRESHOLD: usize = 4;
+
+/// how many call sites a function can check one after the other before it is faster to search them
+pub const CHAIN_LIMIT: usize = 4;
+
+/// how a function gets back to whoever called it, going by the call site id on top of ⌊ARGS
+#[derive(Debug, Clone)]
+pub enum ReturnStrategy {
+    /// nothing calls it, so it never returns
+    Unused,
+    /// only one place calls it, so it jumps straight back there
+    Direct(usize),
+    /// it checks each of a few call sites in turn, the last one needing no check
+    Chain(Vec<usize>),
+    /// it halves the call sites it could be going back to until there are few enough to check in turn
+    BinarySearch(Vec<usize>),
+}
+impl ReturnStrategy {
+    fn for_call_sites(call_sites: Vec<usize>) -> ReturnStrategy {
+        match call_sites.len() {
+            0 => ReturnStrategy::Unused,
+            1 => ReturnStrategy::Direct(call_sites[0]),
+            count if count <= CHAIN_LIMIT => ReturnStrategy::Chain(call_sites),
+            _ => ReturnStrategy::BinarySearch(call_sites),
+        }
+    }
+}
+
+impl fmt::Display for ReturnStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReturnStrategy::Unused => write!(f, "never called"),
+            ReturnStrategy::Direct(call_site_id) => write!(f, "returns straight to site {}", call_site_id),
+            ReturnStrategy::Chain(ref call_sites) => write!(f, "returns by checking sites {} in turn", call_sites.iter().map(usize::to_string).collect::<Vec<String>>().join(", ")),
+            ReturnStrategy::BinarySearch(ref call_sites) => write!(f, "returns by a binary search over {} sites", call_sites.len()),
+        }
+    }
+}
+
+/// a user defined function, lowered
+#[derive(Debug)]
+pub struct MirFunc {
+    pub func_id: usize,
+    /// the arguments live in Local(1) through Local(arg_count)
+    pub arg_count: usize,
+    /// how many slots (arguments, locals and temporaries) the frame needs, not counting the call site id
+    pub frame_size: usize,
+    pub body: Vec<MirStmnt>,
+    /// a numeric expression, to be pushed onto ⌊RES
+    pub result: MirExpr,
+    pub returns: ReturnStrategy,
+}
+impl MirFunc {
+    /// how many statements the body has, counting the ones nested in blocks and the result as one more
+    fn size(&self) -> usize {
+        let mut size = 1;
+        walk_stmnts(&self.body, &mut |_| size += 1);
+        size
+    }
+}
+
+#[derive(Debug)]
+pub struct MirProgram {
+    pub global_count: usize,
+    pub main: Vec<MirStmnt>,
+    /// the value of the last top level s-expr
+    pub result: MirExpr,
+    pub funcs: Vec<MirFunc>,
+    /// the functions whose bodies were put where they were called, with the call sites they replaced
+    pub inlined: Vec<(usize, Vec<usize>)>,
+}
+
+impl MirProgram {
+    /// visit every statement of the program, including the ones nested in blocks
+    pub fn visit_stmnts(&self, visit: &mut dyn FnMut(&MirStmnt)) {
+        walk_stmnts(&self.main, visit);
+        for func in self.funcs.iter() {
+            walk_stmnts(&func.body, visit);
+        }
+    }
+
+    /// every function of this type that is ever made into a value, so could be called through one
+    pub fn closure_candidates(&self, m: &Manager, typ: &Type) -> Vec<usize> {
+        let mut candidates = vec![];
+        self.visit_stmnts(&mut |stmnt| {
+            if let MirStmnt::MakeClosure { func_id, .. } = *stmnt {
+                if !candidates.contains(&func_id) && m.func_manager.func_type(func_id) == *typ {
+                    candidates.push(func_id);
+                }
+            }
+        });
+        candidates.sort();
+        candidates
+    }
+
+    /// the slots a direct call to this function pushes onto ⌊ARGS, not counting the call site id,
+    /// or nothing when its body was put in place of every call
+    pub fn frame_size(&self, func_id: usize) -> Option<usize> {
+        // generic functions have no MIR of their own, so the functions are not in order of their ids
+        self.funcs.iter().find(|func| func.func_id == func_id).map(|func| func.frame_size)
+    }
+
+    /// the slots a call through a function value of this type pushes onto ⌊ARGS, not counting the call site id.
+    /// the frame has to fit whichever function gets called, as well as the record after the arguments
+    pub fn value_frame_size(&self, m: &Manager, typ: &Type, arg_count: usize) -> usize {
+        let candidates = self.closure_candidates(m, typ);
+        self.funcs
+            .iter()
+            .filter(|func| candidates.contains(&func.func_id))
+            .map(|func| func.frame_size)
+            .fold(arg_count + 1, usize::max)
+    }
+
+    /// every call site that could invoke the given function, in order
+    pub fn call_sites_of(&self, m: &Manager, func_id: usize) -> Vec<usize> {
+        let mut call_sites = vec![];
+        self.visit_stmnts(&mut |stmnt| match *stmnt {
+            MirStmnt::InvokeFunc { call_site_id, func_id: callee, .. } if callee == func_id => call_sites.push(call_site_id),
+            MirStmnt::InvokeValue { call_site_id, ref typ, .. } if self.closure_candidates(m, typ).contains(&func_id) => call_sites.push(call_site_id),
+            _ => {}
+        });
+        call_sites.sort();
+        call_sites
+    }
+
+    /// a function that can have its body put where it is called from instead:
+    /// it is only ever called directly, and never by itself
+    fn can_inline(&self, m: &Manager, func: &MirFunc, call_sites: &[usize]) -> bool {
+        let mut direct = vec![];
+        self.visit_stmnts(&mut |stmnt| match *stmnt {
+            MirStmnt::InvokeFunc { call_site_id, func_id, .. } if func_id == func.func_id => direct.push(call_site_id),
+            _ => {}
+        });
+        direct.sort();
+        let mut recursive = false;
+        walk_stmnts(&func.body, &mut |stmnt| match *stmnt {
+            MirStmnt::InvokeFunc { func_id, .. } | MirStmnt::Restart { func_id, .. } if func_id == func.func_id => recursive = true,
+            MirStmnt::InvokeValue { ref typ, .. } if self.closure_candidates(m, typ).contains(&func.func_id) => recursive = true,
+            _ => {}
+        });
+        !call_sites.is_empty() && direct == call_sites && !recursive
+    }
+
+    /// put the body of the function at this index where each of these calls is, instead of calling it.
+    /// its frame becomes part of each caller's
+    fn inline(&mut self, m: &mut Manager, index: usize, call_sites: Vec<usize>) -> Result<(), Error> {
+        let mut callee = self.funcs.remove(index);
+        for (count, &call_site_id) in call_sites.iter().enumerate() {
+            if count > 0 {
+                // the calls in each copy after the first need call sites of their own to return to
+                renumber_call_sites(m, &mut callee.body)?;
+            }
+            // globals count from 0 while locals count from 1, past the call site id
+            let global_base = self.global_count;
+            let to_global = |place: MirPlace| match place {
+                MirPlace::Local(offset) => MirPlace::Global(global_base + offset - 1),
+                global => global,
+            };
+            if replace_call(&mut self.main, call_site_id, &callee, &to_global) {
+                self.global_count += callee.frame_size;
+                continue;
+            }
+            for func in self.funcs.iter_mut() {
+                let local_base = func.frame_size;
+                let to_local = |place: MirPlace| match place {
+                    MirPlace::Local(offset) => MirPlace::Local(local_base + offset),
+                    global => global,
+                };
+                if replace_call(&mut func.body, call_site_id, &callee, &to_local) {
+                    func.frame_size += callee.frame_size;
+                    break;
+                }
+            }
+        }
+        self.inlined.push((callee.func_id, call_sites));
+        Ok(())
+    }
+
+    /// inline every function that is only called from one place, or that has at most `inline_threshold` statements counting its result,
+    /// then settle how the rest return
+    fn inline_and_plan_returns(&mut self, m: &mut Manager, inline_threshold: usize) -> Result<(), Error> {
+        loop {
+            let inlinable = self.funcs.iter().enumerate().find_map(|(index, func)| {
+                let call_sites = self.call_sites_of(m, func.func_id);
+                let small = call_sites.len() == 1 || func.size() <= inline_threshold;
+                if small && self.can_inline(m, func, &call_sites) { Some((index, call_sites)) } else { None }
+            });
+            match inlinable {
+                Some((index, call_sites)) => self.inline(m, index, call_sites)?,
+                None => break,
+            }
+        }
+        for index in 0..self.funcs.len() {
+            let call_sites = self.call_sites_of(m, self.funcs[index].func_id);
+            self.funcs[index].returns = ReturnStrategy::for_call_sites(call_sites);
+        }
+        Ok(())
+    }
+}
+
+/// hands out the places of a single frame
+struct Frame {
+    is_global: bool,
+    size: usize,
+    /// map (scope index, variable id) to where the variable lives
+    variables: HashMap<(usize, usize), MirPlace>,
+    /// the calls the function makes to itself as the last thing it does
+    tail_calls: Vec<SexprId>,
+    /// set by those calls, so that the function starts over once it is out of every block
+    restart_flag: Option<MirPlace>,
+}
+impl Frame {
+    fn new(is_global: bool) -> Frame {
+        Frame {
+            is_global,
+            size: 0,
+            variables: HashMap::new(),
+            tail_calls: vec![],
+            restart_flag: None,
+        }
+    }
+    /// allocate a fresh place in this frame
+    fn prealloc(&mut self) -> MirPlace {
+        self.size += 1;
+        if self.is_global {
+            MirPlace::Global(self.size - 1)
+        } else {
+            MirPlace::Local(self.size)
+        }
+    }
+    /// allocate a fresh place for the given variable
+    fn bind(&mut self, scope_index: usize, var_id: usize) -> MirPlace {
+        let place = self.prealloc();
+        self.variables.insert((scope_index, var_id), place);
+        place
+    }
 }
 
 impl Manager {
-    fn make_mir(&self, sexpr_id: SexprId, mir: &mut Vec<MirStmnt>) -> Result<MirExpr, Error> {
-        use std::ops::Deref;
-        match *self.sexpr(sexpr_id).kind.deref() {
-            SexprKind::Declare {ref variable_pattern, expr, body} => {
-                let at = self.prealloc();
-                let expr = self.make_mir(*expr, mir);
-                mir.push(MirStmnt::WriteAtFixed {at, expr});
-                self.make_mir(*body, mir);
+    /// find where the variable that this name resolves to is stored
+    fn lookup_place(&self, sexpr_id: SexprId, name: &str, frame: &Frame) -> MirPlace {
+        let (scope_id, var_id) = self.resolve_variable(self.scope_of(sexpr_id), name).expect("unresolved variable in mir");
+        *frame.variables.get(&(scope_id.index, var_id)).expect("variable was never given a place")
+    }
+
+    /// write a value of the given type to a place
+    fn store(&self, at: MirPlace, expr: MirExpr, typ: &Type, mir: &mut Vec<MirStmnt>) {
+        match (typ, expr) {
+            (_, MirExpr::Void) => {},
+            (_, _) if typ.is_void() => {},
+            (&Type::String, MirExpr::Unpack(handle)) => mir.push(MirStmnt::WriteAtFixed { at, expr: *handle }),
+            (&Type::String, expr) => mir.push(MirStmnt::Pack { at, expr }),
+            (_, expr) => mir.push(MirStmnt::WriteAtFixed { at, expr }),
+        }
+    }
+
+    /// read a value of the given type from a place
+    fn read(&self, at: MirPlace, typ: &Type) -> MirExpr {
+        match *typ {
+            _ if typ.is_void() => MirExpr::Void,
+            Type::String => MirExpr::Unpack(Box::new(MirExpr::Read(at))),
+            _ => MirExpr::Read(at),
+        }
+    }
+
+    /// get a numeric version of this value, packing strings into Str0 if need be
+    /// used for writing to frames and the heap
+    fn as_numeric(&self, expr: MirExpr, typ: &Type, frame: &mut Frame, mir: &mut Vec<MirStmnt>) -> MirExpr {
+        match (typ, expr) {
+            (_, _) if typ.is_void() => MirExpr::Num("0".to_string()),
+            (&Type::String, MirExpr::Unpack(handle)) => *handle,
+            (&Type::String, expr) => {
+                let at = frame.prealloc();
+                mir.push(MirStmnt::Pack { at, expr });
+                MirExpr::Read(at)
+            }
+            (_, expr) => expr,
+        }
+    }
+
+    /// a value of the given type from the number it is kept as in ⌊OBJ
+    fn read_word(&self, word: MirExpr, typ: &Type) -> MirExpr {
+        match *typ {
+            Type::String => MirExpr::Unpack(Box::new(word)),
+            _ => word,
+        }
+    }
+
+    /// a handle that can be read more than once, saving it to a place of its own unless it is cheap to read
+    fn reusable(&self, expr: MirExpr, frame: &mut Frame, mir: &mut Vec<MirStmnt>) -> MirExpr {
+        match expr {
+            MirExpr::Read(_) => expr,
+            _ if expr.is_constant() => expr,
+            _ => {
+                let at = frame.prealloc();
+                mir.push(MirStmnt::WriteAtFixed { at, expr });
+                MirExpr::Read(at)
+            }
+        }
+    }
+
+    /// the word of a list's element. indices count from 0, while ⌊OBJ counts from 1 like every TI list,
+    /// so the header says where in ⌊OBJ the element at index 0 is.
+    /// an index outside of the list points at ⌊OBJ(0), which stops the program with ERR:INVALID DIM
+    fn list_element(&self, list: MirExpr, index: MirExpr, frame: &mut Frame, mir: &mut Vec<MirStmnt>) -> MirExpr {
+        let list = self.reusable(list, frame, mir);
+        let index = self.reusable(index, frame, mir);
+        let start = MirExpr::ObjGet { base: Box::new(list.clone()), offset: 1 };
+        let len = MirExpr::ObjGet { base: Box::new(list), offset: 0 };
+        MirExpr::Fmt { fmt_str: "({0}+{1})*(0≤{1} and {1}<{2})".to_string(), handles: vec![start, index, len] }
+    }
+
+    /// copy `len` words of ⌊OBJ from `from` to `to`, one after the other
+    fn copy_words(&self, from: MirExpr, to: MirExpr, len: MirExpr, frame: &mut Frame, mir: &mut Vec<MirStmnt>) {
+        let counter = frame.prealloc();
+        mir.push(MirStmnt::WriteAtFixed { at: counter, expr: MirExpr::Num("0".to_string()) });
+        let body = vec![
+            MirStmnt::ObjSet {
+                base: MirExpr::Fmt { fmt_str: "{0}+{1}".to_string(), handles: vec![to, MirExpr::Read(counter)] },
+                offset: 0,
+                expr: MirExpr::ObjGet { base: Box::new(MirExpr::Fmt { fmt_str: "{0}+{1}".to_string(), handles: vec![from, MirExpr::Read(counter)] }), offset: 0 },
+            },
+            MirStmnt::WriteAtFixed { at: counter, expr: MirExpr::Fmt { fmt_str: "{0}+1".to_string(), handles: vec![MirExpr::Read(counter)] } },
+        ];
+        let predicate = MirExpr::Fmt { fmt_str: "({0}<{1})".to_string(), handles: vec![MirExpr::Read(counter), len] };
+        mir.push(MirStmnt::WhileBlock { predicate_stmnts: vec![], predicate, body });
+    }
+
+    /// lower the arguments of a call, as the numbers that go into the callee's frame
+    fn make_args(&self, exprs: &[SexprId], frame: &mut Frame, mir: &mut Vec<MirStmnt>) -> Result<Vec<MirExpr>, Error> {
+        let handles = self.make_mir_all(exprs, frame, mir)?;
+        Ok(handles
+            .into_iter()
+            .zip(exprs.iter())
+            .map(|(handle, expr)| self.as_numeric(handle, self.result_type(*expr), frame, mir))
+            .collect())
+    }
+
+    /// a function as a value: a record of which one it is and what it captured
+    fn make_closure(&self, func_id: usize, frame: &mut Frame, mir: &mut Vec<MirStmnt>) -> MirExpr {
+        let captures = self.func_manager.captures[func_id]
+            .iter()
+            .map(|key| MirExpr::Read(*frame.variables.get(key).expect("captured variable was never given a place")))
+            .collect();
+        let at = frame.prealloc();
+        mir.push(MirStmnt::MakeClosure { at, func_id, captures });
+        MirExpr::Read(at)
+    }
+
+    /// the calls that a function makes to itself as the last thing it does, which can reuse its frame
+    pub fn self_tail_calls(&self, func_id: usize) -> Vec<SexprId> {
+        let mut calls = vec![];
+        self.find_tail_calls(self.func_manager.body[func_id], func_id, &mut calls);
+        calls
+    }
+    fn find_tail_calls(&self, sexpr_id: SexprId, func_id: usize, calls: &mut Vec<SexprId>) {
+        match *self.kind(sexpr_id) {
+            SexprKind::Block { ref statements } => if let Some(&last) = statements.back() {
+                self.find_tail_calls(last, func_id, calls);
+            },
+            SexprKind::IfSwitch { if_branch, else_branch, .. } => {
+                self.find_tail_calls(if_branch, func_id, calls);
+                self.find_tail_calls(else_branch, func_id, calls);
+            }
+            SexprKind::Declare { body, .. } => self.find_tail_calls(body, func_id, calls),
+            SexprKind::Match { ref arms, .. } => for arm in arms {
+                self.find_tail_calls(arm.body, func_id, calls);
+            },
+            SexprKind::FuncCall { func_id: callee, .. } if callee == func_id => calls.push(sexpr_id),
+            _ => {}
+        }
+    }
+
+    /// lower each s-expr in order, returning their handles
+    /// if the code for a later s-expr could change what an earlier handle reads, the earlier handle is saved first
+    fn make_mir_all(&self, sexpr_ids: &[SexprId], frame: &mut Frame, mir: &mut Vec<MirStmnt>) -> Result<Vec<MirExpr>, Error> {
+        let mut handles: Vec<MirExpr> = vec![];
+        for (index, &sexpr_id) in sexpr_ids.iter().enumerate() {
+            let mut stmnts = vec![];
+            let handle = self.make_mir(sexpr_id, frame, &mut stmnts)?;
+            if !stmnts.is_empty() {
+                for earlier in 0..index {
+                    if handles[earlier].is_constant() {
+                        continue;
+                    }
+                    let typ = self.result_type(sexpr_ids[earlier]);
+                    let at = frame.prealloc();
+                    let saved = self.read(at, typ);
+                    let old = ::std::mem::replace(&mut handles[earlier], saved);
+                    self.store(at, old, typ, mir);
+                }
+            }
+            mir.extend(stmnts);
+            handles.push(handle);
+        }
+        Ok(handles)
+    }
+
+    fn make_mir(&self, sexpr_id: SexprId, frame: &mut Frame, mir: &mut Vec<MirStmnt>) -> Result<MirExpr, Error> {
+        let kind = self.kind(sexpr_id).clone();
+        let typ = self.result_type(sexpr_id).clone();
+        Ok(match kind {
+            SexprKind::Declare {ref variable_pattern, expr, body, ..} => {
+                let (scope_id, var_id) = self.resolve_variable(self.scope_of(body), variable_pattern).expect("unresolved variable in mir");
+                let expr_type = self.result_type(expr).clone();
+                let expr = self.make_mir(expr, frame, mir)?;
+                let at = frame.bind(scope_id.index, var_id);
+                self.store(at, expr, &expr_type, mir);
+                self.make_mir(body, frame, mir)?
             },
             SexprKind::Assign {ref variable_pattern, expr} => {
-                let expr = self.make_mir(*expr, mir);
-                mir.push(MirStmnt::WriteAtFixed {at, expr});
+                let at = self.lookup_place(sexpr_id, variable_pattern, frame);
+                let expr = self.make_mir(expr, frame, mir)?;
+                self.store(at, expr, &typ, mir);
+                self.read(at, &typ)
             }
             SexprKind::IfSwitch {predicate, if_branch, else_branch} => {
-                let predicate = self.make_mir(*predicate, mir);
-                let if_stmnts = vec![];
-                let if_expr = self.make_mir(*if_branch, &mut if_stmnts);
-                let else_stmnts = vec![];
-                let else_expr = self.make_mir(*else_branch, &mut else_stmnts);
-                mir.push(MirStmnt::IfBlock{predicate, if_stmnts, if_expr, else_stmnts, else_expr});
+                let predicate = self.make_mir(predicate, frame, mir)?;
+                let at = frame.prealloc();
+                let mut if_stmnts = vec![];
+                let if_expr = self.make_mir(if_branch, frame, &mut if_stmnts)?;
+                self.store(at, if_expr, &typ, &mut if_stmnts);
+                let mut else_stmnts = vec![];
+                let else_expr = self.make_mir(else_branch, frame, &mut else_stmnts)?;
+                self.store(at, else_expr, &typ, &mut else_stmnts);
+                mir.push(MirStmnt::IfBlock{predicate, if_stmnts, else_stmnts});
+                self.read(at, &typ)
             }
             SexprKind::WhileLoop {predicate, body} => {
-                unimplemented!()
+                let mut predicate_stmnts = vec![];
+                let mut predicate = self.make_mir(predicate, frame, &mut predicate_stmnts)?;
+                if !predicate_stmnts.is_empty() {
+                    let at = frame.prealloc();
+                    predicate_stmnts.push(MirStmnt::WriteAtFixed { at, expr: predicate });
+                    predicate = MirExpr::Read(at);
+                }
+                let at = frame.prealloc();
+                let mut body_stmnts = vec![];
+                let body_expr = self.make_mir(body, frame, &mut body_stmnts)?;
+                self.store(at, body_expr, &typ, &mut body_stmnts);
+                mir.push(MirStmnt::WhileBlock { predicate_stmnts, predicate, body: body_stmnts });
+                self.read(at, &typ)
             }
             SexprKind::Block {ref statements} => {
-                for ref statement in statements {
-                    self.make_mir(**statement)?;
+                let mut expr = MirExpr::empty();
+                for statement in statements {
+                    expr = self.make_mir(*statement, frame, mir)?;
                 }
+                expr
+            }
+            SexprKind::StructGet { id, expr, ref field } => {
+                let struct_id = id.expect("struct id should have been logged in typechecking");
+                let base = self.make_mir(expr, frame, mir)?;
+                let offset = self.udt_manager.get_field_offset(struct_id, field);
+                self.read_word(MirExpr::ObjGet { base: Box::new(base), offset }, &typ)
+            }
+            SexprKind::StructSet { id, expr, ref field, value } => {
+                let struct_id = id.expect("struct id should have been logged in typechecking");
+                let mut handles = self.make_mir_all(&[expr, value], frame, mir)?;
+                let value_handle = handles.pop().unwrap();
+                let base = handles.pop().unwrap();
+                let offset = self.udt_manager.get_field_offset(struct_id, field);
+                let expr = self.as_numeric(value_handle, self.result_type(value), frame, mir);
+                mir.push(MirStmnt::ObjSet { base, offset, expr });
+                MirExpr::Void
+            }
+            SexprKind::List { ref elements } => {
+                let elements = elements.iter().cloned().collect::<Vec<SexprId>>();
+                let handles = self.make_mir_all(&elements, frame, mir)?;
+                let words = handles
+                    .into_iter()
+                    .zip(elements.iter())
+                    .map(|(handle, element)| self.as_numeric(handle, self.result_type(*element), frame, mir))
+                    .collect::<Vec<MirExpr>>();
+                // the header is the length and where the elements start, which is right after it
+                let at = frame.prealloc();
+                mir.push(MirStmnt::Malloc { at, width: MirExpr::Num((words.len() + 2).to_string()) });
+                mir.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset: 0, expr: MirExpr::Num(words.len().to_string()) });
+                mir.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset: 1, expr: MirExpr::Fmt { fmt_str: "{0}+2".to_string(), handles: vec![MirExpr::Read(at)] } });
+                for (index, expr) in words.into_iter().enumerate() {
+                    mir.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset: index + 2, expr });
+                }
+                MirExpr::Read(at)
+            }
+            SexprKind::ListGet { list, index } => {
+                let mut handles = self.make_mir_all(&[list, index], frame, mir)?;
+                let index = handles.pop().unwrap();
+                let list = handles.pop().unwrap();
+                let element = self.list_element(list, index, frame, mir);
+                self.read_word(MirExpr::ObjGet { base: Box::new(element), offset: 0 }, &typ)
             }
-            SexprKind::StructGet { id:_, expr, field:_ } => {
-                unimplemented!()
+            SexprKind::ListSet { list, index, elem } => {
+                let mut handles = self.make_mir_all(&[list, index, elem], frame, mir)?;
+                let elem_handle = handles.pop().unwrap();
+                let index = handles.pop().unwrap();
+                let list = handles.pop().unwrap();
+                let expr = self.as_numeric(elem_handle, self.result_type(elem), frame, mir);
+                let base = self.list_element(list, index, frame, mir);
+                mir.push(MirStmnt::ObjSet { base, offset: 0, expr });
+                MirExpr::Void
             }
-            SexprKind::StructSet { id:_, expr, field:_, value } => {
-                unimplemented!()
+            SexprKind::ListLen { list } => {
+                let list = self.make_mir(list, frame, mir)?;
+                MirExpr::ObjGet { base: Box::new(list), offset: 0 }
             }
-            SexprKind::FuncDef { func_id } => {
-                unimplemented!()
+            SexprKind::ListPush { list, elem } => {
+                let mut handles = self.make_mir_all(&[list, elem], frame, mir)?;
+                let elem_handle = handles.pop().unwrap();
+                let expr = self.as_numeric(elem_handle, self.result_type(elem), frame, mir);
+                let list = self.reusable(handles.pop().unwrap(), frame, mir);
+                let (len, start) = (frame.prealloc(), frame.prealloc());
+                mir.push(MirStmnt::WriteAtFixed { at: len, expr: MirExpr::ObjGet { base: Box::new(list.clone()), offset: 0 } });
+                mir.push(MirStmnt::WriteAtFixed { at: start, expr: MirExpr::ObjGet { base: Box::new(list.clone()), offset: 1 } });
+                // the elements grow in place when nothing comes after them in ⌊OBJ, and are moved to the end first when something does
+                let moved = frame.prealloc();
+                let mut move_stmnts = vec![MirStmnt::WriteAtFixed { at: moved, expr: MirExpr::Fmt { fmt_str: "dim(⌊OBJ)+1".to_string(), handles: vec![] } }];
+                self.copy_words(MirExpr::Read(start), MirExpr::Read(moved), MirExpr::Read(len), frame, &mut move_stmnts);
+                move_stmnts.push(MirStmnt::WriteAtFixed { at: start, expr: MirExpr::Read(moved) });
+                move_stmnts.push(MirStmnt::ObjSet { base: list.clone(), offset: 1, expr: MirExpr::Read(start) });
+                mir.push(MirStmnt::IfBlock {
+                    predicate: MirExpr::Fmt { fmt_str: "({0}+{1}≠dim(⌊OBJ)+1)".to_string(), handles: vec![MirExpr::Read(start), MirExpr::Read(len)] },
+                    if_stmnts: move_stmnts,
+                    else_stmnts: vec![],
+                });
+                mir.push(MirStmnt::ObjSet { base: MirExpr::Fmt { fmt_str: "{0}+{1}".to_string(), handles: vec![MirExpr::Read(start), MirExpr::Read(len)] }, offset: 0, expr });
+                mir.push(MirStmnt::ObjSet { base: list, offset: 0, expr: MirExpr::Fmt { fmt_str: "{0}+1".to_string(), handles: vec![MirExpr::Read(len)] } });
+                MirExpr::Void
             }
-            SexprKind::StructDef { id } => {
-                unimplemented!()
+            SexprKind::ListMap { target, func, list } => {
+                let element_type = match *self.result_type(list) {
+                    Type::List(ref element_type) => (**element_type).clone(),
+                    ref typ => panic!("type checking let a {} through where a list was expected", typ),
+                };
+                let out_type = match typ {
+                    Type::List(ref out_type) => (**out_type).clone(),
+                    ref typ => panic!("list-map was type checked as {}", typ),
+                };
+                // a function value is read before the list, like the arguments of any other call
+                let callee = match target {
+                    Some(MapTarget::Value { .. }) => {
+                        let callee = self.make_mir(func, frame, mir)?;
+                        let at = frame.prealloc();
+                        mir.push(MirStmnt::WriteAtFixed { at, expr: callee });
+                        Some(MirExpr::Read(at))
+                    }
+                    _ => None,
+                };
+                let list = self.make_mir(list, frame, mir)?;
+                let list = self.reusable(list, frame, mir);
+                let len = frame.prealloc();
+                mir.push(MirStmnt::WriteAtFixed { at: len, expr: MirExpr::ObjGet { base: Box::new(list.clone()), offset: 0 } });
+                // the whole list is made before anything is called, since what is called might use ⌊OBJ too
+                let at = frame.prealloc();
+                mir.push(MirStmnt::Malloc { at, width: MirExpr::Fmt { fmt_str: "{0}+2".to_string(), handles: vec![MirExpr::Read(len)] } });
+                mir.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset: 0, expr: MirExpr::Read(len) });
+                mir.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset: 1, expr: MirExpr::Fmt { fmt_str: "{0}+2".to_string(), handles: vec![MirExpr::Read(at)] } });
+                let counter = frame.prealloc();
+                mir.push(MirStmnt::WriteAtFixed { at: counter, expr: MirExpr::Num("0".to_string()) });
+
+                let mut body = vec![];
+                let element_word = MirExpr::ObjGet {
+                    base: Box::new(MirExpr::Fmt { fmt_str: "{0}+{1}".to_string(), handles: vec![MirExpr::ObjGet { base: Box::new(list), offset: 1 }, MirExpr::Read(counter)] }),
+                    offset: 0,
+                };
+                let element = self.read_word(element_word, &element_type);
+                let value = match target.expect("list-map target should have been settled in type checking") {
+                    MapTarget::Func { func_id, call_id } => {
+                        let args = vec![self.as_numeric(element, &element_type, frame, &mut body)];
+                        let result = frame.prealloc();
+                        body.push(MirStmnt::InvokeFunc { call_site_id: call_id, func_id, args, result: Some(result) });
+                        self.read(result, &out_type)
+                    }
+                    MapTarget::Value { call_id } => {
+                        let args = vec![self.as_numeric(element, &element_type, frame, &mut body)];
+                        let result = frame.prealloc();
+                        let typ = self.result_type(func).clone();
+                        body.push(MirStmnt::InvokeValue { call_site_id: call_id, callee: callee.expect("list-map through a value without the value"), typ, args, result: Some(result) });
+                        self.read(result, &out_type)
+                    }
+                    MapTarget::StructInit { id: _ } => {
+                        let expr = self.as_numeric(element, &element_type, frame, &mut body);
+                        let at = frame.prealloc();
+                        body.push(MirStmnt::Malloc { at, width: MirExpr::Num("1".to_string()) });
+                        body.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset: 0, expr });
+                        MirExpr::Read(at)
+                    }
+                    MapTarget::BuiltIn { id } => {
+                        let code = &self.builtin_manager.code[id];
+                        if !code.is_empty() {
+                            body.push(MirStmnt::Exec { fmt_str: code.clone(), handles: vec![element.clone()] });
+                        }
+                        MirExpr::Fmt { fmt_str: self.builtin_manager.handle[id].clone(), handles: vec![element] }
+                    }
+                };
+                let expr = self.as_numeric(value, &out_type, frame, &mut body);
+                body.push(MirStmnt::ObjSet {
+                    base: MirExpr::Fmt { fmt_str: "{0}+2+{1}".to_string(), handles: vec![MirExpr::Read(at), MirExpr::Read(counter)] },
+                    offset: 0,
+                    expr,
+                });
+                body.push(MirStmnt::WriteAtFixed { at: counter, expr: MirExpr::Fmt { fmt_str: "{0}+1".to_string(), handles: vec![MirExpr::Read(counter)] } });
+                let predicate = MirExpr::Fmt { fmt_str: "({0}<{1})".to_string(), handles: vec![MirExpr::Read(counter), MirExpr::Read(len)] };
+                mir.push(MirStmnt::WhileBlock { predicate_stmnts: vec![], predicate, body });
+                MirExpr::Read(at)
+            }
+            SexprKind::FuncDef { .. } | SexprKind::StructDef { .. } | SexprKind::EnumDef { .. } => {
+                // definitions have no in-place code
+                MirExpr::Void
             }
             SexprKind::Format { ref exprs } => {
-                unimplemented!()
+                let exprs = exprs.iter().cloned().collect::<Vec<SexprId>>();
+                let handles = self.make_mir_all(&exprs, frame, mir)?;
+                let pieces = handles
+                    .into_iter()
+                    .zip(exprs.iter().map(|expr| self.result_type(*expr).clone()))
+                    .collect::<Vec<(MirExpr, Type)>>();
+                let at = frame.prealloc();
+                let utils = (frame.prealloc(), frame.prealloc());
+                mir.push(MirStmnt::Format { at, utils, pieces });
+                self.read(at, &Type::String)
             }
-            SexprKind::Other { ref opt_exprs } => {
-                unimplemented!()
+            SexprKind::Other { .. } => panic!("we should not be lowering a SexprKind::Other (it should have been realized in type checking)"),
+            SexprKind::FuncCall { ref exprs, .. } if frame.tail_calls.iter().any(|call| call.index == sexpr_id.index) => {
+                let exprs = exprs.iter().cloned().collect::<Vec<SexprId>>();
+                let args = self.make_args(&exprs, frame, mir)?;
+                // the new arguments are all worked out before any of the old ones are written over
+                let args = args
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, arg)| match arg {
+                        MirExpr::Read(MirPlace::Local(offset)) if offset == index + 1 => None,
+                        arg if arg.is_constant() => Some(arg),
+                        arg => {
+                            let at = frame.prealloc();
+                            mir.push(MirStmnt::WriteAtFixed { at, expr: arg });
+                            Some(MirExpr::Read(at))
+                        }
+                    })
+                    .collect::<Vec<Option<MirExpr>>>();
+                for (index, arg) in args.into_iter().enumerate() {
+                    if let Some(expr) = arg {
+                        mir.push(MirStmnt::WriteAtFixed { at: MirPlace::Local(index + 1), expr });
+                    }
+                }
+                let flag = frame.restart_flag.expect("a tail call in a frame without a restart flag");
+                mir.push(MirStmnt::WriteAtFixed { at: flag, expr: MirExpr::Num("1".to_string()) });
+                // nothing is returned, since the function starts over
+                MirExpr::Void
             }
-            // we don't know these exist yet
-            SexprKind::FuncCall {..} => {
-                unimplemented!()
+            SexprKind::FuncCall { func_id, call_id, ref exprs } => {
+                let exprs = exprs.iter().cloned().collect::<Vec<SexprId>>();
+                let mut args = self.make_args(&exprs, frame, mir)?;
+                // a function that captures variables gets their values now, in a record after its arguments
+                let width = self.func_manager.captures[func_id].len() + 1;
+                let env = if width > 1 { Some(self.make_closure(func_id, frame, mir)) } else { None };
+                args.extend(env.clone());
+                let result = if typ.is_void() { None } else { Some(frame.prealloc()) };
+                mir.push(MirStmnt::InvokeFunc { call_site_id: call_id, func_id, args, result });
+                if let Some(base) = env {
+                    mir.push(MirStmnt::Free { base, width });
+                }
+                result.map_or(MirExpr::Void, |at| self.read(at, &typ))
             }
-            SexprKind::StructInit {..} => {
-                unimplemented!()
+            SexprKind::ValueCall { call_id, ref exprs } => {
+                let exprs = exprs.iter().cloned().collect::<Vec<SexprId>>();
+                let args = self.make_args(&exprs, frame, mir)?;
+                // the variable is read after the arguments, which could assign to it
+                let (scope_id, var_id) = self.resolve_variable(self.scope_of(sexpr_id), self.text(sexpr_id)).expect("unresolved variable in mir");
+                let callee_type = self.scope(scope_id).variable_types[var_id].clone().expect("typeless variable");
+                let callee = MirExpr::Read(self.lookup_place(sexpr_id, self.text(sexpr_id), frame));
+                let result = if typ.is_void() { None } else { Some(frame.prealloc()) };
+                mir.push(MirStmnt::InvokeValue { call_site_id: call_id, callee, typ: callee_type, args, result });
+                result.map_or(MirExpr::Void, |at| self.read(at, &typ))
             }
-            SexprKind::Builtin {..} => {
-                unimplemented!()
+            SexprKind::Lambda { func_id } | SexprKind::FuncRef { func_id } => self.make_closure(func_id, frame, mir),
+            SexprKind::StructInit { id, ref exprs } => {
+                let exprs = exprs.iter().cloned().collect::<Vec<SexprId>>();
+                let handles = self.make_mir_all(&exprs, frame, mir)?;
+                let words = handles
+                    .into_iter()
+                    .zip(exprs.iter())
+                    .map(|(handle, expr)| self.as_numeric(handle, self.result_type(*expr), frame, mir))
+                    .collect::<Vec<MirExpr>>();
+                let at = frame.prealloc();
+                mir.push(MirStmnt::Malloc { at, width: MirExpr::Num(self.udt_manager.args[id].len().to_string()) });
+                for (offset, expr) in words.into_iter().enumerate() {
+                    mir.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset, expr });
+                }
+                MirExpr::Read(at)
+            }
+            SexprKind::EnumInit { variant, ref exprs, .. } => {
+                let exprs = exprs.iter().cloned().collect::<Vec<SexprId>>();
+                let handles = self.make_mir_all(&exprs, frame, mir)?;
+                let words = handles
+                    .into_iter()
+                    .zip(exprs.iter())
+                    .map(|(handle, expr)| self.as_numeric(handle, self.result_type(*expr), frame, mir))
+                    .collect::<Vec<MirExpr>>();
+                let at = frame.prealloc();
+                // the first word says which variant this is, and its fields come after
+                mir.push(MirStmnt::Malloc { at, width: MirExpr::Num((words.len() + 1).to_string()) });
+                mir.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset: 0, expr: MirExpr::Num(variant.to_string()) });
+                for (offset, expr) in words.into_iter().enumerate() {
+                    mir.push(MirStmnt::ObjSet { base: MirExpr::Read(at), offset: offset + 1, expr });
+                }
+                MirExpr::Read(at)
+            }
+            SexprKind::Match { id, expr, ref arms } => {
+                let enum_id = id.expect("enum id should have been logged in typechecking");
+                let value = self.make_mir(expr, frame, mir)?;
+                let value = self.reusable(value, frame, mir);
+                let at = frame.prealloc();
+                // the arms are chained from the last one back, which is the one left when no other variant matched
+                let mut stmnts = vec![];
+                for (index, arm) in arms.iter().enumerate().rev() {
+                    let mut arm_stmnts = vec![];
+                    let variant = arm.variant.as_ref().map(|name| self.udt_manager.variant_index(enum_id, name).expect("unknown variant in mir"));
+                    if let Some(variant) = variant {
+                        let field_types = &self.udt_manager.variants[enum_id][variant].sgntr;
+                        let scope_index = self.scope_of(arm.body).index;
+                        for (offset, (binding, field_type)) in arm.bindings.iter().zip(field_types.iter()).enumerate() {
+                            let var_id = self.scope(self.scope_of(arm.body)).declared_variables[binding];
+                            let field_at = frame.bind(scope_index, var_id);
+                            let word = MirExpr::ObjGet { base: Box::new(value.clone()), offset: offset + 1 };
+                            self.store(field_at, self.read_word(word, field_type.unwrap()), field_type.unwrap(), &mut arm_stmnts);
+                        }
+                    }
+                    let body_expr = self.make_mir(arm.body, frame, &mut arm_stmnts)?;
+                    self.store(at, body_expr, &typ, &mut arm_stmnts);
+                    stmnts = match variant {
+                        Some(variant) if index + 1 < arms.len() => {
+                            let tag = MirExpr::ObjGet { base: Box::new(value.clone()), offset: 0 };
+                            let predicate = MirExpr::Fmt { fmt_str: "({0}={1})".to_string(), handles: vec![tag, MirExpr::Num(variant.to_string())] };
+                            vec![MirStmnt::IfBlock { predicate, if_stmnts: arm_stmnts, else_stmnts: stmnts }]
+                        }
+                        _ => arm_stmnts,
+                    };
+                }
+                mir.extend(stmnts);
+                self.read(at, &typ)
+            }
+            SexprKind::BuiltIn { id, ref exprs } => {
+                let exprs = exprs.iter().cloned().collect::<Vec<SexprId>>();
+                let handles = self.make_mir_all(&exprs, frame, mir)?;
+                let code = &self.builtin_manager.code[id];
+                if !code.is_empty() {
+                    mir.push(MirStmnt::Exec { fmt_str: code.clone(), handles: handles.clone() });
+                }
+                let handle = &self.builtin_manager.handle[id];
+                if handle.is_empty() || typ.is_void() {
+                    MirExpr::Void
+                } else {
+                    MirExpr::Fmt { fmt_str: handle.clone(), handles }
+                }
             }
             SexprKind::Identifier => {
-                return MirExpr::ReadAtFixed{};
+                let at = self.lookup_place(sexpr_id, self.text(sexpr_id), frame);
+                self.read(at, &typ)
             }
             SexprKind::RealLiteral | SexprKind::IntegerLiteral => {
-                unimplemented!()
+                let number = Number::parse(self.text(sexpr_id)).expect("the lexer let through a mangled number literal");
+                MirExpr::Num(number.ti_spelling())
             }
             SexprKind::StringLiteral => {
-                unimplemented!()
+                let text = self.text(sexpr_id);
+                MirExpr::Str(text[1..text.len() - 1].to_string())
             }
             SexprKind::BooleLiteral => {
-                unimplemented!()
+                MirExpr::Num(match self.text(sexpr_id) {
+                    "true"  => String::from("1"),
+                    "false" => String::from("0"),
+                    b => panic!("mangled boole value: {}", b)
+                })
             },
+        })
+    }
+
+    /// lower the body of a user defined function into its own frame
+    fn make_func_mir(&self, func_id: usize) -> Result<MirFunc, Error> {
+        let mut frame = Frame::new(false);
+        let body = self.func_manager.body[func_id];
+        let scope_index = self.scope_of(body).index;
+        let arg_count = self.func_manager.args[func_id].len();
+        for var_id in 0..arg_count {
+            // arguments are bound first, so their variable ids line up with their positions
+            frame.bind(scope_index, var_id);
+        }
+        let mut stmnts = vec![];
+        let captures = &self.func_manager.captures[func_id];
+        if !captures.is_empty() {
+            // the record this was called through comes right after the arguments, and holds the captured values
+            let env = frame.prealloc();
+            for (index, &(scope_index, var_id)) in captures.iter().enumerate() {
+                let at = frame.bind(scope_index, var_id);
+                stmnts.push(MirStmnt::WriteAtFixed { at, expr: MirExpr::ObjGet { base: Box::new(MirExpr::Read(env)), offset: index + 1 } });
+            }
         }
-        Ok(MirExpr::empty())
+        frame.tail_calls = self.self_tail_calls(func_id);
+        if !frame.tail_calls.is_empty() {
+            let flag = frame.prealloc();
+            frame.restart_flag = Some(flag);
+            stmnts.push(MirStmnt::WriteAtFixed { at: flag, expr: MirExpr::Num("0".to_string()) });
+        }
+        let expr = self.make_mir(body, &mut frame, &mut stmnts)?;
+        if let Some(flag) = frame.restart_flag {
+            stmnts.push(MirStmnt::Restart { func_id, predicate: MirExpr::Read(flag) });
+        }
+        let out_type = self.func_manager.out_type[func_id].unwrap();
+        let result = match expr {
+            // the body was nothing but a call to itself
+            MirExpr::Void if !out_type.is_void() => MirExpr::Num("0".to_string()),
+            expr => self.as_numeric(expr, out_type, &mut frame, &mut stmnts),
+        };
+        Ok(MirFunc {
+            func_id,
+            arg_count,
+            frame_size: frame.size,
+            body: stmnts,
+            result,
+            // settled once every function has been lowered
+            returns: ReturnStrategy::Unused,
+        })
     }
 }
 
-pub fn make_all_mir(m: &mut Manager) -> Result<Vec<MirSymbol>, Error> {
-
-}
\ No newline at end of file
+/// lower the program and every non-generic function, inlining the ones called once or with at most `inline_threshold` statements
+pub fn make_all_mir(m: &mut Manager, inline_threshold: usize) -> Result<MirProgram, Error> {
+    let mut frame = Frame::new(true);
+    let mut main = vec![];
+    let mut result = MirExpr::empty();
+    for sexpr_id in m.top_level_sexprs.iter() {
+        result = m.make_mir(*sexpr_id, &mut frame, &mut main)?;
+    }
+    let funcs = (0..m.func_manager.count)
+        .filter(|&func_id| !m.func_manager.is_generic(func_id))
+        .map(|func_id| m.make_func_mir(func_id))
+        .collect::<Result<Vec<MirFunc>, Error>>()?;
+    let mut program = MirProgram {
+        global_count: frame.size,
+        main,
+        result,
+        funcs,
+        inlined: vec![],
+    };
+    program.inline_and_plan_returns(m, inline_threshold)?;
+    Ok(program)
+}
@@ -0,0 +1,200 @@
+//! numeric literals: what counts as one, what it's worth, and how the calculator spells it
+
+use util::ErrorCode;
+
+/// the calculator keeps 14 significant digits
+const SIGNIFICANT_DIGITS: usize = 14;
+/// and its exponents run from ᴇ⁻99 up to ᴇ99
+const MAX_EXPONENT: i32 = 99;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Number {
+    pub is_int: bool,
+    pub negative: bool,
+    /// the significant digits, without leading or trailing zeros
+    digits: String,
+    /// where the decimal point goes: the value is 0.{digits} × 10^point
+    point: i32,
+}
+
+impl Number {
+    /// read a literal like `12`, `-4.5`, `.5e2`, `1ᴇ⁻3` or `0x1F`.
+    /// an int is written without a decimal point or exponent, everything else is a real
+    pub fn parse(text: &str) -> Result<Number, (ErrorCode, String)> {
+        let malformed = || (ErrorCode::UnrecognizedAtom, format!("`{}` is not a valid number", text));
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, text),
+        };
+        if let Some(hex) = unsigned.strip_prefix("0x").or_else(|| unsigned.strip_prefix("0X")) {
+            if hex.is_empty() || !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+                return Err(malformed());
+            }
+            let value = u64::from_str_radix(hex, 16).map_err(|_| (ErrorCode::UnrepresentableNumber, format!("`{}` is too large for the calculator to hold exactly", text)))?;
+            let digits = value.to_string();
+            return Number::from_digits(text, true, negative, &digits, digits.len() as i32);
+        }
+
+        // split off the exponent, which can be written as e, E or ᴇ, and signed with -, + or ⁻
+        let (mantissa, exponent) = match unsigned.find(['e', 'E', 'ᴇ']) {
+            Some(at) => {
+                let marker_len = unsigned[at..].chars().next().unwrap().len_utf8();
+                (&unsigned[..at], Some(&unsigned[at + marker_len..]))
+            }
+            None => (unsigned, None),
+        };
+        let exponent = match exponent {
+            Some(exponent) => {
+                let (sign, digits) = if let Some(digits) = exponent.strip_prefix('-').or_else(|| exponent.strip_prefix('⁻')) {
+                    (-1, digits)
+                } else {
+                    (1, exponent.strip_prefix('+').unwrap_or(exponent))
+                };
+                if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_digit()) {
+                    return Err(malformed());
+                }
+                // anything this long is out of range anyway, it just has to not overflow
+                Some(sign * digits.parse::<i32>().unwrap_or(i32::MAX / 2))
+            }
+            None => None,
+        };
+
+        let (whole, fraction) = match mantissa.find('.') {
+            Some(at) => (&mantissa[..at], Some(&mantissa[at + 1..])),
+            None => (mantissa, None),
+        };
+        let fraction_digits = fraction.unwrap_or("");
+        if whole.len() + fraction_digits.len() == 0
+            || !whole.chars().chain(fraction_digits.chars()).all(|ch| ch.is_ascii_digit()) {
+            return Err(malformed());
+        }
+        let is_int = fraction.is_none() && exponent.is_none();
+        let digits = format!("{}{}", whole, fraction_digits);
+        let point = whole.len() as i32 + exponent.unwrap_or(0);
+        Number::from_digits(text, is_int, negative, &digits, point)
+    }
+
+    /// `point` is where the decimal point goes in `digits`.
+    /// normalize the digits, then make sure the calculator can hold the number without losing anything
+    fn from_digits(text: &str, is_int: bool, negative: bool, digits: &str, point: i32) -> Result<Number, (ErrorCode, String)> {
+        let leading_zeros = digits.len() - digits.trim_start_matches('0').len();
+        let digits = digits.trim_start_matches('0').trim_end_matches('0').to_string();
+        let point = point - leading_zeros as i32;
+        if digits.is_empty() {
+            return Ok(Number { is_int, negative: false, digits, point: 0 });
+        }
+        if digits.len() > SIGNIFICANT_DIGITS {
+            return Err((ErrorCode::UnrepresentableNumber, format!("`{}` has more than the {} significant digits the calculator can hold", text, SIGNIFICANT_DIGITS)));
+        }
+        // the calculator writes numbers as d.ddd × 10^exponent, which is one less than our point
+        let exponent = point - 1;
+        if exponent > MAX_EXPONENT {
+            return Err((ErrorCode::UnrepresentableNumber, format!("`{}` is too large for the calculator, which goes up to 9.9999999999999ᴇ{}", text, MAX_EXPONENT)));
+        }
+        if exponent < -MAX_EXPONENT {
+            return Err((ErrorCode::UnrepresentableNumber, format!("`{}` is too small for the calculator, which goes down to 1ᴇ⁻{}", text, MAX_EXPONENT)));
+        }
+        Ok(Number { is_int, negative, digits, point })
+    }
+
+    pub fn value(&self) -> f64 {
+        if self.digits.is_empty() {
+            return 0.0;
+        }
+        let magnitude = format!("0.{}e{}", self.digits, self.point).parse::<f64>().unwrap();
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    /// how the calculator spells the number's magnitude, using ᴇ and ⁻ in the exponent when it needs one
+    pub fn ti_magnitude(&self) -> String {
+        let digits = &self.digits;
+        if digits.is_empty() {
+            return "0".to_string();
+        }
+        let len = digits.len() as i32;
+        if self.point >= len && self.point <= SIGNIFICANT_DIGITS as i32 {
+            // a whole number
+            format!("{}{}", digits, "0".repeat((self.point - len) as usize))
+        } else if self.point > 0 && self.point < len {
+            format!("{}.{}", &digits[..self.point as usize], &digits[self.point as usize..])
+        } else if self.point <= 0 && self.point > -4 {
+            format!(".{}{}", "0".repeat(-self.point as usize), digits)
+        } else {
+            let exponent = self.point - 1;
+            let rest = if len > 1 { format!(".{}", &digits[1..]) } else { String::new() };
+            let exponent_sign = if exponent < 0 { "⁻" } else { "" };
+            format!("{}{}ᴇ{}{}", &digits[..1], rest, exponent_sign, exponent.abs())
+        }
+    }
+
+    /// how the number is written as TI-84 Basic, with a leading `-` when it's negative,
+    /// which the code generator turns into the calculator's negation sign
+    pub fn ti_spelling(&self) -> String {
+        format!("{}{}", if self.negative { "-" } else { "" }, self.ti_magnitude())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spelling(text: &str) -> String {
+        Number::parse(text).unwrap_or_else(|(_, why)| panic!("{}", why)).ti_spelling()
+    }
+
+    fn rejected(text: &str) -> String {
+        match Number::parse(text) {
+            Ok(number) => panic!("`{}` should not parse, but gave {:?}", text, number),
+            Err((code, why)) => format!("{} {}", code.code(), why),
+        }
+    }
+
+    #[test]
+    fn fourteen_significant_digits_fit() {
+        assert_eq!(spelling("12345678901234"), "12345678901234");
+        assert_eq!(spelling("1.2345678901234"), "1.2345678901234");
+        // zeros on either end are not significant, though past 14 places the calculator needs an exponent
+        assert_eq!(spelling("000123456789012340"), "1.2345678901234ᴇ14");
+    }
+
+    #[test]
+    fn fifteen_significant_digits_do_not() {
+        assert!(rejected("123456789012345").starts_with("E0017 `123456789012345` has more than the 14 significant digits"));
+        assert!(rejected("-1.00000000000001").starts_with("E0017"));
+        assert!(rejected("0xFFFFFFFFFFFFF").starts_with("E0017"));
+    }
+
+    #[test]
+    fn exponents_out_of_range() {
+        assert!(rejected("1e999").starts_with("E0017 `1e999` is too large"));
+        assert!(rejected("1e100").starts_with("E0017 `1e100` is too large"));
+        assert!(rejected("1e-100").starts_with("E0017 `1e-100` is too small"));
+        assert_eq!(spelling("9.9999999999999e99"), "9.9999999999999ᴇ99");
+        assert_eq!(spelling("1e-99"), "1ᴇ⁻99");
+        // a long run of exponent digits is out of range, not an overflow
+        assert!(rejected("1e99999999999999999999").starts_with("E0017"));
+    }
+
+    #[test]
+    fn hex_too_large_to_hold() {
+        assert!(rejected("0x10000000000000000").starts_with("E0017 `0x10000000000000000` is too large"));
+        assert_eq!(spelling("0xFFFFFFFFFFF"), "17592186044415");
+    }
+
+    #[test]
+    fn scientific_spelling() {
+        assert_eq!(spelling("1e-30"), "1ᴇ⁻30");
+        assert_eq!(spelling("-1e50"), "-1ᴇ50");
+        assert_eq!(spelling("-2.5E+20"), "-2.5ᴇ20");
+        assert_eq!(spelling("1e-3"), ".001");
+        assert_eq!(spelling("1e-4"), ".0001");
+        assert_eq!(spelling("1e-5"), "1ᴇ⁻5");
+    }
+
+    #[test]
+    fn malformed_literals() {
+        for text in ["1e", "1.2.3", "0x", "0xG", "1e+-2", "."] {
+            assert!(rejected(text).starts_with("E0003"), "`{}`", text);
+        }
+    }
+}
@@ -1,51 +1,60 @@
-use util::Error;
-use tokenizer::Token;
-use manager::Manager;
+use util::{Error, ErrorCode, has_unique_elements};
+use tokenizer::{Token, TokenKind};
+use manager::{Manager, Variant};
 use type_checker::FutureType;
-use sexpr::{Sexpr, SexprId, SexprKind};
+use sexpr::{Sexpr, SexprId, SexprKind, MatchArm};
 use std::vec::Drain;
-use std::collections::VecDeque;
+use std::collections::{VecDeque, HashSet};
 use std::iter::Peekable;
 
 
-/// given a token's text, make its corresponding atom kind
-fn make_atom(text: &str) -> Option<SexprKind> {
-    Some(if &text[0..1] == "\"" {
-        SexprKind::StringLiteral
+/// given a token's kind, make its corresponding atom kind
+fn make_atom(kind: TokenKind) -> Option<SexprKind> {
+    Some(match kind {
+        TokenKind::String => SexprKind::StringLiteral,
+        TokenKind::Bool => SexprKind::BooleLiteral,
+        TokenKind::Int => SexprKind::IntegerLiteral,
+        TokenKind::Real => SexprKind::RealLiteral,
+        TokenKind::Ident => SexprKind::Identifier,
+        _ => return None,
+    })
+}
+
+/// a written type: a single token, or a function type like `(-> int int)`, which is given a token spanning all of it
+fn type_token(tokens: &mut Peekable<Drain<Token>>, missing: Error) -> Result<Token, Error> {
+    let first = tokens.next().ok_or(missing)?;
+    if first.kind != TokenKind::LParen {
+        return Ok(first);
     }
-        else if text == "true" || text == "false" {
-            SexprKind::BooleLiteral
+    let mut depth = 1;
+    let mut last = first;
+    while depth > 0 {
+        last = tokens.next().ok_or(Error::new(ErrorCode::UnclosedSexpr, "unclosed function type".to_string(), first))?;
+        match last.kind {
+            TokenKind::LParen => depth += 1,
+            TokenKind::RParen => depth -= 1,
+            _ => {}
         }
-            else if text.parse::<i32>().is_ok() {
-                SexprKind::IntegerLiteral
-            }
-                else if text.parse::<f32>().is_ok() {
-                    SexprKind::RealLiteral
-                }
-                    else if text.chars().nth(0).map_or(false, |ch| !ch.is_numeric()) && text.chars().all(|ch| ch == '-' || ch == '_' || ch == '<' || ch  == '>' || ch.is_alphanumeric()) {
-                        // first character must exist and be non numeric, rest of the characters can alphanumeric, dash, or underscore
-                        SexprKind::Identifier
-                    } else {
-                        return None;
-                    })
+    }
+    Ok(Token::new(first.begin(), last.begin() + last.len() - first.begin(), TokenKind::Ident))
 }
 
-fn parse_name_type_pairs_until(source: &str, head: Token, tokens: &mut Peekable<Drain<Token>>, closing_token_text: &str) -> Result<(Vec<String>, Vec<FutureType>), Error> {
+fn parse_name_type_pairs_until(source: &str, head: Token, tokens: &mut Peekable<Drain<Token>>, closing_kind: TokenKind) -> Result<(Vec<String>, Vec<FutureType>), Error> {
     let mut arguments = vec![];
     let mut signature = vec![];
     loop {
-        let first_token: Token = tokens.next().ok_or(Error::new("expected argument name, found end of file".to_owned(), head))?;
+        let first_token: Token = tokens.next().ok_or(Error::new(ErrorCode::MalformedDefinition, "expected argument name, found end of file".to_owned(), head))?;
 
-        if first_token.get_text(source) == closing_token_text {
+        if first_token.kind == closing_kind {
             break;
         }
 
-        let second_token: Token = tokens.next().ok_or(Error::new("expected type separator `:`, found end of file".to_owned(), head))?;
-        if second_token.get_text(source) != ":" {
-            return Err(Error::new(format!("expected type separator `:`, found `{}`", second_token.get_text(source)), second_token))
+        let second_token: Token = tokens.next().ok_or(Error::new(ErrorCode::MalformedDefinition, "expected type separator `:`, found end of file".to_owned(), head))?;
+        if second_token.kind != TokenKind::Colon {
+            return Err(Error::new(ErrorCode::MalformedDefinition, format!("expected type separator `:`, found `{}`", second_token.get_text(source)), second_token))
         }
 
-        let third_token = tokens.next().ok_or(Error::new("expected argument type, found end of file".to_owned(), first_token))?;
+        let third_token = type_token(tokens, Error::new(ErrorCode::MalformedDefinition, "expected argument type, found end of file".to_owned(), first_token))?;
 
         arguments.push(first_token.get_text(source).to_string());
         signature.push(FutureType::new(third_token));
@@ -54,6 +63,39 @@ fn parse_name_type_pairs_until(source: &str, head: Token, tokens: &mut Peekable<
     Ok((arguments, signature))
 }
 
+/// the argument names and types, whether a `->` follows them, and a bare name that is the whole body
+type ParsedArguments = (Vec<String>, Vec<FutureType>, bool, Option<Token>);
+
+/// a function's arguments: names, each with an optional `: type`, up to the `->` before the return type.
+/// without a `->`, the arguments end where the body starts and the return type is inferred,
+/// as are the types of arguments that don't have one.
+/// a bare name right before the closing `)` is then the body, which is given back on its own
+fn parse_arguments(source: &str, head: Token, tokens: &mut Peekable<Drain<Token>>) -> Result<ParsedArguments, Error> {
+    let mut arguments = vec![];
+    let mut signature = vec![];
+    loop {
+        let name_token = match tokens.peek() {
+            None => return Err(Error::new(ErrorCode::MalformedDefinition, "expected argument name, found end of file".to_owned(), head)),
+            Some(token) if token.kind == TokenKind::Arrow => {
+                tokens.next();
+                return Ok((arguments, signature, true, None));
+            }
+            Some(token) if token.kind == TokenKind::Ident => tokens.next().unwrap(),
+            Some(_) => return Ok((arguments, signature, false, None)),
+        };
+        let typ = match tokens.peek() {
+            Some(token) if token.kind == TokenKind::Colon => {
+                tokens.next();
+                FutureType::new(type_token(tokens, Error::new(ErrorCode::MalformedDefinition, "expected argument type, found end of file".to_owned(), name_token))?)
+            }
+            Some(token) if token.kind == TokenKind::RParen => return Ok((arguments, signature, false, Some(name_token))),
+            _ => FutureType::Infer(name_token),
+        };
+        arguments.push(name_token.get_text(source).to_string());
+        signature.push(typ);
+    }
+}
+
 impl Manager {
     fn push_new_sexpr(&mut self, kind: SexprKind, token: Token) -> SexprId {
         use std::cell::RefCell;
@@ -64,19 +106,17 @@ impl Manager {
     /// parses a s-expr from the tokens, which expects at least one of
     fn parse(&mut self, tokens: &mut Peekable<Drain<Token>>) -> Result<SexprId, Error> {
         let token = tokens.next().unwrap();
-        let text = &token.get_text(&self.source).to_owned();
         let (kind, token) =
-            if text == "(" {
+            if token.kind == TokenKind::LParen {
                 // we have a compound type
                 if tokens.peek().is_none() {
-                    return Err(Error::new(format!("unclosed s-expression"), token))
+                    return Err(Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), token))
                 }
                 let head = tokens.next().unwrap();
-                let head_str = head.get_text(&self.source).to_owned();
-                match &head_str[..] {
-                    "(" | ")" => {
-                        return Err(Error::new(format!("illegal head of s-expression"), head));
-                    }
+                if head.kind == TokenKind::LParen || head.kind == TokenKind::RParen {
+                    return Err(Error::new(ErrorCode::IllegalHead, "illegal head of s-expression".to_string(), head));
+                }
+                match head.get_text(&self.source) {
                     "func" => {
                         // handle the function definition parsing elsewhere
                         return self.parse_function_definition(head, tokens);
@@ -85,6 +125,22 @@ impl Manager {
                         // handle the struct definition parsing elsewhere
                         return self.parse_struct_definition(head,tokens);
                     }
+                    "declare" => {
+                        // the optional type annotation is not an s-expr, so declare is parsed elsewhere too
+                        return self.parse_declare(head, tokens);
+                    }
+                    "enum" => {
+                        // enums are declared like structs, a field list for each variant
+                        return self.parse_enum_definition(head, tokens);
+                    }
+                    "match" => {
+                        // neither are the patterns of a match
+                        return self.parse_match(head, tokens);
+                    }
+                    "lambda" => {
+                        // lambdas take their arguments the way function definitions do
+                        return self.parse_lambda(head, tokens);
+                    }
                     _ => {}
                 }
                 let mut tail = VecDeque::new();
@@ -92,7 +148,7 @@ impl Manager {
                 let compound_sexpr_token =
                     loop {
                         if let Some(peeked_token) = tokens.peek() {
-                            if peeked_token.get_text(&self.source) == ")" {
+                            if peeked_token.kind == TokenKind::RParen {
                                 // what a gentleman. the s-expr has been closed
                                 break (self.make_compound(head, tail)?, head);
                             } else {
@@ -100,18 +156,18 @@ impl Manager {
                             }
                         } else {
                             // the s-expr has been left un-closed
-                            return Err(Error::new(format!("unclosed s-expression"), token));
+                            return Err(Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), token));
                         }
                         tail.push_back(self.parse(tokens)?);
                     };
                 // eat the closing (
                 tokens.next();
                 compound_sexpr_token
-            } else if text == ")" {
-                return Err(Error::new(format!("un-paired closing parenthesis"), token));
+            } else if token.kind == TokenKind::RParen {
+                return Err(Error::new(ErrorCode::UnpairedParen, "un-paired closing parenthesis".to_string(), token));
             } else {
                 // we have an atom
-                let atom = make_atom(text).ok_or(Error::new(format!("not a recognized keyword, literal, or identifier"), token))?;
+                let atom = make_atom(token.kind).ok_or_else(|| Error::new(ErrorCode::UnrecognizedAtom, format!("`{}` can not be used as an expression", token.get_text(&self.source)), token))?;
                 (atom, token)
             };
         Ok(self.push_new_sexpr(kind, token))
@@ -122,20 +178,9 @@ impl Manager {
         let name = head.get_text(&self.source);
         Ok(match name {
             // we must simply settle the particulars
-            "declare"   => {
-                if tail.len() < 3 { //TODO type inference
-                    return Err(Error::new(format!("declare expected at least 3 arguments, not {}", tail.len()), head));
-                }
-                let variable_pattern = self.get_ident(tail.pop_front().unwrap())?;
-                let expr = tail.pop_front().unwrap();
-                let body = self.push_new_sexpr(
-                    SexprKind::Block{statements: tail}, head
-                );
-                SexprKind::Declare { variable_pattern, expr, body }
-            },
             "assign" => {
                 if tail.len() != 2 {
-                    return Err(Error::new(format!("assign expected exactly 2 arguments"), head));
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "assign expected exactly 2 arguments".to_string(), head));
                 }
                 let variable_pattern = self.get_ident(tail.pop_front().unwrap())?;
                 let expr = tail.pop_front().unwrap();
@@ -143,7 +188,7 @@ impl Manager {
             }
             "if" => {
                 if tail.len() != 3 {
-                    return Err(Error::new(format!("if expected exactly 3 arguments"), head));
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "if expected exactly 3 arguments".to_string(), head));
                 }
                 SexprKind::IfSwitch {
                     predicate: tail.pop_front().unwrap(),
@@ -152,8 +197,8 @@ impl Manager {
                 }
             }
             "while" => {
-                if tail.len() < 1 {
-                    return Err(Error::new(format!("while expected at least 1 argument"), head));
+                if tail.is_empty() {
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "while expected at least 1 argument".to_string(), head));
                 }
                 SexprKind::WhileLoop {
                     predicate: tail.pop_front().unwrap(),
@@ -164,7 +209,7 @@ impl Manager {
             }
             "get-field" => {
                 if tail.len() != 2 {
-                    return Err(Error::new(format!("get expected exactly 2 arguments"), head));
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "get expected exactly 2 arguments".to_string(), head));
                 }
                 let expr = tail.pop_front().unwrap();
                 let field = self.get_ident(tail.pop_front().unwrap())?;
@@ -172,13 +217,55 @@ impl Manager {
             }
             "set-field" => {
                 if tail.len() != 3 {
-                    return Err(Error::new(format!("set expected exactly 3 arguments"), head));
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "set expected exactly 3 arguments".to_string(), head));
                 }
                 let expr = tail.pop_front().unwrap();
                 let field = self.get_ident(tail.pop_front().unwrap())?;
                 let value = tail.pop_front().unwrap();
                 SexprKind::StructSet { id: None, expr, field, value }
             }
+            "list" => {
+                SexprKind::List { elements: tail }
+            }
+            "list-get" => {
+                if tail.len() != 2 {
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "list-get expected exactly 2 arguments".to_string(), head));
+                }
+                let list = tail.pop_front().unwrap();
+                let index = tail.pop_front().unwrap();
+                SexprKind::ListGet { list, index }
+            }
+            "list-set" => {
+                if tail.len() != 3 {
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "list-set expected exactly 3 arguments".to_string(), head));
+                }
+                let list = tail.pop_front().unwrap();
+                let index = tail.pop_front().unwrap();
+                let elem = tail.pop_front().unwrap();
+                SexprKind::ListSet { list, index, elem }
+            }
+            "list-len" => {
+                if tail.len() != 1 {
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "list-len expected exactly 1 argument".to_string(), head));
+                }
+                SexprKind::ListLen { list: tail.pop_front().unwrap() }
+            }
+            "list-push" => {
+                if tail.len() != 2 {
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "list-push expected exactly 2 arguments".to_string(), head));
+                }
+                let list = tail.pop_front().unwrap();
+                let elem = tail.pop_front().unwrap();
+                SexprKind::ListPush { list, elem }
+            }
+            "list-map" => {
+                if tail.len() != 2 {
+                    return Err(Error::new(ErrorCode::WrongArgumentCount, "list-map expected exactly 2 arguments".to_string(), head));
+                }
+                let func = tail.pop_front().unwrap();
+                let list = tail.pop_front().unwrap();
+                SexprKind::ListMap { func, target: None, list }
+            }
             "format" => {
                 SexprKind::Format { exprs: tail }
             }
@@ -195,18 +282,125 @@ impl Manager {
         })
     }
 
+    /// `(declare name initial-value body...)` or `(declare name: type initial-value body...)`
+    fn parse_declare(&mut self, head: Token, tokens: &mut Peekable<Drain<Token>>) -> Result<SexprId, Error> {
+        let name_token = tokens.next().ok_or(Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), head))?;
+        if name_token.kind != TokenKind::Ident {
+            return Err(Error::new(ErrorCode::NotAnIdentifier, "declare expected a variable name".to_string(), name_token));
+        }
+        let variable_pattern = name_token.get_text(&self.source).to_string();
+        let annotation = if tokens.peek().is_some_and(|token| token.kind == TokenKind::Colon) {
+            tokens.next();
+            Some(FutureType::new(type_token(tokens, Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), head))?))
+        } else {
+            None
+        };
+        let mut tail = VecDeque::new();
+        loop {
+            match tokens.peek() {
+                Some(token) if token.kind == TokenKind::RParen => break,
+                Some(_) => tail.push_back(self.parse(tokens)?),
+                None => return Err(Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), head)),
+            }
+        }
+        tokens.next(); // eat up the closing )
+        let expr = tail.pop_front().ok_or(Error::new(ErrorCode::WrongArgumentCount, format!("declare expected an initial value for `{}`", variable_pattern), head))?;
+        let body = self.push_new_sexpr(
+            SexprKind::Block{statements: tail}, head
+        );
+        Ok(self.push_new_sexpr(
+            SexprKind::Declare { variable_pattern, annotation, expr, body },
+            head
+        ))
+    }
+
     fn parse_struct_definition(&mut self, head: Token, tokens: &mut Peekable<Drain<Token>>) -> Result<SexprId, Error> {
-        let name_token = tokens.next().ok_or(Error::new("unexpected end of file while scanning struct definition: missing a name".to_string(), head))?;
+        let name_token = tokens.next().ok_or(Error::new(ErrorCode::MalformedDefinition, "unexpected end of file while scanning struct definition: missing a name".to_string(), head))?;
         let name = String::from(name_token.get_text(&self.source));
-        match make_atom(&name) {
-            Some(SexprKind::Identifier) => {},
-            _ => {
-                return Err(Error::new("invalid struct name: must be a proper identifier".to_string(), name_token));
+        if name_token.kind != TokenKind::Ident {
+            return Err(Error::new(ErrorCode::MalformedDefinition, "invalid struct name: must be a proper identifier".to_string(), name_token));
+        }
+        let (arguments, signature) = parse_name_type_pairs_until(&self.source, head, tokens, TokenKind::RParen)?;
+        let id = self.udt_manager.declare_type(head, name, arguments, signature)?;
+        Ok(self.push_new_sexpr(
+            SexprKind::StructDef{id},
+            head
+        ))
+    }
+
+    /// `(enum Name (variant field: type ...) ...)`
+    fn parse_enum_definition(&mut self, head: Token, tokens: &mut Peekable<Drain<Token>>) -> Result<SexprId, Error> {
+        let name_token = tokens.next().ok_or(Error::new(ErrorCode::MalformedDefinition, "unexpected end of file while scanning enum definition: missing a name".to_string(), head))?;
+        let name = String::from(name_token.get_text(&self.source));
+        if name_token.kind != TokenKind::Ident {
+            return Err(Error::new(ErrorCode::MalformedDefinition, "invalid enum name: must be a proper identifier".to_string(), name_token));
+        }
+        let mut variants = vec![];
+        loop {
+            let open = tokens.next().ok_or(Error::new(ErrorCode::MalformedDefinition, "unexpected end of file while scanning enum definition".to_string(), head))?;
+            match open.kind {
+                TokenKind::RParen => break,
+                TokenKind::LParen => {}
+                _ => return Err(Error::new(ErrorCode::MalformedDefinition, format!("expected a variant like `(name field: type ...)`, found `{}`", open.get_text(&self.source)), open)),
+            }
+            let variant_token = tokens.next().ok_or(Error::new(ErrorCode::MalformedDefinition, "unexpected end of file while scanning enum definition: missing a variant name".to_string(), open))?;
+            if variant_token.kind != TokenKind::Ident {
+                return Err(Error::new(ErrorCode::MalformedDefinition, "invalid variant name: must be a proper identifier".to_string(), variant_token));
+            }
+            let (args, sgntr) = parse_name_type_pairs_until(&self.source, variant_token, tokens, TokenKind::RParen)?;
+            variants.push(Variant { name: variant_token.get_text(&self.source).to_string(), args, sgntr });
+        }
+        if variants.is_empty() {
+            return Err(Error::new(ErrorCode::MalformedDefinition, format!("enum `{}` needs at least one variant", name), name_token));
+        }
+        let id = self.udt_manager.declare_enum(name_token, name, variants)?;
+        Ok(self.push_new_sexpr(
+            SexprKind::EnumDef { id },
+            head
+        ))
+    }
+
+    /// `(match expr (variant name ...) body ... _ body)`
+    fn parse_match(&mut self, head: Token, tokens: &mut Peekable<Drain<Token>>) -> Result<SexprId, Error> {
+        if tokens.peek().is_none_or(|token| token.kind == TokenKind::RParen) {
+            return Err(Error::new(ErrorCode::WrongArgumentCount, "match expected something to match on".to_string(), head));
+        }
+        let expr = self.parse(tokens)?;
+        let mut arms = vec![];
+        loop {
+            let open = tokens.next().ok_or(Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), head))?;
+            let (pattern, variant, bindings) = match open.kind {
+                TokenKind::RParen => break,
+                TokenKind::Ident if open.get_text(&self.source) == "_" => (open, None, vec![]),
+                TokenKind::LParen => {
+                    let variant_token = tokens.next().ok_or(Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), open))?;
+                    if variant_token.kind != TokenKind::Ident {
+                        return Err(Error::new(ErrorCode::NotAnIdentifier, "a pattern starts with the name of a variant".to_string(), variant_token));
+                    }
+                    let mut bindings = vec![];
+                    loop {
+                        let token = tokens.next().ok_or(Error::new(ErrorCode::UnclosedSexpr, "unclosed s-expression".to_string(), open))?;
+                        match token.kind {
+                            TokenKind::RParen => break,
+                            TokenKind::Ident => bindings.push(token.get_text(&self.source).to_string()),
+                            _ => return Err(Error::new(ErrorCode::NotAnIdentifier, "a pattern names the variant's fields with identifiers".to_string(), token)),
+                        }
+                    }
+                    if !has_unique_elements(bindings.iter()) {
+                        return Err(Error::new(ErrorCode::DuplicateName, "pattern gives the same name to more than one field".to_string(), variant_token));
+                    }
+                    (variant_token, Some(variant_token.get_text(&self.source).to_string()), bindings)
+                }
+                _ => return Err(Error::new(ErrorCode::MalformedDefinition, format!("expected a pattern like `(variant name ...)` or `_`, found `{}`", open.get_text(&self.source)), open)),
+            };
+            if tokens.peek().is_none_or(|token| token.kind == TokenKind::RParen) {
+                return Err(Error::new(ErrorCode::WrongArgumentCount, "match arm has no body".to_string(), pattern));
             }
+            let body = self.parse(tokens)?;
+            arms.push(MatchArm { pattern, variant, bindings, body });
         }
-        let (arguments, signature) = parse_name_type_pairs_until(&self.source, head, tokens, ")")?;
         Ok(self.push_new_sexpr(
-            SexprKind::StructDef{id: self.udt_manager.declare_type(head, name, arguments, signature)?},
+            SexprKind::Match { id: None, expr, arms },
             head
         ))
     }
@@ -219,25 +413,57 @@ impl Manager {
         //  1 requires functions to be treated differently: this might be quite hard to do with how close parens are handled
         //  1 looks much nicer and is clearer
         //  2 requires that we let the head be anything at all and then kill if it's not a function
-        let name_token = tokens.next().ok_or(Error::new("unexpected end of file while scanning function definition: missing a name".to_string(), head))?;
+        let name_token = tokens.next().ok_or(Error::new(ErrorCode::MalformedDefinition, "unexpected end of file while scanning function definition: missing a name".to_string(), head))?;
         let name = name_token.get_text(&self.source).to_string();
-        if let Some(SexprKind::Identifier) = make_atom(&name) { }
-            else {
-                return Err(Error::new("invalid function name: must be a proper identifier".to_string(), name_token));
+        if name_token.kind != TokenKind::Ident {
+            return Err(Error::new(ErrorCode::MalformedDefinition, "invalid function name: must be a proper identifier".to_string(), name_token));
+        }
+        // a generic function lists its type parameters after its name, like `len<T>` or `zip<A,B>`
+        let (name, type_params) = match name.find('<') {
+            Some(open) if name.ends_with('>') => {
+                let type_params = name[open + 1..name.len() - 1].split(',').map(str::to_string).collect::<Vec<String>>();
+                if type_params.iter().any(|param| param.is_empty() || !param.chars().all(|ch| ch == '_' || ch.is_alphanumeric())) {
+                    return Err(Error::new(ErrorCode::MalformedDefinition, "invalid type parameters: must be identifiers separated by commas".to_string(), name_token));
+                }
+                (name[..open].to_string(), type_params)
             }
+            _ => (name, vec![]),
+        };
 
-        let (arguments, signature) = parse_name_type_pairs_until(&self.source, head, tokens, "->")?;
-        let out_token = tokens.next().ok_or(Error::new(format!("unexpected end of file while scanning function definition (expected out type after -> )"), head))?;
-        let out_type = FutureType::new(out_token);
+        let (arguments, signature, out_type, body) = self.parse_signature_and_body(head, name_token, tokens)?;
+        let func_id = self.func_manager.declare_func(name, type_params.clone(), arguments, signature, out_type, body)?;
+        // each instance gets a copy of the body, but the functions inside of it would all share the one definition
+        if !type_params.is_empty() && self.holds_function(body) {
+            return Err(Error::new(ErrorCode::MalformedDefinition, "generic functions can not hold lambdas or other functions".to_string(), name_token)
+                .with_note("define the function outside, and pass it in or give it what it needs as arguments".to_string()));
+        }
+        Ok(self.push_new_sexpr(
+            SexprKind::FuncDef { func_id },
+            head
+        ))
+    }
+
+    /// the arguments, return type and body that function definitions and lambdas both have.
+    /// `name_token` is what a left out return type is the type of
+    fn parse_signature_and_body(&mut self, head: Token, name_token: Token, tokens: &mut Peekable<Drain<Token>>) -> Result<(Vec<String>, Vec<FutureType>, FutureType, SexprId), Error> {
+        let (arguments, signature, has_arrow, bare_body) = parse_arguments(&self.source, head, tokens)?;
+        let out_type = if has_arrow {
+            FutureType::new(type_token(tokens, Error::new(ErrorCode::MalformedDefinition, "unexpected end of file while scanning function definition (expected out type after -> )".to_string(), head))?)
+        } else {
+            FutureType::Infer(name_token)
+        };
         let mut statements = VecDeque::new();
+        if let Some(token) = bare_body {
+            statements.push_back(self.push_new_sexpr(make_atom(token.kind).expect("names are atoms"), token));
+        }
         loop {
             {
                 let token = tokens
                     .peek()
-                    .ok_or(Error::new(
+                    .ok_or(Error::new(ErrorCode::MalformedDefinition,
                         "unexpected end of file while scanning function definition expected closing parenthesis".to_owned(),
                         head))?;
-                if token.get_text(&self.source) == ")" {
+                if token.kind == TokenKind::RParen {
                     break;
                 }
             }
@@ -247,21 +473,65 @@ impl Manager {
         let body = self.push_new_sexpr(
             SexprKind::Block{ statements }, head
         );
+        Ok((arguments, signature, out_type, body))
+    }
+
+    /// `(lambda arg: type ... -> type body...)`, a function without a name that is made where it is written
+    fn parse_lambda(&mut self, head: Token, tokens: &mut Peekable<Drain<Token>>) -> Result<SexprId, Error> {
+        let (arguments, signature, out_type, body) = self.parse_signature_and_body(head, head, tokens)?;
+        if matches!(*self.kind(body), SexprKind::Block { ref statements } if statements.is_empty()) {
+            return Err(Error::new(ErrorCode::MalformedDefinition, "lambda has no body".to_string(), head));
+        }
+        let func_id = self.func_manager.declare_func("lambda".to_string(), vec![], arguments, signature, out_type, body)?;
         Ok(self.push_new_sexpr(
-            SexprKind::FuncDef { func_id: self.func_manager.declare_func(name, arguments, signature, out_type, body)? },
+            SexprKind::Lambda { func_id },
             head
         ))
     }
+
+    /// whether there is a lambda or a function definition anywhere in this s-expr
+    fn holds_function(&self, sexpr_id: SexprId) -> bool {
+        matches!(*self.kind(sexpr_id), SexprKind::Lambda { .. } | SexprKind::FuncDef { .. }) || self.children(sexpr_id).into_iter().any(|child| self.holds_function(child))
+    }
 }
 
-pub fn generate_global_sexprs(m: &mut Manager, mut tokens: Vec<Token>) -> Result<(), Error> {
-    //TODO get this to return multiple errors
-    //    println!("running method generate_global_sexprs length: {}", tokens.len());
+/// where each top level s-expr starts, by counting parentheses
+/// a stray closing parenthesis doesn't take us below the top level
+fn top_level_starts(tokens: &[Token]) -> HashSet<usize> {
+    let mut starts = HashSet::new();
+    let mut depth = 0usize;
+    for token in tokens {
+        match token.kind {
+            TokenKind::LParen => {
+                if depth == 0 {
+                    starts.insert(token.begin());
+                }
+                depth += 1;
+            }
+            TokenKind::RParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    starts
+}
+
+pub fn generate_global_sexprs(m: &mut Manager, mut tokens: Vec<Token>) -> Result<(), Vec<Error>> {
+    let top_level_starts = top_level_starts(&tokens);
     let mut top_level_sexprs = vec![];
+    let mut errors = vec![];
     let mut iterator = tokens.drain(..).peekable();
     while iterator.peek().is_some() {
-        top_level_sexprs.push(m.parse(&mut iterator)? );
+        match m.parse(&mut iterator) {
+            Ok(sexpr_id) => top_level_sexprs.push(sexpr_id),
+            Err(error) => {
+                errors.push(error);
+                // pick back up at the next top level s-expr
+                while iterator.peek().is_some_and(|token| !top_level_starts.contains(&token.begin())) {
+                    iterator.next();
+                }
+            }
+        }
     }
     m.top_level_sexprs = top_level_sexprs;
-    Ok(())
-}
\ No newline at end of file
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
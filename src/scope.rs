@@ -18,6 +18,8 @@ pub struct Scope {
     pub is_func_def: bool,
     // store the  id of every struct in scope
     pub declared_structs: Vec<usize>,
+    // store the id of every enum in scope, whose variants can be made here
+    pub declared_enums: Vec<usize>,
     // None iff this is the global scope
     pub parent: Option<ScopeId>,
     // empty for global scopes
@@ -32,13 +34,14 @@ impl Scope {
             declared_functions: vec![],
             is_func_def,
             declared_structs: vec![],
+            declared_enums: vec![],
             parent,
             children: vec![],
         }
     }
     pub fn new_to_vec(all_scopes: &mut Vec<Scope>, parent: Option<ScopeId>, is_func_def: bool) -> ScopeId {
         all_scopes.push(Scope::new(parent, is_func_def));
-        ((all_scopes.len() - 1) as usize).into()
+        (all_scopes.len() - 1).into()
     }
 }
 
@@ -1,25 +1,145 @@
 use sexpr::*;
 use manager::Manager;
-use util::Error;
+use util::{Error, ErrorCode, Stage};
 use scope::*;
+use type_checker::FutureType;
 
 impl Manager {
+    /// create the scope of a function's body, where its arguments are bound.
+    /// it can see what is around where it is defined, which is how it can call itself and use outer variables
+    pub fn scope_func(&mut self, func_id: usize, parent: ScopeId) -> Result<(), Error> {
+        let new = Scope::new_to_vec(&mut self.all_scopes, Some(parent), true);
+        self.scope_mut(parent).children.push(new);
+        self.bind_arguments(new, func_id);
+        self.create_scope(self.func_manager.body[func_id], new)
+    }
+    fn bind_arguments(&mut self, scope_id: ScopeId, func_id: usize) {
+        for i in 0..(self.func_manager.args[func_id].len()) {
+            // bind all arguments. the ones without a type get it when signatures are inferred
+            let arg_type = self.func_manager.in_types[func_id][i].known().cloned();
+            self.bind_variable(scope_id,
+                              self.func_manager.args[func_id][i].clone(),
+                              arg_type.as_ref()
+            );
+        }
+    }
+    /// the variables that a function's body uses from the scopes around the function, which it gets a copy of.
+    /// that includes what the functions it calls use from around them.
+    /// since they are copies, the function can't assign to them
+    fn find_captures(&self, sexpr_id: SexprId, func_id: usize, func_scope: ScopeId, captures: &mut Vec<(usize, usize)>) -> Result<(), Error> {
+        // a variable is captured when it is bound in a scope that the function is inside of
+        let around = |scope_id: ScopeId| {
+            let mut outer = self.scope(func_scope).parent;
+            while let Some(outer_id) = outer {
+                if outer_id.index == scope_id.index {
+                    return true;
+                }
+                outer = self.scope(outer_id).parent;
+            }
+            false
+        };
+        let token = self.sexpr(sexpr_id).token;
+        let mut capture = |key: (usize, usize)| {
+            if captures.contains(&key) {
+                return Ok(());
+            }
+            if self.func_manager.is_generic(func_id) {
+                return Err(Error::new(ErrorCode::MalformedDefinition, format!("generic function `{}` can not use variables from around it yet", self.func_manager.name[func_id]), token));
+            }
+            captures.push(key);
+            Ok(())
+        };
+        let name = match *self.kind(sexpr_id) {
+            SexprKind::Assign { ref variable_pattern, .. } => {
+                if self.resolve_variable(self.scope_of(sexpr_id), variable_pattern).is_some_and(|(scope_id, _)| around(scope_id)) {
+                    let who = match &self.func_manager.name[func_id][..] {
+                        "lambda" => "a lambda".to_string(),
+                        name => format!("`{}`", name),
+                    };
+                    return Err(Error::new(ErrorCode::AssignToCapture, format!("{} can not assign to `{}`, which it only has a copy of", who, variable_pattern), token));
+                }
+                None
+            }
+            // the head of an unresolved call could be a variable holding a function
+            SexprKind::Identifier | SexprKind::Other { .. } => Some(self.text(sexpr_id)),
+            // a nested function's body runs in a frame of its own, so what it captures is picked up where it is used
+            SexprKind::FuncDef { .. } => return Ok(()),
+            _ => None,
+        };
+        if let Some(name) = name {
+            match self.resolve_variable(self.scope_of(sexpr_id), name) {
+                Some((scope_id, var_id)) => if around(scope_id) {
+                    capture((scope_id.index, var_id))?;
+                },
+                // calling a function, or making a value of it, needs whatever it captures
+                None => for callee in self.funcs_named(self.scope_of(sexpr_id), name) {
+                    for &key in self.func_manager.captures[callee].iter() {
+                        if around(ScopeId::from(key.0)) {
+                            capture(key)?;
+                        }
+                    }
+                },
+            }
+        }
+        for child in self.children(sexpr_id) {
+            self.find_captures(child, func_id, func_scope, captures)?;
+        }
+        Ok(())
+    }
+    /// settle what every function captures. a function picks up what the functions it uses capture,
+    /// so this goes until nothing new is found
+    fn find_all_captures(&mut self) -> Result<(), Error> {
+        loop {
+            let mut changed = false;
+            for func_id in 0..self.func_manager.count {
+                let body = self.func_manager.body[func_id];
+                let func_scope = self.scope_of(body);
+                if !self.scope(func_scope).is_func_def {
+                    // its definition was never scoped
+                    continue;
+                }
+                let mut captures = self.func_manager.captures[func_id].clone();
+                self.find_captures(body, func_id, func_scope, &mut captures)?;
+                if captures.len() != self.func_manager.captures[func_id].len() {
+                    self.func_manager.captures[func_id] = captures;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
     ///creates the scope for this sexpr and all its children, returning ownership on a success.
     fn create_scope(&mut self, sexpr_id: SexprId, parent: ScopeId) -> Result<(), Error> {
-        assert_eq!(self.sexpr_scopes.len(), sexpr_id.index);
-        self.sexpr_scopes.push(parent);
-        use std::ops::Deref;
-        match *self.sexpr_mut(sexpr_id).kind.deref() {
-            SexprKind::Declare {ref variable_pattern, expr, body} => {
+        self.sexpr_scopes[sexpr_id.index] = parent;
+        // clone the kind so that we are free to mutate the manager while walking the children
+        let kind = self.kind(sexpr_id).clone();
+        match kind {
+            SexprKind::Declare {ref variable_pattern, ref annotation, expr, body} => {
+                if let Some((shadowed, _)) = self.resolve_variable(self.scope_of(sexpr_id), variable_pattern) {
+                    let token = self.sexpr(sexpr_id).token;
+                    self.warnings.push(Error::warning(ErrorCode::ShadowedVariable, format!("declaring `{}` shadows a variable of the same name", variable_pattern), token).in_stage(Stage::Scoping));
+                    // point at the declaration being shadowed, unless it is something like an argument
+                    let earlier = (0..self.all_sexprs.len()).map(SexprId::from).find(|&other| match *self.kind(other) {
+                        SexprKind::Declare { variable_pattern: ref name, body: other_body, .. } => name == variable_pattern && self.scope_of(other_body).index == shadowed.index,
+                        _ => false,
+                    });
+                    if let Some(earlier) = earlier {
+                        let token = self.sexpr(earlier).token;
+                        self.warnings.push(Error::note(ErrorCode::ShadowedVariable, format!("`{}` was declared here", variable_pattern), token).in_stage(Stage::Scoping));
+                    }
+                }
                 // create a child scope with the variable_name : declare_type binding
+                // without an annotation, the type is inferred from the initial value when type checking
                 let child = self.create_child(self.scope_of(sexpr_id));
-                self.bind_variable(child, variable_pattern.to_string(), None);
+                self.bind_variable(child, variable_pattern.to_string(), annotation.as_ref().map(FutureType::unwrap));
                 self.create_scope(expr, parent)?;
                 self.create_scope(body, child)?;
             },
             SexprKind::Assign {ref variable_pattern, expr} => {
                 if self.resolve_variable(self.scope_of(sexpr_id), variable_pattern).is_none(){
-                    return Err(Error::new(format!("assigning to undeclared variable `{}`", variable_pattern), self.sexpr(sexpr_id).token));
+                    return Err(Error::new(ErrorCode::UndeclaredVariable, format!("assigning to undeclared variable `{}`", variable_pattern), self.sexpr(sexpr_id).token));
                 }
                 self.create_scope(expr, parent)?;
             }
@@ -33,8 +153,8 @@ impl Manager {
                 self.create_scope(body, parent)?;
             }
             SexprKind::Block {ref statements} => {
-                for ref statement in statements {
-                    self.create_scope(**statement, parent)?;
+                for statement in statements {
+                    self.create_scope(*statement, parent)?;
                 }
             }
             SexprKind::StructGet { id:_, expr, field:_ } => {
@@ -44,38 +164,77 @@ impl Manager {
                 self.create_scope(expr, parent)?;
                 self.create_scope(value, parent)?;
             }
+            SexprKind::List { ref elements } => {
+                for element in elements {
+                    self.create_scope(*element, parent)?;
+                }
+            }
+            SexprKind::ListGet { list, index } => {
+                self.create_scope(list, parent)?;
+                self.create_scope(index, parent)?;
+            }
+            SexprKind::ListSet { list, index, elem } => {
+                self.create_scope(list, parent)?;
+                self.create_scope(index, parent)?;
+                self.create_scope(elem, parent)?;
+            }
+            SexprKind::ListLen { list } => {
+                self.create_scope(list, parent)?;
+            }
+            SexprKind::ListMap { func, list, .. } => {
+                // a name that isn't a variable could be a struct or a builtin, which are settled in type checking
+                if matches!(*self.kind(func), SexprKind::Identifier) && self.resolve_variable(parent, self.text(func)).is_none() {
+                    self.sexpr_scopes[func.index] = parent;
+                } else {
+                    self.create_scope(func, parent)?;
+                }
+                self.create_scope(list, parent)?;
+            }
+            SexprKind::ListPush { list, elem } => {
+                self.create_scope(list, parent)?;
+                self.create_scope(elem, parent)?;
+            }
             SexprKind::FuncDef { func_id } => {
                 self.bind_func(self.scope_of(sexpr_id), func_id);
-                // create our own parallel scoping business
-                let new = Scope::new_to_vec(&mut self.all_scopes, None, true);
-                self.bind_func(new, func_id); // it is visible inside its own scope for recursion
-                for i in 0..(self.func_manager.args[func_id].len()) {
-                    // bind all arguments
-                    self.bind_variable(new,
-                                      self.func_manager.args[func_id][i].clone(),
-                                      Some(&self.func_manager.in_types[func_id][i].unwrap()) // unwrap is for the FutureType nonsense
-                    );
-                }
-                self.create_scope(self.func_manager.body[func_id], new);
+                self.scope_func(func_id, parent)?;
             },
+            SexprKind::Lambda { func_id } => {
+                self.scope_func(func_id, parent)?;
+            }
             SexprKind::StructDef { id } => {
                 self.bind_struct_init(self.scope_of(sexpr_id), id);
             }
+            SexprKind::EnumDef { id } => {
+                self.bind_enum(self.scope_of(sexpr_id), id);
+            }
+            SexprKind::Match { expr, ref arms, .. } => {
+                self.create_scope(expr, parent)?;
+                for arm in arms {
+                    // each arm gets a child scope with its bindings, whose types are known once the matched enum is
+                    let child = self.create_child(parent);
+                    for binding in arm.bindings.iter() {
+                        self.bind_variable(child, binding.to_string(), None);
+                    }
+                    self.create_scope(arm.body, child)?;
+                }
+            }
             SexprKind::Format { ref exprs } => {
-                for ref mut expr in exprs {
-                    self.create_scope(**expr, parent)?;
+                for expr in exprs {
+                    self.create_scope(*expr, parent)?;
                 }
             }
             SexprKind::Other { ref opt_exprs } => {
-                for ref mut expr in opt_exprs.unwrap() {
+                for expr in opt_exprs.as_ref().expect("other kind opt_exprs should be full while scoping") {
                     self.create_scope(*expr, parent)?;
                 }
             }
             // we don't know these exist yet
-            SexprKind::FuncCall {..} | SexprKind::StructInit {..} | SexprKind::BuiltIn {..} => panic!("scoping a expression that we shouldnt know about yet"),
+            SexprKind::FuncCall {..} | SexprKind::FuncRef {..} | SexprKind::ValueCall {..} | SexprKind::StructInit {..} | SexprKind::EnumInit {..} | SexprKind::BuiltIn {..} => panic!("scoping a expression that we shouldnt know about yet"),
             SexprKind::Identifier => {
-                if self.resolve_variable(self.scope_of(sexpr_id), self.text(sexpr_id)).is_none() {
-                    return Err(Error::new(format!("variable is undeclared"), self.sexpr(sexpr_id).token));
+                // the name of a function is a value too
+                let scope_id = self.scope_of(sexpr_id);
+                if self.resolve_variable(scope_id, self.text(sexpr_id)).is_none() && self.funcs_named(scope_id, self.text(sexpr_id)).is_empty() {
+                    return Err(Error::new(ErrorCode::UndeclaredVariable, "variable is undeclared".to_string(), self.sexpr(sexpr_id).token));
                 }
             }
             SexprKind::RealLiteral | SexprKind::IntegerLiteral | SexprKind::StringLiteral | SexprKind::BooleLiteral => {},
@@ -84,10 +243,21 @@ impl Manager {
     }
 }
 
-pub fn create_all_scopes(m: &mut Manager) -> Result<(), Error> {
+pub fn create_all_scopes(m: &mut Manager) -> Result<(), Vec<Error>> {
     let global = Scope::new_to_vec(&mut m.all_scopes, None, false);
-    for sexpr_id in m.top_level_sexprs {
-        m.create_scope(sexpr_id, global)?;
+    m.sexpr_scopes = vec![global; m.all_sexprs.len()];
+    let mut errors = vec![];
+    let mut scoped = vec![];
+    for sexpr_id in m.top_level_sexprs.clone() {
+        match m.create_scope(sexpr_id, global) {
+            Ok(()) => scoped.push(sexpr_id),
+            Err(error) => errors.push(error),
+        }
     }
-    Ok(())
-}
\ No newline at end of file
+    // only the s-exprs that scoped properly go on to type checking, so it can still check the rest of them
+    m.top_level_sexprs = scoped;
+    if let Err(error) = m.find_all_captures() {
+        errors.push(error);
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
@@ -1,4 +1,5 @@
 use tokenizer::Token;
+use type_checker::FutureType;
 use std::collections::VecDeque;
 
 #[derive(Debug, Copy, Clone)]
@@ -9,9 +10,9 @@ impl std::convert::From<usize> for SexprId {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SexprKind {
-    Declare{variable_pattern: String, expr: SexprId, body: SexprId},
+    Declare{variable_pattern: String, annotation: Option<FutureType>, expr: SexprId, body: SexprId},
     Assign{variable_pattern: String, expr: SexprId},
     IfSwitch{predicate: SexprId, if_branch: SexprId, else_branch: SexprId},
     WhileLoop{predicate: SexprId, body: SexprId},
@@ -19,12 +20,32 @@ pub enum SexprKind {
 
     FuncDef{func_id: usize},
     FuncCall{func_id: usize, call_id: usize, exprs: VecDeque<SexprId>},
+    /// a function without a name, made into a value where it is written
+    Lambda{func_id: usize},
+    /// a named function used as a value, which its name becomes in type checking
+    FuncRef{func_id: usize},
+    /// a call through the function value held by the variable that the head names
+    ValueCall{call_id: usize, exprs: VecDeque<SexprId>},
 
     StructDef {id: usize},
     StructInit{id: usize, exprs: VecDeque<SexprId>},
     StructGet{id: Option<usize>, expr: SexprId, field: String, },
     StructSet{id: Option<usize>, expr: SexprId, field: String, value: SexprId },
 
+    EnumDef{id: usize},
+    EnumInit{id: usize, variant: usize, exprs: VecDeque<SexprId>},
+    /// `id` is the enum being matched on, which is settled in type checking
+    Match{id: Option<usize>, expr: SexprId, arms: Vec<MatchArm>},
+
+    List{elements: VecDeque<SexprId>},
+    ListGet{list: SexprId, index: SexprId},
+    ListSet{list: SexprId, index: SexprId, elem: SexprId},
+    ListLen{list: SexprId},
+    ListPush{list: SexprId, elem: SexprId},
+    /// `func` is what is called on each element: the name of a function, struct or builtin, or a function value.
+    /// which of them it is gets settled in type checking
+    ListMap{func: SexprId, target: Option<MapTarget>, list: SexprId},
+
     Format{exprs: VecDeque<SexprId>},
     BuiltIn{id: usize, exprs: VecDeque<SexprId>},
 
@@ -37,6 +58,26 @@ pub enum SexprKind {
     Identifier,
 }
 
+/// what `list-map` calls on each element
+#[derive(Debug, Copy, Clone)]
+pub enum MapTarget {
+    Func{func_id: usize, call_id: usize},
+    StructInit{id: usize},
+    BuiltIn{id: usize},
+    /// a function value, called through the dispatch table
+    Value{call_id: usize},
+}
+
+/// one arm of a match: the variant it takes, the names it gives that variant's fields, and what it does
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Token,
+    /// None for `_`, which takes every variant that the arms before it don't
+    pub variant: Option<String>,
+    pub bindings: Vec<String>,
+    pub body: SexprId,
+}
+
 #[derive(Debug)]
 pub struct Sexpr {
     pub kind: Box<SexprKind>,
@@ -0,0 +1,1097 @@
+use ti_tokens::split_basic;
+use util::Error;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// the most statements we will run before deciding the program never stops
+const STEP_LIMIT: usize = 10_000_000;
+/// the calculator won't let a list grow past this many elements
+const MAX_LIST_LEN: usize = 999;
+/// the calculator keeps 14 significant digits, and shows 10 of them
+const INTERNAL_DIGITS: usize = 14;
+const DISPLAY_DIGITS: usize = 10;
+
+/// the functions we know how to run, as they are spelled in the token table
+const FUNCTIONS: &[&str] = &[
+    "dim(", "augment(", "sub(", "length(", "inString(", "iPart(", "fPart(", "int(", "abs(", "round(",
+    "not(", "remainder(", "randInt(", "ln(", "ℯ^(", "log(", "₁₀^(", "√(", "sin(", "cos(", "tan(",
+    "sin⁻¹(", "cos⁻¹(", "tan⁻¹(", "max(", "min(", "real(", "imag(", "sum(", "gcd(", "lcm(",
+];
+
+type Sim<T> = Result<T, String>;
+
+/// round a number to the precision the calculator actually stores
+pub fn round_to_precision(x: f64) -> f64 {
+    if x == 0.0 || !x.is_finite() {
+        return x;
+    }
+    format!("{:.*e}", INTERNAL_DIGITS - 1, x).parse().unwrap()
+}
+
+/// round the result of an addition or `fPart(` to the decimal places its operands were stored with,
+/// which the calculator's decimal arithmetic gets exactly right and binary floats don't
+pub fn round_like(x: f64, magnitude: f64) -> f64 {
+    if magnitude == 0.0 || !x.is_finite() {
+        return round_to_precision(x);
+    }
+    let places = INTERNAL_DIGITS as i32 - 1 - magnitude.abs().log10().floor() as i32;
+    if places <= 0 {
+        return round_to_precision(x);
+    }
+    round_to_precision(format!("{:.*}", places as usize, x).parse().unwrap())
+}
+
+/// write a real number the way the home screen shows it in Normal Float mode:
+/// up to 10 significant digits, no leading zero, ⁻ for negatives and ᴇ for very large or small numbers
+pub fn format_number(x: f64) -> String {
+    if x == 0.0 {
+        return "0".to_string();
+    }
+    let sign = if x < 0.0 { "⁻" } else { "" };
+    // round first, since rounding can carry into the next power of ten
+    let scientific = format!("{:.*e}", DISPLAY_DIGITS - 1, x.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let exponent = exponent[1..].parse::<i32>().unwrap();
+    let digits = mantissa.replace('.', "");
+    let digits = digits.trim_end_matches('0');
+
+    if exponent >= DISPLAY_DIGITS as i32 || exponent < -3 {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let exponent_sign = if exponent < 0 { "⁻" } else { "" };
+        format!("{}{}{}{}ᴇ{}{}", sign, first, point, rest, exponent_sign, exponent.abs())
+    } else if exponent >= 0 {
+        let whole_len = exponent as usize + 1;
+        if digits.len() <= whole_len {
+            format!("{}{}{}", sign, digits, "0".repeat(whole_len - digits.len()))
+        } else {
+            format!("{}{}.{}", sign, &digits[..whole_len], &digits[whole_len..])
+        }
+    } else {
+        format!("{}.{}{}", sign, "0".repeat((-exponent - 1) as usize), digits)
+    }
+}
+
+/// the calculator's random number generator: L'Ecuyer's combined linear congruential generator
+pub struct TiRand {
+    seeds: (f64, f64),
+}
+
+impl TiRand {
+    /// the seeds of a calculator fresh out of the box, as if after 0→rand
+    pub fn new() -> TiRand {
+        TiRand { seeds: (12345.0, 67890.0) }
+    }
+    /// what `rand` gives
+    pub fn next(&mut self) -> f64 {
+        let (s1, s2) = self.seeds;
+        let s1 = (40014.0 * s1) % 2147483563.0;
+        let s2 = (40692.0 * s2) % 2147483399.0;
+        self.seeds = (s1, s2);
+        let r = (s1 - s2) / 2147483563.0;
+        round_to_precision(if r < 0.0 { r + 1.0 } else { r })
+    }
+    /// what `randInt(low,high)` gives, for integers low and high
+    pub fn int(&mut self, low: f64, high: f64) -> f64 {
+        let (low, high) = if low <= high { (low, high) } else { (high, low) };
+        low + (self.next() * (high - low + 1.0)).floor()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn real(re: f64) -> Complex {
+        Complex { re, im: 0.0 }
+    }
+    pub fn is_real(&self) -> bool {
+        self.im == 0.0
+    }
+    fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+    fn add(self, other: Complex) -> Complex {
+        Complex {
+            re: round_like(self.re + other.re, self.re.abs().max(other.re.abs())),
+            im: round_like(self.im + other.im, self.im.abs().max(other.im.abs())),
+        }
+    }
+    fn sub(self, other: Complex) -> Complex {
+        self.add(Complex { re: -other.re, im: -other.im })
+    }
+    fn mul(self, other: Complex) -> Complex {
+        Complex { re: self.re * other.re - self.im * other.im, im: self.re * other.im + self.im * other.re }
+    }
+    fn div(self, other: Complex) -> Sim<Complex> {
+        let denominator = other.re * other.re + other.im * other.im;
+        if denominator == 0.0 {
+            return Err("ERR:DIVIDE BY 0".to_string());
+        }
+        Ok(Complex {
+            re: (self.re * other.re + self.im * other.im) / denominator,
+            im: (self.im * other.re - self.re * other.im) / denominator,
+        })
+    }
+    fn ln(self) -> Complex {
+        Complex { re: self.abs().ln(), im: self.im.atan2(self.re) }
+    }
+    fn exp(self) -> Complex {
+        let magnitude = self.re.exp();
+        Complex { re: magnitude * self.im.cos(), im: magnitude * self.im.sin() }
+    }
+    fn sqrt(self) -> Complex {
+        if self.is_real() && self.re >= 0.0 {
+            return Complex::real(self.re.sqrt());
+        }
+        let magnitude = self.abs().sqrt();
+        let angle = self.im.atan2(self.re) / 2.0;
+        Complex { re: magnitude * angle.cos(), im: magnitude * angle.sin() }
+    }
+    fn pow(self, exponent: Complex) -> Sim<Complex> {
+        if self.re == 0.0 && self.im == 0.0 {
+            return match exponent.re {
+                re if re > 0.0 => Ok(Complex::real(0.0)),
+                re if re < 0.0 => Err("ERR:DIVIDE BY 0".to_string()),
+                _ => Err("ERR:DOMAIN".to_string()),
+            };
+        }
+        if self.is_real() && exponent.is_real() {
+            if self.re < 0.0 && exponent.re.fract() != 0.0 {
+                return Err("ERR:NONREAL ANS".to_string());
+            }
+            return Ok(Complex::real(self.re.powf(exponent.re)));
+        }
+        Ok(exponent.mul(self.ln()).exp())
+    }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let imaginary = |im: f64| if im == 1.0 { "𝑖".to_string() } else { format!("{}𝑖", format_number(im)) };
+        if self.is_real() {
+            write!(f, "{}", format_number(self.re))
+        } else if self.re == 0.0 {
+            if self.im < 0.0 {
+                write!(f, "⁻{}", imaginary(-self.im))
+            } else {
+                write!(f, "{}", imaginary(self.im))
+            }
+        } else {
+            let sign = if self.im < 0.0 { "-" } else { "+" };
+            write!(f, "{}{}{}", format_number(self.re), sign, imaginary(self.im.abs()))
+        }
+    }
+}
+
+/// anything a TI-84 Basic expression can produce
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Num(Complex),
+    /// strings are kept as tokens, since that is what `length(` and `sub(` count in
+    Str(Vec<&'static str>),
+    List(Vec<Complex>),
+}
+
+impl Value {
+    fn num(x: f64) -> Value {
+        Value::Num(Complex::real(x))
+    }
+    fn as_real(&self) -> Sim<f64> {
+        match *self {
+            Value::Num(c) if c.is_real() => Ok(c.re),
+            Value::Num(_) => Err("ERR:NONREAL ANS".to_string()),
+            _ => Err("ERR:DATA TYPE".to_string()),
+        }
+    }
+    fn as_index(&self) -> Sim<usize> {
+        let x = self.as_real()?;
+        if x.fract() != 0.0 || x < 1.0 {
+            return Err("ERR:INVALID DIM".to_string());
+        }
+        Ok(x as usize)
+    }
+    fn is_true(&self) -> Sim<bool> {
+        Ok(self.as_real()? != 0.0)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Num(c) => write!(f, "{}", c),
+            Value::Str(ref tokens) => write!(f, "{}", tokens.concat()),
+            Value::List(ref elems) => {
+                write!(f, "{{{}}}", elems.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(" "))
+            }
+        }
+    }
+}
+
+/// keep a number inside what the calculator can hold
+fn checked(c: Complex) -> Sim<Complex> {
+    if !c.re.is_finite() || !c.im.is_finite() || c.re.abs() >= 1e100 || c.im.abs() >= 1e100 {
+        return Err("ERR:OVERFLOW".to_string());
+    }
+    Ok(Complex { re: round_to_precision(c.re), im: round_to_precision(c.im) })
+}
+
+/// apply a numeric operation to a number, or to every element of a list
+fn map_num<F: Fn(Complex) -> Sim<Complex>>(value: Value, op: F) -> Sim<Value> {
+    match value {
+        Value::Num(c) => Ok(Value::Num(checked(op(c)?)?)),
+        Value::List(elems) => Ok(Value::List(elems.into_iter().map(|c| checked(op(c)?)).collect::<Sim<Vec<Complex>>>()?)),
+        Value::Str(_) => Err("ERR:DATA TYPE".to_string()),
+    }
+}
+
+/// like `map_num`, for operations only defined on real numbers
+fn map_real<F: Fn(f64) -> Sim<f64>>(value: Value, op: F) -> Sim<Value> {
+    map_num(value, |c| if c.is_real() { Ok(Complex::real(op(c.re)?)) } else { Err("ERR:DATA TYPE".to_string()) })
+}
+
+/// apply a numeric operation pairwise, letting a single number stand in for every element of a list
+fn zip_num<F: Fn(Complex, Complex) -> Sim<Complex>>(left: Value, right: Value, op: F) -> Sim<Value> {
+    match (left, right) {
+        (Value::Num(a), Value::Num(b)) => Ok(Value::Num(checked(op(a, b)?)?)),
+        (Value::List(elems), Value::Num(b)) => map_num(Value::List(elems), |a| op(a, b)),
+        (Value::Num(a), Value::List(elems)) => map_num(Value::List(elems), |b| op(a, b)),
+        (Value::List(lefts), Value::List(rights)) => {
+            if lefts.len() != rights.len() {
+                return Err("ERR:DIM MISMATCH".to_string());
+            }
+            Ok(Value::List(lefts.into_iter().zip(rights).map(|(a, b)| checked(op(a, b)?)).collect::<Sim<Vec<Complex>>>()?))
+        }
+        _ => Err("ERR:DATA TYPE".to_string()),
+    }
+}
+
+/// like `zip_num`, for operations only defined on real numbers
+fn zip_real<F: Fn(f64, f64) -> Sim<f64>>(left: Value, right: Value, op: F) -> Sim<Value> {
+    zip_num(left, right, |a, b| {
+        if a.is_real() && b.is_real() { Ok(Complex::real(op(a.re, b.re)?)) } else { Err("ERR:DATA TYPE".to_string()) }
+    })
+}
+
+fn truth(b: bool) -> f64 {
+    if b { 1.0 } else { 0.0 }
+}
+
+fn is_name_token(token: &str) -> bool {
+    token == "θ" || (token.len() == 1 && token.chars().all(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit()))
+}
+
+fn is_real_var(token: &str) -> bool {
+    token == "θ" || (token.len() == 1 && token.chars().all(|ch| ch.is_ascii_uppercase()))
+}
+
+fn is_string_var(token: &str) -> bool {
+    token.len() == 4 && token.starts_with("Str")
+}
+
+/// can this token start a value, and so imply a multiplication when it follows another one?
+fn starts_value(token: &str) -> bool {
+    is_real_var(token) || is_string_var(token) || FUNCTIONS.contains(&token) || token.chars().all(|ch| ch.is_ascii_digit())
+        || matches!(token, "." | "ᴇ" | "(" | "{" | "π" | "𝑖" | "ℯ" | "Ans" | "rand" | "⌊")
+}
+
+/// one statement of the program, a line or a piece of a line split by `:`
+struct Stmnt {
+    line: usize,
+    tokens: Vec<&'static str>,
+}
+
+impl Stmnt {
+    fn first(&self) -> Option<&'static str> {
+        self.tokens.first().cloned()
+    }
+    fn rest(&self) -> &[&'static str] {
+        &self.tokens[1.min(self.tokens.len())..]
+    }
+}
+
+/// the blocks we are inside of, each with the statement that opened it
+#[derive(Debug)]
+enum Block {
+    If,
+    While(usize),
+    Repeat(usize),
+}
+
+struct Cursor<'t> {
+    tokens: &'t [&'static str],
+    at: usize,
+}
+
+impl<'t> Cursor<'t> {
+    fn peek(&self) -> Option<&'static str> {
+        self.tokens.get(self.at).cloned()
+    }
+    fn next(&mut self) -> Option<&'static str> {
+        let token = self.peek();
+        if token.is_some() {
+            self.at += 1;
+        }
+        token
+    }
+    fn eat(&mut self, token: &str) -> bool {
+        if self.peek() == Some(token) {
+            self.at += 1;
+            true
+        } else {
+            false
+        }
+    }
+    /// the calculator lets closing parentheses be left off before a store or the end of the line
+    fn close(&mut self) -> Sim<()> {
+        if self.eat(")") || self.peek().is_none_or(|token| token == "→") {
+            Ok(())
+        } else {
+            Err(format!("ERR:SYNTAX (expected `)` but found `{}`)", self.peek().unwrap()))
+        }
+    }
+    fn finish(&self) -> Sim<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(format!("ERR:SYNTAX (unexpected `{}`)", token)),
+        }
+    }
+    /// the name of a list, after its ⌊
+    fn list_name(&mut self) -> Sim<String> {
+        let mut name = String::new();
+        while let Some(token) = self.peek() {
+            if !is_name_token(token) || name.chars().count() == 5 {
+                break;
+            }
+            name.push_str(token);
+            self.at += 1;
+        }
+        if name.is_empty() { Err("ERR:SYNTAX (missing list name)".to_string()) } else { Ok(name) }
+    }
+}
+
+/// what was left behind after running a program
+#[derive(Debug)]
+pub struct Simulation {
+    /// every line the program showed with `Disp` or `Pause`
+    pub display: Vec<String>,
+    /// the last answer, which the home screen shows when the program ends on an expression
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub ans: Value,
+    /// the error screen the program stopped on, if it didn't finish
+    pub error: Option<String>,
+}
+
+/// a TI-84 Plus running the subset of TI-84 Basic that the compiler emits
+struct Simulator {
+    reals: HashMap<&'static str, Complex>,
+    strings: HashMap<&'static str, Vec<&'static str>>,
+    lists: HashMap<String, Vec<Complex>>,
+    ans: Value,
+    rand: TiRand,
+    display: Vec<String>,
+}
+
+impl Simulator {
+    fn new() -> Simulator {
+        Simulator {
+            reals: HashMap::new(),
+            strings: HashMap::new(),
+            lists: HashMap::new(),
+            ans: Value::num(0.0),
+            rand: TiRand::new(),
+            display: vec![],
+        }
+    }
+
+    fn list(&self, name: &str) -> Sim<&Vec<Complex>> {
+        self.lists.get(name).ok_or_else(|| format!("ERR:UNDEFINED (⌊{})", name))
+    }
+
+    fn expr(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let mut left = self.and_expr(cur)?;
+        loop {
+            if cur.eat(" or ") {
+                let right = self.and_expr(cur)?;
+                left = zip_real(left, right, |a, b| Ok(truth(a != 0.0 || b != 0.0)))?;
+            } else if cur.eat(" xor ") {
+                let right = self.and_expr(cur)?;
+                left = zip_real(left, right, |a, b| Ok(truth((a != 0.0) != (b != 0.0))))?;
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn and_expr(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let mut left = self.relation(cur)?;
+        while cur.eat(" and ") {
+            let right = self.relation(cur)?;
+            left = zip_real(left, right, |a, b| Ok(truth(a != 0.0 && b != 0.0)))?;
+        }
+        Ok(left)
+    }
+
+    fn relation(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let mut left = self.sum(cur)?;
+        while let Some(op) = cur.peek() {
+            let compare: fn(f64, f64) -> bool = match op {
+                "=" => |a, b| a == b,
+                "≠" => |a, b| a != b,
+                "<" => |a, b| a < b,
+                ">" => |a, b| a > b,
+                "≤" => |a, b| a <= b,
+                "≥" => |a, b| a >= b,
+                _ => break,
+            };
+            cur.next();
+            let right = self.sum(cur)?;
+            left = match (left, right) {
+                // strings can only be tested for equality
+                (Value::Str(a), Value::Str(b)) if op == "=" || op == "≠" => Value::num(truth((a == b) == (op == "="))),
+                (Value::Num(a), Value::Num(b)) if op == "=" || op == "≠" => Value::num(truth((a == b) == (op == "="))),
+                (left, right) => zip_real(left, right, |a, b| Ok(truth(compare(a, b))))?,
+            };
+        }
+        Ok(left)
+    }
+
+    fn sum(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let mut left = self.product(cur)?;
+        loop {
+            if cur.eat("+") {
+                let right = self.product(cur)?;
+                left = match (left, right) {
+                    (Value::Str(mut a), Value::Str(b)) => {
+                        a.extend(b);
+                        Value::Str(a)
+                    }
+                    (left, right) => zip_num(left, right, |a, b| Ok(a.add(b)))?,
+                };
+            } else if cur.eat("-") {
+                let right = self.product(cur)?;
+                left = zip_num(left, right, |a, b| Ok(a.sub(b)))?;
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn product(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let mut left = self.negation(cur)?;
+        loop {
+            if cur.eat("*") {
+                let right = self.negation(cur)?;
+                left = zip_num(left, right, |a, b| Ok(a.mul(b)))?;
+            } else if cur.eat("/") {
+                let right = self.negation(cur)?;
+                left = zip_num(left, right, |a, b| a.div(b))?;
+            } else if cur.peek().is_some_and(starts_value) {
+                // implied multiplication, as in 10W
+                let right = self.negation(cur)?;
+                left = zip_num(left, right, |a, b| Ok(a.mul(b)))?;
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    /// negation binds looser than powers, so ⁻2² is ⁻4
+    fn negation(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        if cur.eat("⁻") {
+            let value = self.negation(cur)?;
+            map_num(value, |c| Ok(Complex::real(0.0).sub(c)))
+        } else {
+            self.power(cur)
+        }
+    }
+
+    fn power(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let mut left = self.postfix(cur)?;
+        while cur.eat("^") {
+            let negate = cur.eat("⁻");
+            let mut right = self.postfix(cur)?;
+            if negate {
+                right = map_num(right, |c| Ok(Complex::real(0.0).sub(c)))?;
+            }
+            left = zip_num(left, right, |a, b| a.pow(b))?;
+        }
+        Ok(left)
+    }
+
+    fn postfix(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let mut value = self.primary(cur)?;
+        loop {
+            if cur.eat("²") {
+                value = map_num(value, |c| Ok(c.mul(c)))?;
+            } else if cur.eat("⁻¹") {
+                value = map_num(value, |c| Complex::real(1.0).div(c))?;
+            } else if cur.eat("!") {
+                value = map_real(value, |x| {
+                    if x.fract() != 0.0 || !(0.0..=69.0).contains(&x) {
+                        return Err("ERR:DOMAIN".to_string());
+                    }
+                    Ok((1..=x as u32).map(f64::from).product())
+                })?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn number(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let mut text = String::new();
+        while let Some(token) = cur.peek() {
+            if token == "." || token.chars().all(|ch| ch.is_ascii_digit()) {
+                text.push_str(token);
+                cur.next();
+            } else {
+                break;
+            }
+        }
+        if cur.eat("ᴇ") {
+            if text.is_empty() {
+                text.push('1');
+            }
+            text.push('e');
+            if cur.eat("⁻") {
+                text.push('-');
+            }
+            while let Some(token) = cur.peek().filter(|token| token.chars().all(|ch| ch.is_ascii_digit())) {
+                text.push_str(token);
+                cur.next();
+            }
+        }
+        let x = text.parse::<f64>().map_err(|_| format!("ERR:SYNTAX (malformed number `{}`)", text))?;
+        Ok(Value::Num(checked(Complex::real(x))?))
+    }
+
+    fn primary(&mut self, cur: &mut Cursor) -> Sim<Value> {
+        let token = match cur.peek() {
+            Some(token) => token,
+            None => return Err("ERR:SYNTAX (missing a value)".to_string()),
+        };
+        if token == "." || token == "ᴇ" || token.chars().all(|ch| ch.is_ascii_digit()) {
+            return self.number(cur);
+        }
+        cur.next();
+        match token {
+            "(" => {
+                let value = self.expr(cur)?;
+                cur.close()?;
+                Ok(value)
+            }
+            "{" => {
+                let mut elems = vec![];
+                loop {
+                    elems.push(match self.expr(cur)? {
+                        Value::Num(c) => c,
+                        _ => return Err("ERR:DATA TYPE".to_string()),
+                    });
+                    if !cur.eat(",") {
+                        break;
+                    }
+                }
+                if !cur.eat("}") && cur.peek().is_some_and(|token| token != "→") {
+                    return Err("ERR:SYNTAX (expected `}`)".to_string());
+                }
+                Ok(Value::List(elems))
+            }
+            "\"" => {
+                // a string runs until its closing quote, a store or the end of the line
+                let mut text = vec![];
+                while let Some(token) = cur.peek() {
+                    if token == "→" {
+                        break;
+                    }
+                    cur.next();
+                    if token == "\"" {
+                        break;
+                    }
+                    text.push(token);
+                }
+                Ok(Value::Str(text))
+            }
+            "⌊" => {
+                let name = cur.list_name()?;
+                if cur.eat("(") {
+                    let index = self.expr(cur)?.as_index()?;
+                    cur.close()?;
+                    let list = self.list(&name)?;
+                    list.get(index - 1).map(|&c| Value::Num(c)).ok_or_else(|| "ERR:INVALID DIM".to_string())
+                } else {
+                    Ok(Value::List(self.list(&name)?.clone()))
+                }
+            }
+            "π" => Ok(Value::num(round_to_precision(std::f64::consts::PI))),
+            "ℯ" => Ok(Value::num(round_to_precision(std::f64::consts::E))),
+            "𝑖" => Ok(Value::Num(Complex { re: 0.0, im: 1.0 })),
+            "Ans" => Ok(self.ans.clone()),
+            "rand" => Ok(Value::num(self.rand.next())),
+            _ if is_real_var(token) => Ok(Value::Num(self.reals.get(token).cloned().unwrap_or(Complex::real(0.0)))),
+            _ if is_string_var(token) => {
+                self.strings.get(token).cloned().map(Value::Str).ok_or_else(|| format!("ERR:UNDEFINED ({})", token))
+            }
+            _ if FUNCTIONS.contains(&token) => {
+                let mut args = vec![self.expr(cur)?];
+                while cur.eat(",") {
+                    args.push(self.expr(cur)?);
+                }
+                cur.close()?;
+                self.call(token, args)
+            }
+            _ => Err(format!("ERR:SYNTAX (`{}` is not supported here)", token)),
+        }
+    }
+
+    fn call(&mut self, name: &'static str, mut args: Vec<Value>) -> Sim<Value> {
+        let arg_count = args.len();
+        let mut args_iter = args.drain(..);
+        let mut arg = move || args_iter.next().unwrap();
+        match (name, arg_count) {
+            ("dim(", 1) => match arg() {
+                Value::List(elems) => Ok(Value::num(elems.len() as f64)),
+                _ => Err("ERR:DATA TYPE".to_string()),
+            },
+            ("augment(", 2) => match (arg(), arg()) {
+                (Value::List(mut a), Value::List(b)) => {
+                    a.extend(b);
+                    if a.len() > MAX_LIST_LEN {
+                        return Err("ERR:INVALID DIM".to_string());
+                    }
+                    Ok(Value::List(a))
+                }
+                (Value::Str(_), Value::Str(_)) => Err("ERR:DATA TYPE (join strings with +)".to_string()),
+                _ => Err("ERR:DATA TYPE".to_string()),
+            },
+            ("sub(", 3) => {
+                let text = match arg() {
+                    Value::Str(text) => text,
+                    _ => return Err("ERR:DATA TYPE".to_string()),
+                };
+                let start = arg().as_real()?;
+                let len = arg().as_real()?;
+                if start.fract() != 0.0 || len.fract() != 0.0 || start < 1.0 || len < 1.0 || start + len - 1.0 > text.len() as f64 {
+                    return Err(format!("ERR:DOMAIN (sub( of {} tokens from {} in a string of {})", len, start, text.len()));
+                }
+                let start = start as usize - 1;
+                Ok(Value::Str(text[start..start + len as usize].to_vec()))
+            }
+            ("length(", 1) => match arg() {
+                Value::Str(text) => Ok(Value::num(text.len() as f64)),
+                _ => Err("ERR:DATA TYPE".to_string()),
+            },
+            ("inString(", 2) => match (arg(), arg()) {
+                (Value::Str(haystack), Value::Str(needle)) => {
+                    let found = if needle.is_empty() { None } else { haystack.windows(needle.len()).position(|window| window == &needle[..]) };
+                    Ok(Value::num(found.map_or(0.0, |at| (at + 1) as f64)))
+                }
+                _ => Err("ERR:DATA TYPE".to_string()),
+            },
+            ("iPart(", 1) => map_real(arg(), |x| Ok(x.trunc())),
+            ("fPart(", 1) => map_real(arg(), |x| Ok(round_like(x.fract(), x))),
+            ("int(", 1) => map_real(arg(), |x| Ok(x.floor())),
+            ("abs(", 1) => map_num(arg(), |c| Ok(Complex::real(c.abs()))),
+            ("round(", 1) | ("round(", 2) => {
+                let value = arg();
+                let places = if arg_count == 2 { arg().as_real()? } else { 9.0 };
+                if places.fract() != 0.0 || !(0.0..=9.0).contains(&places) {
+                    return Err("ERR:DOMAIN".to_string());
+                }
+                let scale = 10f64.powi(places as i32);
+                map_real(value, |x| Ok((x * scale).round() / scale))
+            }
+            ("not(", 1) => map_real(arg(), |x| Ok(truth(x == 0.0))),
+            ("remainder(", 2) => zip_real(arg(), arg(), |a, b| {
+                if a.fract() != 0.0 || b.fract() != 0.0 {
+                    return Err("ERR:DOMAIN (remainder( needs integers)".to_string());
+                }
+                if b == 0.0 {
+                    return Err("ERR:DIVIDE BY 0".to_string());
+                }
+                Ok(a % b)
+            }),
+            ("randInt(", 2) => {
+                let (low, high) = (arg().as_real()?, arg().as_real()?);
+                if low.fract() != 0.0 || high.fract() != 0.0 {
+                    return Err("ERR:DOMAIN".to_string());
+                }
+                Ok(Value::num(self.rand.int(low, high)))
+            }
+            ("ln(", 1) => map_num(arg(), |c| {
+                if c.is_real() && c.re < 0.0 {
+                    return Err("ERR:NONREAL ANS".to_string());
+                }
+                if c.re == 0.0 && c.im == 0.0 {
+                    return Err("ERR:DOMAIN".to_string());
+                }
+                Ok(c.ln())
+            }),
+            ("log(", 1) => map_num(arg(), |c| {
+                if c.is_real() && c.re < 0.0 {
+                    return Err("ERR:NONREAL ANS".to_string());
+                }
+                if c.re == 0.0 && c.im == 0.0 {
+                    return Err("ERR:DOMAIN".to_string());
+                }
+                c.ln().div(Complex::real(10f64.ln()))
+            }),
+            ("ℯ^(", 1) => map_num(arg(), |c| Ok(c.exp())),
+            ("₁₀^(", 1) => map_num(arg(), |c| Complex::real(10.0).pow(c)),
+            ("√(", 1) => map_num(arg(), |c| {
+                if c.is_real() && c.re < 0.0 {
+                    return Err("ERR:NONREAL ANS".to_string());
+                }
+                Ok(c.sqrt())
+            }),
+            ("sin(", 1) => map_real(arg(), |x| Ok(x.sin())),
+            ("cos(", 1) => map_real(arg(), |x| Ok(x.cos())),
+            ("tan(", 1) => map_real(arg(), |x| Ok(x.tan())),
+            ("sin⁻¹(", 1) => map_real(arg(), |x| if x.abs() > 1.0 { Err("ERR:DOMAIN".to_string()) } else { Ok(x.asin()) }),
+            ("cos⁻¹(", 1) => map_real(arg(), |x| if x.abs() > 1.0 { Err("ERR:DOMAIN".to_string()) } else { Ok(x.acos()) }),
+            ("tan⁻¹(", 1) => map_real(arg(), |x| Ok(x.atan())),
+            ("max(", 2) => zip_real(arg(), arg(), |a, b| Ok(a.max(b))),
+            ("min(", 2) => zip_real(arg(), arg(), |a, b| Ok(a.min(b))),
+            ("max(", 1) | ("min(", 1) => match arg() {
+                Value::List(ref elems) if !elems.is_empty() => {
+                    let reals = elems.iter().map(|c| if c.is_real() { Ok(c.re) } else { Err("ERR:DATA TYPE".to_string()) }).collect::<Sim<Vec<f64>>>()?;
+                    let pick = if name == "max(" { f64::max } else { f64::min };
+                    Ok(Value::num(reals.into_iter().fold(if name == "max(" { f64::MIN } else { f64::MAX }, pick)))
+                }
+                _ => Err("ERR:DATA TYPE".to_string()),
+            },
+            ("real(", 1) => map_num(arg(), |c| Ok(Complex::real(c.re))),
+            ("imag(", 1) => map_num(arg(), |c| Ok(Complex::real(c.im))),
+            ("sum(", 1) => match arg() {
+                Value::List(elems) => Ok(Value::Num(checked(elems.into_iter().fold(Complex::real(0.0), Complex::add))?)),
+                _ => Err("ERR:DATA TYPE".to_string()),
+            },
+            ("gcd(", 2) | ("lcm(", 2) => zip_real(arg(), arg(), |a, b| {
+                if a.fract() != 0.0 || b.fract() != 0.0 || a < 0.0 || b < 0.0 {
+                    return Err("ERR:DOMAIN".to_string());
+                }
+                let (mut x, mut y) = (a, b);
+                while y != 0.0 {
+                    let next = x % y;
+                    x = y;
+                    y = next;
+                }
+                if name == "gcd(" { Ok(x) } else if x == 0.0 { Ok(0.0) } else { Ok(a / x * b) }
+            }),
+            _ => Err(format!("ERR:ARGUMENT ({} does not take {} arguments)", name, arg_count)),
+        }
+    }
+
+    /// store a value into whatever the tokens after → name
+    fn store(&mut self, value: Value, cur: &mut Cursor) -> Sim<()> {
+        let target = cur.next().ok_or_else(|| "ERR:SYNTAX (nothing to store into)".to_string())?;
+        match (target, value) {
+            (_, Value::Num(c)) if is_real_var(target) => {
+                self.reals.insert(target, c);
+            }
+            (_, Value::Str(text)) if is_string_var(target) => {
+                self.strings.insert(target, text);
+            }
+            ("⌊", value) => {
+                let name = cur.list_name()?;
+                if cur.eat("(") {
+                    let index = self.expr(cur)?.as_index()?;
+                    cur.close()?;
+                    let c = match value {
+                        Value::Num(c) => c,
+                        _ => return Err("ERR:DATA TYPE".to_string()),
+                    };
+                    let list = self.lists.entry(name).or_default();
+                    if index <= list.len() {
+                        list[index - 1] = c;
+                    } else if index == list.len() + 1 && index <= MAX_LIST_LEN {
+                        // storing one past the end grows the list
+                        list.push(c);
+                    } else {
+                        return Err(format!("ERR:INVALID DIM (element {} of a list of {})", index, list.len()));
+                    }
+                } else {
+                    match value {
+                        Value::List(elems) => {
+                            self.lists.insert(name, elems);
+                        }
+                        _ => return Err("ERR:DATA TYPE".to_string()),
+                    }
+                }
+            }
+            ("dim(", value) => {
+                if !cur.eat("⌊") {
+                    return Err("ERR:SYNTAX (dim( can only resize a list)".to_string());
+                }
+                let name = cur.list_name()?;
+                cur.close()?;
+                let len = value.as_real()?;
+                if len.fract() != 0.0 || len < 1.0 || len > MAX_LIST_LEN as f64 {
+                    return Err(format!("ERR:INVALID DIM ({}→dim(⌊{})", format_number(len), name));
+                }
+                self.lists.entry(name).or_default().resize(len as usize, Complex::real(0.0));
+            }
+            (target, _) if is_real_var(target) || is_string_var(target) => return Err("ERR:DATA TYPE".to_string()),
+            (target, _) => return Err(format!("ERR:SYNTAX (can not store into `{}`)", target)),
+        }
+        cur.finish()
+    }
+
+    /// find the End (or the Else, if we're asked to) that closes the block starting at `from`
+    fn skip_block(stmnts: &[Stmnt], from: usize, stop_at_else: bool) -> Sim<usize> {
+        let mut depth = 0;
+        for (index, stmnt) in stmnts.iter().enumerate().skip(from) {
+            match stmnt.first() {
+                Some("Then") | Some("While ") | Some("Repeat ") | Some("For(") => depth += 1,
+                Some("End") if depth == 0 => return Ok(index),
+                Some("End") => depth -= 1,
+                Some("Else") if depth == 0 && stop_at_else => return Ok(index),
+                _ => {}
+            }
+        }
+        Err("ERR:SYNTAX (block is missing its End)".to_string())
+    }
+
+    /// run a single statement, returning where to go next or None to stop
+    fn step(&mut self, stmnts: &[Stmnt], labels: &HashMap<String, usize>, blocks: &mut Vec<Block>, at: usize) -> Sim<Option<usize>> {
+        let stmnt = &stmnts[at];
+        let mut cur = Cursor { tokens: stmnt.rest(), at: 0 };
+        match stmnt.first() {
+            None => {}
+            Some("If ") => {
+                let condition = self.expr(&mut cur)?.is_true()?;
+                cur.finish()?;
+                let has_then = stmnts.get(at + 1).is_some_and(|next| next.tokens == ["Then"]);
+                return Ok(Some(match (has_then, condition) {
+                    (true, true) => {
+                        blocks.push(Block::If);
+                        at + 2
+                    }
+                    (true, false) => {
+                        let skip_to = Simulator::skip_block(stmnts, at + 2, true)?;
+                        if stmnts[skip_to].first() == Some("Else") {
+                            blocks.push(Block::If);
+                        }
+                        skip_to + 1
+                    }
+                    // without a Then, If only guards the next statement
+                    (false, true) => at + 1,
+                    (false, false) => at + 2,
+                }));
+            }
+            Some("Then") => return Err("ERR:SYNTAX (Then without an If)".to_string()),
+            Some("Else") => {
+                // we only run into an Else after finishing the true branch
+                let end = Simulator::skip_block(stmnts, at + 1, false)?;
+                blocks.pop();
+                return Ok(Some(end + 1));
+            }
+            Some("End") => {
+                return match blocks.pop() {
+                    None => Err("ERR:SYNTAX (End without a block)".to_string()),
+                    Some(Block::If) => Ok(Some(at + 1)),
+                    // go back and check the condition again
+                    Some(Block::While(start)) => Ok(Some(start)),
+                    Some(Block::Repeat(start)) => {
+                        let mut condition_cur = Cursor { tokens: stmnts[start].rest(), at: 0 };
+                        if self.expr(&mut condition_cur)?.is_true()? {
+                            Ok(Some(at + 1))
+                        } else {
+                            blocks.push(Block::Repeat(start));
+                            Ok(Some(start + 1))
+                        }
+                    }
+                };
+            }
+            Some("While ") => {
+                let condition = self.expr(&mut cur)?.is_true()?;
+                cur.finish()?;
+                if condition {
+                    blocks.push(Block::While(at));
+                    return Ok(Some(at + 1));
+                }
+                return Ok(Some(Simulator::skip_block(stmnts, at + 1, false)? + 1));
+            }
+            Some("Repeat ") => {
+                // the condition is only checked at the End
+                blocks.push(Block::Repeat(at));
+            }
+            Some("Lbl ") => {}
+            Some("Goto ") => {
+                let label = stmnt.rest().concat();
+                return labels.get(&label).map(|&index| Some(index + 1)).ok_or_else(|| format!("ERR:LABEL (no Lbl {})", label));
+            }
+            Some("Return") | Some("Stop") => return Ok(None),
+            Some("Disp ") => loop {
+                let value = self.expr(&mut cur)?;
+                self.display.push(value.to_string());
+                if !cur.eat(",") {
+                    cur.finish()?;
+                    break;
+                }
+            },
+            Some("Pause ") => {
+                let value = self.expr(&mut cur)?;
+                cur.finish()?;
+                self.display.push(value.to_string());
+            }
+            Some("Pause") | Some("ClrHome") => {}
+            Some(_) => {
+                // anything else is an expression, maybe stored somewhere
+                cur = Cursor { tokens: &stmnt.tokens, at: 0 };
+                let value = self.expr(&mut cur)?;
+                if cur.eat("→") {
+                    self.store(value.clone(), &mut cur)?;
+                } else {
+                    cur.finish()?;
+                }
+                self.ans = value;
+            }
+        }
+        Ok(Some(at + 1))
+    }
+}
+
+/// split the program into statements, on newlines and on colons outside of strings
+fn split_stmnts(text: &str) -> Result<Vec<Stmnt>, Error> {
+    let mut stmnts = vec![Stmnt { line: 1, tokens: vec![] }];
+    let mut line = 1;
+    let mut in_string = false;
+    for &&(_, token) in split_basic(text)?.iter() {
+        match token {
+            "\n" => line += 1,
+            ":" if !in_string => {}
+            _ => {
+                if token == "\"" {
+                    in_string = !in_string;
+                } else if token == "→" {
+                    in_string = false;
+                }
+                stmnts.last_mut().unwrap().tokens.push(token);
+                continue;
+            }
+        }
+        in_string = false;
+        stmnts.push(Stmnt { line, tokens: vec![] });
+    }
+    Ok(stmnts)
+}
+
+/// run a TI-84 Basic program, capturing everything it shows
+/// only fails if the text can't be read as calculator tokens; errors while running end up in the simulation
+pub fn simulate(text: &str) -> Result<Simulation, Error> {
+    simulate_for(text, STEP_LIMIT)
+}
+
+/// run a program for at most `step_limit` statements
+fn simulate_for(text: &str, step_limit: usize) -> Result<Simulation, Error> {
+    let stmnts = split_stmnts(text)?;
+    // Goto always jumps to the first matching label
+    let mut labels = HashMap::new();
+    for (index, stmnt) in stmnts.iter().enumerate() {
+        if stmnt.first() == Some("Lbl ") {
+            labels.entry(stmnt.rest().concat()).or_insert(index);
+        }
+    }
+
+    let mut simulator = Simulator::new();
+    let mut blocks = vec![];
+    let mut at = 0;
+    let mut steps = 0;
+    let mut error = None;
+    while at < stmnts.len() {
+        steps += 1;
+        if steps > step_limit {
+            error = Some(format!("the program ran for more than {} statements without stopping", step_limit));
+            break;
+        }
+        at = match simulator.step(&stmnts, &labels, &mut blocks, at) {
+            Ok(Some(next)) => next,
+            Ok(None) => break,
+            Err(why) => {
+                error = Some(format!("{} on line {}: {}", why, stmnts[at].line, stmnts[at].tokens.concat()));
+                break;
+            }
+        };
+    }
+    Ok(Simulation { display: simulator.display, ans: simulator.ans, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// run a hand written program, which has to read as calculator tokens
+    fn run(text: &str) -> Simulation {
+        simulate(text).unwrap_or_else(|error| panic!("{}", error.why))
+    }
+
+    /// what a program shows, which has to finish without an error
+    fn shown(text: &str) -> Vec<String> {
+        let simulation = run(text);
+        assert_eq!(simulation.error, None, "the program stopped on an error");
+        simulation.display
+    }
+
+    /// the error screen a program stops on, without the details after it
+    fn error(text: &str) -> String {
+        let error = run(text).error.expect("the program finished without an error");
+        let end = [" (", " on line "].iter().filter_map(|detail| error.find(detail)).min().unwrap();
+        error[..end].to_string()
+    }
+
+    #[test]
+    fn false_if_skips_nested_blocks() {
+        let program = "0→A\nIf A\nThen\nIf 1\nThen\nDisp 1\nElse\nDisp 2\nEnd\nWhile 1\nDisp 3\nEnd\nElse\nDisp 4\nEnd\nDisp 5";
+        assert_eq!(shown(program), vec!["4", "5"]);
+        // and without an Else, straight past the End
+        assert_eq!(shown("If 0\nThen\nIf 1\nThen\nDisp 1\nEnd\nEnd\nDisp 2"), vec!["2"]);
+    }
+
+    #[test]
+    fn repeat_runs_at_least_once() {
+        assert_eq!(shown("0→A\nRepeat 1\nA+1→A\nDisp A\nEnd"), vec!["1"]);
+        assert_eq!(shown("0→A\nRepeat A=3\nA+1→A\nEnd\nDisp A"), vec!["3"]);
+    }
+
+    #[test]
+    fn while_can_run_zero_times() {
+        assert_eq!(shown("0→A\nWhile A\nDisp 1\nIf 1\nThen\nDisp 2\nEnd\nEnd\nDisp 3"), vec!["3"]);
+    }
+
+    #[test]
+    fn goto_to_a_label_after_blocks() {
+        let program = "1→A\nWhile 1\nIf A\nThen\nGoto B\nEnd\nDisp 1\nEnd\nDisp 2\nLbl B\nDisp 3";
+        assert_eq!(shown(program), vec!["3"]);
+        // Goto goes to the first label of the name, even when it is further down than a later one
+        assert_eq!(shown("Goto C\nLbl C\nDisp 1\nReturn\nLbl C\nDisp 2"), vec!["1"]);
+        assert_eq!(error("Goto D"), "ERR:LABEL");
+    }
+
+    #[test]
+    fn sub_and_length_count_tokens_of_str0() {
+        // sin( is a single token
+        assert_eq!(shown("\"ABsin(C\"→Str0\nDisp length(Str0)\nDisp sub(Str0,2,2)"), vec!["4", "Bsin("]);
+        assert_eq!(error("\"ABC\"→Str0\nsub(Str0,3,2)"), "ERR:DOMAIN");
+        assert_eq!(error("length(1)"), "ERR:DATA TYPE");
+    }
+
+    #[test]
+    fn lists_grow_only_so_far() {
+        assert_eq!(shown("999→dim(⌊A\nDisp dim(⌊A)"), vec!["999"]);
+        assert_eq!(error("1000→dim(⌊A"), "ERR:INVALID DIM");
+        assert_eq!(error("999→dim(⌊A\naugment(⌊A,{1})→⌊A"), "ERR:INVALID DIM");
+    }
+
+    #[test]
+    fn runaway_program_stops_at_the_step_limit() {
+        let simulation = simulate_for("0→A\nLbl A\nA+1→A\nGoto A", 100).unwrap();
+        assert_eq!(simulation.error.as_deref(), Some("the program ran for more than 100 statements without stopping"));
+        // two statements to start, then 49 times around a loop of two, since Goto jumps past its Lbl
+        assert_eq!(simulation.ans.to_string(), "49");
+    }
+}
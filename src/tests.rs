@@ -0,0 +1,128 @@
+//! end to end tests: every program in tests/fixtures is run straight from its s-exprs by the evaluator,
+//! and again as compiled TI-84 Basic by the simulator, and the two have to show the same thing
+
+use manager::Manager;
+use util::Error;
+use evaluator::Value;
+use mir::MirProgram;
+use {parser, tokenizer, scoping, type_checker, mir, code_gen, ti_tokens, eight_xp, evaluator, simulator, formatter};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+const FIXTURE_DIR: &str = "tests/fixtures";
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURE_DIR))
+        .expect("missing the fixture directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    assert!(!paths.is_empty(), "no fixtures found in {}", FIXTURE_DIR);
+    paths
+}
+
+/// everything up to and including type checking
+fn analyze(path: &Path, source: String) -> Result<Manager, String> {
+    let mut m = Manager::new(source);
+    m.file_name = path.display().to_string();
+    let readout = |errors: Vec<Error>, m: &Manager| errors.iter().map(|error| error.get_readout(m)).collect::<Vec<String>>().join("\n");
+    let tokens = tokenizer::tokenize(&m.source).map_err(|errors| readout(errors, &m))?;
+    parser::generate_global_sexprs(&mut m, tokens).map_err(|errors| readout(errors, &m))?;
+    m.initialize_type_info().map_err(|errors| readout(errors, &m))?;
+    scoping::create_all_scopes(&mut m).map_err(|errors| readout(errors, &m))?;
+    type_checker::type_check_all(&mut m).map_err(|errors| readout(errors, &m))?;
+    Ok(m)
+}
+
+/// compile to TI-84 Basic, checking on the way that the program survives a trip through a .8xp file
+fn compile(m: &Manager, mir: &MirProgram) -> Result<String, String> {
+    let text = code_gen::build_program(m, mir).map_err(|error| error.get_readout(m))?;
+    let text = text.trim_end_matches('\n');
+    let tokens = ti_tokens::tokenize_basic(text).map_err(|error| error.get_readout(m))?;
+    let file = eight_xp::make_8xp("FIXTURE", &tokens).map_err(|error| error.get_readout(m))?;
+    let (name, read_tokens) = eight_xp::read_8xp(&file).map_err(|error| error.get_readout(m))?;
+    let read_text = ti_tokens::detokenize_basic(&read_tokens).map_err(|error| error.get_readout(m))?;
+    if name != "FIXTURE" || read_text != text {
+        return Err("the program changed on its way through a .8xp file".to_string());
+    }
+    Ok(read_text)
+}
+
+/// formatting has to settle: formatting what fmt printed changes nothing
+fn check_format(source: &str) -> Result<(), String> {
+    let lay_out = |source: &str| {
+        let tokens = tokenizer::tokenize_keeping_comments(source).map_err(|errors| errors[0].why.clone())?;
+        formatter::format_source(source, &tokens).map_err(|error| error.why)
+    };
+    let formatted = lay_out(source)?;
+    if lay_out(&formatted)? != formatted {
+        return Err(format!("formatting is not stable, it reformats:\n{}", formatted));
+    }
+    Ok(())
+}
+
+/// the calculator's name for an error, like `ERR:DIVIDE BY 0`, without the details after it
+fn error_name(error: &str) -> &str {
+    let end = [" (", " on line "].iter().filter_map(|detail| error.find(detail)).min().unwrap_or(error.len());
+    &error[..end]
+}
+
+/// run one fixture both ways, describing how they disagree if they do.
+/// it is compiled with and without small functions inlined, since either has to behave the same
+fn check_fixture(path: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    check_format(&source)?;
+    let mut m = analyze(path, source)?;
+    for &inline_threshold in [0, mir::DEFAULT_INLINE_THRESHOLD].iter() {
+        // how deep calls can go depends on how big their frames are, so each way of compiling gets run on its own
+        let mir = mir::make_all_mir(&mut m, inline_threshold).map_err(|error| error.get_readout(&m))?;
+        let evaluation = evaluator::evaluate(&m, &mir);
+        let text = compile(&m, &mir)?;
+        let simulation = simulator::simulate(&text).map_err(|error| error.get_readout(&m))?;
+
+        if evaluation.display != simulation.display {
+            return Err(format!(
+                "the evaluator showed:\n{}\nbut the compiled program (inline threshold {}) showed:\n{}\ncompiled program:\n{}",
+                evaluation.display.join("\n"), inline_threshold, simulation.display.join("\n"), text));
+        }
+        match (&evaluation.error, simulation.error) {
+            (None, None) => {}
+            (Some(error), Some(screen)) => {
+                if error_name(&error.why) != error_name(&screen) {
+                    return Err(format!("the evaluator stopped on {} but the compiled program stopped on {}\ncompiled program:\n{}", error.why, screen, text));
+                }
+            }
+            (Some(error), None) => return Err(format!("the evaluator stopped on an error that the compiled program didn't hit:\n{}", error.get_readout(&m))),
+            (None, Some(error)) => return Err(format!("the compiled program stopped on an error that the evaluator didn't hit:\n{}\ncompiled program:\n{}", error, text)),
+        }
+        // a program that ends on a value leaves it in Ans
+        let result = match evaluation.result {
+            Value::Num(x) => Some(simulator::format_number(x)),
+            Value::Boole(b) => Some(if b { "1" } else { "0" }.to_string()),
+            Value::Str(ref text) => Some(text.clone()),
+            _ => None,
+        };
+        if let Some(result) = result.filter(|_| evaluation.error.is_none()) {
+            if result != simulation.ans.to_string() {
+                return Err(format!("the evaluator ended on {} but the compiled program left {} in Ans\ncompiled program:\n{}", result, simulation.ans, text));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn fixtures_match_between_evaluator_and_simulator() {
+    let failures = fixtures()
+        .into_iter()
+        .filter_map(|path| {
+            // the evaluator needs more stack than a test gets
+            let checked = thread::Builder::new().stack_size(evaluator::STACK_SIZE).spawn(move || check_fixture(&path).map_err(|why| (path, why)));
+            checked.unwrap().join().unwrap().err().map(|(path, why)| format!("{}:\n{}", path.display(), why))
+        })
+        .collect::<Vec<String>>();
+    assert!(failures.is_empty(), "{} fixture(s) failed\n\n{}", failures.len(), failures.join("\n\n"));
+}
@@ -0,0 +1,252 @@
+use util::{Error, ErrorCode};
+
+/// the TI-83+/84+ token table: the bytes of each token and the text we spell it with
+/// the spellings match what the code generator emits, so `→`, `⌊`, `≠`, `≤`, `⁻` etc. are all single tokens
+pub const TOKENS: &[(&[u8], &str)] = &[
+    (&[0x01], "►DMS"), (&[0x02], "►Dec"), (&[0x03], "►Frac"), (&[0x04], "→"),
+    (&[0x05], "Boxplot"), (&[0x06], "["), (&[0x07], "]"), (&[0x08], "{"),
+    (&[0x09], "}"), (&[0x0A], "ʳ"), (&[0x0B], "°"), (&[0x0C], "⁻¹"),
+    (&[0x0D], "²"), (&[0x0E], "ᵀ"), (&[0x0F], "³"),
+    (&[0x10], "("), (&[0x11], ")"), (&[0x12], "round("), (&[0x13], "pxl-Test("),
+    (&[0x14], "augment("), (&[0x15], "rowSwap("), (&[0x16], "row+("), (&[0x17], "*row("),
+    (&[0x18], "*row+("), (&[0x19], "max("), (&[0x1A], "min("), (&[0x1B], "R►Pr("),
+    (&[0x1C], "R►Pθ("), (&[0x1D], "P►Rx("), (&[0x1E], "P►Ry("), (&[0x1F], "median("),
+    (&[0x20], "randM("), (&[0x21], "mean("), (&[0x22], "solve("), (&[0x23], "seq("),
+    (&[0x24], "fnInt("), (&[0x25], "nDeriv("), (&[0x27], "fMin("), (&[0x28], "fMax("),
+    (&[0x29], " "), (&[0x2A], "\""), (&[0x2B], ","), (&[0x2C], "𝑖"),
+    (&[0x2D], "!"), (&[0x2E], "CubicReg "), (&[0x2F], "QuartReg "),
+    (&[0x30], "0"), (&[0x31], "1"), (&[0x32], "2"), (&[0x33], "3"),
+    (&[0x34], "4"), (&[0x35], "5"), (&[0x36], "6"), (&[0x37], "7"),
+    (&[0x38], "8"), (&[0x39], "9"), (&[0x3A], "."), (&[0x3B], "ᴇ"),
+    (&[0x3C], " or "), (&[0x3D], " xor "), (&[0x3E], ":"), (&[0x3F], "\n"),
+    (&[0x40], " and "),
+    (&[0x41], "A"), (&[0x42], "B"), (&[0x43], "C"), (&[0x44], "D"),
+    (&[0x45], "E"), (&[0x46], "F"), (&[0x47], "G"), (&[0x48], "H"),
+    (&[0x49], "I"), (&[0x4A], "J"), (&[0x4B], "K"), (&[0x4C], "L"),
+    (&[0x4D], "M"), (&[0x4E], "N"), (&[0x4F], "O"), (&[0x50], "P"),
+    (&[0x51], "Q"), (&[0x52], "R"), (&[0x53], "S"), (&[0x54], "T"),
+    (&[0x55], "U"), (&[0x56], "V"), (&[0x57], "W"), (&[0x58], "X"),
+    (&[0x59], "Y"), (&[0x5A], "Z"), (&[0x5B], "θ"), (&[0x5F], "prgm"),
+    (&[0x64], "Radian"), (&[0x65], "Degree"), (&[0x66], "Normal"), (&[0x67], "Sci"),
+    (&[0x68], "Eng"), (&[0x69], "Float"), (&[0x6A], "="), (&[0x6B], "<"),
+    (&[0x6C], ">"), (&[0x6D], "≤"), (&[0x6E], "≥"), (&[0x6F], "≠"),
+    (&[0x70], "+"), (&[0x71], "-"), (&[0x72], "Ans"), (&[0x73], "Fix "),
+    (&[0x74], "Horiz"), (&[0x75], "Full"), (&[0x76], "Func"), (&[0x77], "Param"),
+    (&[0x78], "Polar"), (&[0x79], "Seq"), (&[0x7A], "IndpntAuto"), (&[0x7B], "IndpntAsk"),
+    (&[0x7C], "DependAuto"), (&[0x7D], "DependAsk"),
+    (&[0x82], "*"), (&[0x83], "/"), (&[0x84], "Trace"), (&[0x85], "ClrDraw"),
+    (&[0x86], "ZStandard"), (&[0x87], "ZTrig"), (&[0x88], "ZBox"), (&[0x89], "Zoom In"),
+    (&[0x8A], "Zoom Out"), (&[0x8B], "ZSquare"), (&[0x8C], "ZInteger"), (&[0x8D], "ZPrevious"),
+    (&[0x8E], "ZDecimal"), (&[0x8F], "ZoomStat"), (&[0x90], "ZoomRcl"), (&[0x91], "PrintScreen"),
+    (&[0x92], "ZoomSto"), (&[0x93], "Text("), (&[0x94], " nPr "), (&[0x95], " nCr "),
+    (&[0x96], "FnOn "), (&[0x97], "FnOff "), (&[0x98], "StorePic "), (&[0x99], "RecallPic "),
+    (&[0x9A], "StoreGDB "), (&[0x9B], "RecallGDB "), (&[0x9C], "Line("), (&[0x9D], "Vertical "),
+    (&[0x9E], "Pt-On("), (&[0x9F], "Pt-Off("), (&[0xA0], "Pt-Change("), (&[0xA1], "Pxl-On("),
+    (&[0xA2], "Pxl-Off("), (&[0xA3], "Pxl-Change("), (&[0xA4], "Shade("), (&[0xA5], "Circle("),
+    (&[0xA6], "Horizontal "), (&[0xA7], "Tangent("), (&[0xA8], "DrawInv "), (&[0xA9], "DrawF "),
+    (&[0xAB], "rand"), (&[0xAC], "π"), (&[0xAD], "getKey"), (&[0xAE], "'"),
+    (&[0xAF], "?"), (&[0xB0], "⁻"), (&[0xB1], "int("), (&[0xB2], "abs("),
+    (&[0xB3], "det("), (&[0xB4], "identity("), (&[0xB5], "dim("), (&[0xB6], "sum("),
+    (&[0xB7], "prod("), (&[0xB8], "not("), (&[0xB9], "iPart("), (&[0xBA], "fPart("),
+    (&[0xBC], "√("), (&[0xBD], "³√("), (&[0xBE], "ln("), (&[0xBF], "ℯ^("),
+    (&[0xC0], "log("), (&[0xC1], "₁₀^("), (&[0xC2], "sin("), (&[0xC3], "sin⁻¹("),
+    (&[0xC4], "cos("), (&[0xC5], "cos⁻¹("), (&[0xC6], "tan("), (&[0xC7], "tan⁻¹("),
+    (&[0xC8], "sinh("), (&[0xC9], "sinh⁻¹("), (&[0xCA], "cosh("), (&[0xCB], "cosh⁻¹("),
+    (&[0xCC], "tanh("), (&[0xCD], "tanh⁻¹("), (&[0xCE], "If "), (&[0xCF], "Then"),
+    (&[0xD0], "Else"), (&[0xD1], "While "), (&[0xD2], "Repeat "), (&[0xD3], "For("),
+    (&[0xD4], "End"), (&[0xD5], "Return"), (&[0xD6], "Lbl "), (&[0xD7], "Goto "),
+    (&[0xD8], "Pause "), (&[0xD9], "Stop"), (&[0xDA], "IS>("), (&[0xDB], "DS<("),
+    (&[0xDC], "Input "), (&[0xDD], "Prompt "), (&[0xDE], "Disp "), (&[0xDF], "DispGraph"),
+    (&[0xE0], "Output("), (&[0xE1], "ClrHome"), (&[0xE2], "Fill("), (&[0xE3], "SortA("),
+    (&[0xE4], "SortD("), (&[0xE5], "DispTable"), (&[0xE6], "Menu("), (&[0xE7], "Send("),
+    (&[0xE8], "Get("), (&[0xE9], "PlotsOn "), (&[0xEA], "PlotsOff "), (&[0xEB], "⌊"),
+    (&[0xEC], "Plot1("), (&[0xED], "Plot2("), (&[0xEE], "Plot3("),
+    (&[0xF0], "^"), (&[0xF1], "×√"), (&[0xF2], "1-Var Stats "), (&[0xF3], "2-Var Stats "),
+    (&[0xF4], "LinReg(a+bx) "), (&[0xF5], "ExpReg "), (&[0xF6], "LnReg "), (&[0xF7], "PwrReg "),
+    (&[0xF8], "Med-Med "), (&[0xF9], "QuadReg "), (&[0xFA], "ClrList "), (&[0xFB], "ClrTable"),
+    (&[0xFC], "Histogram"), (&[0xFD], "xyLine"), (&[0xFE], "Scatter"), (&[0xFF], "LinReg(ax+b) "),
+    // matrices
+    (&[0x5C, 0x00], "[A]"), (&[0x5C, 0x01], "[B]"), (&[0x5C, 0x02], "[C]"), (&[0x5C, 0x03], "[D]"),
+    (&[0x5C, 0x04], "[E]"), (&[0x5C, 0x05], "[F]"), (&[0x5C, 0x06], "[G]"), (&[0x5C, 0x07], "[H]"),
+    (&[0x5C, 0x08], "[I]"), (&[0x5C, 0x09], "[J]"),
+    // built in lists
+    (&[0x5D, 0x00], "L₁"), (&[0x5D, 0x01], "L₂"), (&[0x5D, 0x02], "L₃"),
+    (&[0x5D, 0x03], "L₄"), (&[0x5D, 0x04], "L₅"), (&[0x5D, 0x05], "L₆"),
+    // function equations
+    (&[0x5E, 0x10], "Y₁"), (&[0x5E, 0x11], "Y₂"), (&[0x5E, 0x12], "Y₃"), (&[0x5E, 0x13], "Y₄"),
+    (&[0x5E, 0x14], "Y₅"), (&[0x5E, 0x15], "Y₆"), (&[0x5E, 0x16], "Y₇"), (&[0x5E, 0x17], "Y₈"),
+    (&[0x5E, 0x18], "Y₉"), (&[0x5E, 0x19], "Y₀"),
+    // pictures and graph databases
+    (&[0x60, 0x00], "Pic1"), (&[0x60, 0x01], "Pic2"), (&[0x60, 0x02], "Pic3"), (&[0x60, 0x03], "Pic4"),
+    (&[0x60, 0x04], "Pic5"), (&[0x60, 0x05], "Pic6"), (&[0x60, 0x06], "Pic7"), (&[0x60, 0x07], "Pic8"),
+    (&[0x60, 0x08], "Pic9"), (&[0x60, 0x09], "Pic0"),
+    (&[0x61, 0x00], "GDB1"), (&[0x61, 0x01], "GDB2"), (&[0x61, 0x02], "GDB3"), (&[0x61, 0x03], "GDB4"),
+    (&[0x61, 0x04], "GDB5"), (&[0x61, 0x05], "GDB6"), (&[0x61, 0x06], "GDB7"), (&[0x61, 0x07], "GDB8"),
+    (&[0x61, 0x08], "GDB9"), (&[0x61, 0x09], "GDB0"),
+    // window variables
+    (&[0x63, 0x0A], "Xmin"), (&[0x63, 0x0B], "Xmax"), (&[0x63, 0x0C], "Ymin"), (&[0x63, 0x0D], "Ymax"),
+    (&[0x63, 0x02], "Xscl"), (&[0x63, 0x03], "Yscl"),
+    // strings
+    (&[0xAA, 0x00], "Str1"), (&[0xAA, 0x01], "Str2"), (&[0xAA, 0x02], "Str3"), (&[0xAA, 0x03], "Str4"),
+    (&[0xAA, 0x04], "Str5"), (&[0xAA, 0x05], "Str6"), (&[0xAA, 0x06], "Str7"), (&[0xAA, 0x07], "Str8"),
+    (&[0xAA, 0x08], "Str9"), (&[0xAA, 0x09], "Str0"),
+    // the second page of functions
+    (&[0xBB, 0x00], "npv("), (&[0xBB, 0x01], "irr("), (&[0xBB, 0x02], "bal("), (&[0xBB, 0x03], "ΣPrn("),
+    (&[0xBB, 0x04], "ΣInt("), (&[0xBB, 0x05], "►Nom("), (&[0xBB, 0x06], "►Eff("), (&[0xBB, 0x07], "dbd("),
+    (&[0xBB, 0x08], "lcm("), (&[0xBB, 0x09], "gcd("), (&[0xBB, 0x0A], "randInt("), (&[0xBB, 0x0B], "randBin("),
+    (&[0xBB, 0x0C], "sub("), (&[0xBB, 0x0D], "stdDev("), (&[0xBB, 0x0E], "variance("), (&[0xBB, 0x0F], "inString("),
+    (&[0xBB, 0x10], "normalcdf("), (&[0xBB, 0x11], "invNorm("), (&[0xBB, 0x12], "tcdf("), (&[0xBB, 0x13], "χ²cdf("),
+    (&[0xBB, 0x14], "Fcdf("), (&[0xBB, 0x15], "binompdf("), (&[0xBB, 0x16], "binomcdf("), (&[0xBB, 0x17], "poissonpdf("),
+    (&[0xBB, 0x18], "poissoncdf("), (&[0xBB, 0x19], "geometpdf("), (&[0xBB, 0x1A], "geometcdf("), (&[0xBB, 0x1B], "normalpdf("),
+    (&[0xBB, 0x1C], "tpdf("), (&[0xBB, 0x1D], "χ²pdf("), (&[0xBB, 0x1E], "Fpdf("), (&[0xBB, 0x1F], "randNorm("),
+    (&[0xBB, 0x25], "conj("), (&[0xBB, 0x26], "real("), (&[0xBB, 0x27], "imag("), (&[0xBB, 0x28], "angle("),
+    (&[0xBB, 0x29], "cumSum("), (&[0xBB, 0x2A], "expr("), (&[0xBB, 0x2B], "length("), (&[0xBB, 0x2C], "ΔList("),
+    (&[0xBB, 0x2D], "ref("), (&[0xBB, 0x2E], "rref("), (&[0xBB, 0x2F], "►Rect"), (&[0xBB, 0x30], "►Polar"),
+    (&[0xBB, 0x31], "ℯ"), (&[0xBB, 0x4D], "Real"), (&[0xBB, 0x4E], "re^θ𝑖"), (&[0xBB, 0x4F], "a+b𝑖"),
+    (&[0xBB, 0x50], "ExprOn"), (&[0xBB, 0x51], "ExprOff"), (&[0xBB, 0x52], "ClrAllLists"), (&[0xBB, 0x53], "GetCalc("),
+    (&[0xBB, 0x54], "DelVar "), (&[0xBB, 0x55], "Equ►String("), (&[0xBB, 0x56], "String►Equ("), (&[0xBB, 0x57], "Clear Entries"),
+    (&[0xBB, 0x58], "Select("), (&[0xBB, 0x66], "DiagnosticOn"), (&[0xBB, 0x67], "DiagnosticOff"), (&[0xBB, 0x68], "Archive "),
+    (&[0xBB, 0x69], "UnArchive "), (&[0xBB, 0x6A], "Asm("), (&[0xBB, 0x6B], "AsmComp("), (&[0xBB, 0x6C], "AsmPrgm"),
+    // lowercase letters (0xBBBB is skipped)
+    (&[0xBB, 0xB0], "a"), (&[0xBB, 0xB1], "b"), (&[0xBB, 0xB2], "c"), (&[0xBB, 0xB3], "d"),
+    (&[0xBB, 0xB4], "e"), (&[0xBB, 0xB5], "f"), (&[0xBB, 0xB6], "g"), (&[0xBB, 0xB7], "h"),
+    (&[0xBB, 0xB8], "i"), (&[0xBB, 0xB9], "j"), (&[0xBB, 0xBA], "k"), (&[0xBB, 0xBC], "l"),
+    (&[0xBB, 0xBD], "m"), (&[0xBB, 0xBE], "n"), (&[0xBB, 0xBF], "o"), (&[0xBB, 0xC0], "p"),
+    (&[0xBB, 0xC1], "q"), (&[0xBB, 0xC2], "r"), (&[0xBB, 0xC3], "s"), (&[0xBB, 0xC4], "t"),
+    (&[0xBB, 0xC5], "u"), (&[0xBB, 0xC6], "v"), (&[0xBB, 0xC7], "w"), (&[0xBB, 0xC8], "x"),
+    (&[0xBB, 0xC9], "y"), (&[0xBB, 0xCA], "z"),
+    // tokens added by the 84+
+    (&[0xEF, 0x00], "setDate("), (&[0xEF, 0x01], "setTime("), (&[0xEF, 0x02], "checkTmr("), (&[0xEF, 0x09], "getDate"),
+    (&[0xEF, 0x0A], "getTime"), (&[0xEF, 0x0B], "startTmr"), (&[0xEF, 0x32], "remainder("),
+];
+
+/// split TI-84 Basic source text into the calculator's tokens
+/// always takes the longest token that matches, the same as the calculator's own editor does
+pub fn split_basic(text: &str) -> Result<Vec<&'static (&'static [u8], &'static str)>, Error> {
+    let mut tokens = vec![];
+    let mut rest = text;
+    let mut line = 1;
+    while !rest.is_empty() {
+        let token = longest_token(rest)
+            .ok_or_else(|| Error::new_zero(ErrorCode::NoCalculatorToken, format!(
+                "no calculator token for `{}` on line {} of the program",
+                rest.chars().next().unwrap(),
+                line)))?;
+        if token.1 == "\n" {
+            line += 1;
+        }
+        tokens.push(token);
+        rest = &rest[token.1.len()..];
+    }
+    Ok(tokens)
+}
+
+/// the longest token that the text starts with
+fn longest_token(text: &str) -> Option<&'static (&'static [u8], &'static str)> {
+    TOKENS
+        .iter()
+        .filter(|&&(_, spelling)| text.starts_with(spelling))
+        .max_by_key(|&&(_, spelling)| spelling.len())
+}
+
+/// the first character that no token can be found for when splitting the text, if there is one
+pub fn untokenizable(text: &str) -> Option<char> {
+    let mut rest = text;
+    while let Some(first) = rest.chars().next() {
+        match longest_token(rest) {
+            Some(&(_, spelling)) => rest = &rest[spelling.len()..],
+            None => return Some(first),
+        }
+    }
+    None
+}
+
+/// convert TI-84 Basic source text into the calculator's tokens
+pub fn tokenize_basic(text: &str) -> Result<Vec<u8>, Error> {
+    Ok(split_basic(text)?
+        .into_iter()
+        .flat_map(|&(token_bytes, _)| token_bytes.iter().cloned())
+        .collect())
+}
+
+/// the bytes that begin a two byte token
+fn is_prefix(byte: u8) -> bool {
+    matches!(byte, 0x5C | 0x5D | 0x5E | 0x60 | 0x61 | 0x62 | 0x63 | 0x7E | 0xAA | 0xBB | 0xEF)
+}
+
+/// convert the calculator's tokens back into TI-84 Basic source text, spelled the same way `tokenize_basic` reads it
+pub fn detokenize_basic(bytes: &[u8]) -> Result<String, Error> {
+    let mut text = String::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let width = if is_prefix(bytes[index]) { 2 } else { 1 };
+        let token_bytes = bytes.get(index..index + width).ok_or_else(|| Error::new_zero(ErrorCode::Malformed8xp, format!(
+            "the program ends in the middle of a two byte token (0x{:02X})", bytes[index])))?;
+        let &(_, spelling) = TOKENS
+            .iter()
+            .find(|&&(candidate, _)| candidate == token_bytes)
+            .ok_or_else(|| Error::new_zero(ErrorCode::Malformed8xp, format!(
+                "unknown token {} at byte {}",
+                token_bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect::<Vec<String>>().join(" "),
+                index)))?;
+        text.push_str(spelling);
+        index += width;
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the spelling of each token the text splits into
+    fn split(text: &str) -> Vec<&'static str> {
+        split_basic(text).unwrap_or_else(|error| panic!("{}", error.why)).iter().map(|&&(_, spelling)| spelling).collect()
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        // not the letters n, o and t followed by a parenthesis
+        assert_eq!(split("not(A)"), vec!["not(", "A", ")"]);
+        // not the constant ℯ raised to something in parentheses
+        assert_eq!(split("ℯ^(2)"), vec!["ℯ^(", "2", ")"]);
+        assert_eq!(split("ℯ^2"), vec!["ℯ", "^", "2"]);
+        // the negative sign next to a digit, as opposed to the inverse
+        assert_eq!(split("⁻1"), vec!["⁻", "1"]);
+        assert_eq!(split("A⁻¹"), vec!["A", "⁻¹"]);
+        assert_eq!(split("sin⁻¹(⁻1)"), vec!["sin⁻¹(", "⁻", "1", ")"]);
+        assert_eq!(split("1→A"), vec!["1", "→", "A"]);
+        assert_eq!(tokenize_basic("⁻1→A").unwrap(), vec![0xB0, 0x31, 0x04, 0x41]);
+    }
+
+    #[test]
+    fn two_byte_tokens() {
+        // a list's name is a run of single byte letters after ⌊
+        assert_eq!(tokenize_basic("dim(⌊ARGS").unwrap(), vec![0xB5, 0xEB, 0x41, 0x52, 0x47, 0x53]);
+        assert_eq!(tokenize_basic("length(Str0").unwrap(), vec![0xBB, 0x2B, 0xAA, 0x09]);
+        assert_eq!(tokenize_basic("remainder(").unwrap(), vec![0xEF, 0x32]);
+        assert_eq!(detokenize_basic(&[0xAA, 0x09, 0x04, 0xAA, 0x00]).unwrap(), "Str0→Str1");
+    }
+
+    #[test]
+    fn round_trips() {
+        let text = "\"ABsin(C\"→Str0\nIf ⌊ARGS(dim(⌊ARGS))≠2\nDisp sub(Str0,1,ℯ^(0))\n⁻1.5ᴇ⁻3→θ";
+        assert_eq!(detokenize_basic(&tokenize_basic(text).unwrap()).unwrap(), text);
+    }
+
+    #[test]
+    fn no_token_for_a_character() {
+        let error = split_basic("Disp 1\nDisp \"a@b\"").unwrap_err();
+        assert_eq!(error.code, ErrorCode::NoCalculatorToken);
+        assert_eq!(error.why, "no calculator token for `@` on line 2 of the program");
+        assert_eq!(untokenizable("a@b~"), Some('@'));
+        assert_eq!(untokenizable("Hello, world!"), None);
+    }
+
+    #[test]
+    fn bytes_that_are_not_tokens() {
+        // a two byte token cut off at the end
+        assert_eq!(detokenize_basic(&[0x41, 0xBB]).unwrap_err().code, ErrorCode::Malformed8xp);
+        assert_eq!(detokenize_basic(&[0xBB, 0xFF]).unwrap_err().why, "unknown token 0xBB 0xFF at byte 0");
+    }
+}
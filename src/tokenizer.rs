@@ -1,104 +1,221 @@
+use util::{Error, ErrorCode};
+use number::Number;
 use std::vec::Vec;
 
+/// what sort of thing a token is, decided once by the lexer
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Colon,
+    Arrow,
+    String,
+    Int,
+    Real,
+    Bool,
+    Ident,
+    /// only handed out by `tokenize_keeping_comments`
+    Comment,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Token {
     begin: usize,
     len: usize,
+    pub kind: TokenKind,
 }
 
 impl Token {
-    pub fn new(begin: usize, len: usize) -> Token {
-        Token{begin, len}
+    pub fn new(begin: usize, len: usize, kind: TokenKind) -> Token {
+        Token{begin, len, kind}
+    }
+    /// where this token starts in the source
+    pub fn begin(&self) -> usize {
+        self.begin
     }
-    pub fn get_text<'a, 'b>(&'a self, source: &'b str) -> &'b str {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn get_text<'b>(&self, source: &'b str) -> &'b str {
         &source[self.begin..self.begin+self.len]
     }
-    pub fn get_underlined(&self, source: &str) -> String {
-        let mut line_start = self.begin;
-        while line_start > 0 && &source[line_start - 1..line_start] != "\n" {
-            line_start -= 1;
-        }
-        let mut line_end = self.begin+self.len;
-        while line_end < source.len() && &source[line_end..line_end+1] != "\n" {
-            line_end += 1;
-        }
-        let mut string = String::from(&source[line_start..line_end]);
-        string.push('\n');
-        for _ in line_start..self.begin {
-            string.push(' ');
-        }
-        for _ in 0..self.len {
-            string.push('^');
-        }
-        string
+    /// the line and column (both counted from 1) that this token starts at
+    pub fn position(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.begin];
+        let line = before.matches('\n').count() + 1;
+        let col = before[before.rfind('\n').map_or(0, |newline| newline + 1)..].chars().count() + 1;
+        (line, col)
     }
 }
 
-pub fn tokenize(source: &str) -> Vec<Token> {
-    // TODO the program "(" doesn't generate any tokens
+/// characters that end whatever word comes before them
+fn is_delimiter(ch: char) -> bool {
+    ch.is_whitespace() || "(){}[]:\";".contains(ch)
+}
 
+/// decide what a word (anything between delimiters) is
+fn classify_word(word: &str) -> Result<TokenKind, (ErrorCode, String)> {
+    if word == "->" {
+        return Ok(TokenKind::Arrow);
+    }
+    if word == "true" || word == "false" {
+        return Ok(TokenKind::Bool);
+    }
+    let unsigned = word.strip_prefix('-').unwrap_or(word);
+    if unsigned.starts_with(|ch: char| ch.is_ascii_digit() || ch == '.') {
+        let number = Number::parse(word)?;
+        return Ok(if number.is_int { TokenKind::Int } else { TokenKind::Real });
+    }
+    // identifiers can have letters, digits, dashes, underscores and angle brackets, but can't start with a digit.
+    // commas can separate what is between angle brackets
+    if word.chars().all(|ch| ch == '-' || ch == '_' || ch == '<' || ch == '>' || ch.is_alphanumeric() || (ch == ',' && word.contains('<'))) {
+        return Ok(TokenKind::Ident);
+    }
+    Err((ErrorCode::UnrecognizedAtom, format!("`{}` is not a recognized keyword, literal, or identifier", word)))
+}
+
+fn lex(source: &str, keep_comments: bool) -> Result<Vec<Token>, Vec<Error>> {
     let mut tokens = vec![];
+    let mut errors = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some((begin, ch)) = chars.next() {
+        // everything until `end` belongs to this token
+        let mut end = begin + ch.len_utf8();
+        let kind = match ch {
+            _ if ch.is_whitespace() => continue,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            ':' => TokenKind::Colon,
+            ';' => {
+                // a line comment runs up to the end of the line
+                while let Some(&(at, next)) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    end = at + next.len_utf8();
+                    chars.next();
+                }
+                TokenKind::Comment
+            }
+            '#' if source[end..].starts_with('|') => {
+                // a block comment runs up to its closing |#
+                match source[end..].find("|#") {
+                    Some(close) => end += close + 2,
+                    None => {
+                        errors.push(Error::new(ErrorCode::UnterminatedComment, "unterminated block comment".to_string(), Token::new(begin, 2, TokenKind::Comment)));
+                        end = source.len();
+                    }
+                }
+                while chars.peek().is_some_and(|&(at, _)| at < end) {
+                    chars.next();
+                }
+                TokenKind::Comment
+            }
+            '"' => {
+                // TI-84 basic does not have escape characters in strings, so the string runs up to the next quote
+                match chars.find(|&(_, next)| next == '"') {
+                    Some((at, _)) => end = at + 1,
+                    None => {
+                        errors.push(Error::new(ErrorCode::UnterminatedString, "unterminated string".to_string(), Token::new(begin, 1, TokenKind::String)));
+                        break;
+                    }
+                }
+                TokenKind::String
+            }
+            _ => {
+                while let Some(&(at, next)) = chars.peek() {
+                    if is_delimiter(next) || source[at..].starts_with("#|") {
+                        break;
+                    }
+                    end = at + next.len_utf8();
+                    chars.next();
+                }
+                match classify_word(&source[begin..end]) {
+                    Ok(kind) => kind,
+                    Err((code, why)) => {
+                        errors.push(Error::new(code, why, Token::new(begin, end - begin, TokenKind::Ident)));
+                        continue;
+                    }
+                }
+            }
+        };
+        if kind != TokenKind::Comment || keep_comments {
+            tokens.push(Token::new(begin, end - begin, kind));
+        }
+    }
+    if errors.is_empty() { Ok(tokens) } else { Err(errors) }
+}
 
-    if source.len() == 0 {
-        return tokens;
+/// split the source into tokens, skipping comments
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<Error>> {
+    lex(source, false)
+}
+
+/// split the source into tokens, keeping each comment as a token of its own
+pub fn tokenize_keeping_comments(source: &str) -> Result<Vec<Token>, Vec<Error>> {
+    lex(source, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// each token's kind and text
+    fn lexed(source: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(source).unwrap_or_else(|errors| panic!("{:?}", errors)).iter().map(|token| (token.kind, token.get_text(source))).collect()
     }
 
-    let mut token_begin = 0;
-    let mut token_len = 0;
-    let mut i = 0;
-    let mut not_first = false;
-    while i < source.len() - 1 {
-        if not_first {
-            i += 1;
-        } else {
-            not_first = true;
-        }
+    /// the code and span of each error
+    fn lex_errors(source: &str) -> Vec<(&'static str, usize, usize)> {
+        tokenize(source).expect_err("should not lex").iter().map(|error| (error.code.code(), error.ats[0].begin(), error.ats[0].len())).collect()
+    }
 
-        let ch = source[i..i+1].chars().nth(0).unwrap();
-        if token_len == 0 {
-            token_begin = i;
-        }
+    #[test]
+    fn lone_paren() {
+        assert_eq!(lexed("("), vec![(TokenKind::LParen, "(")]);
+    }
 
-        // whitespace means end the current token
-        if ch.is_whitespace() {
-            if token_len != 0 {
-                tokens.push(Token::new(token_begin, token_len));
-                token_begin = 0;
-                token_len = 0;
-            }
-            continue;
-        }
+    #[test]
+    fn token_on_the_last_character() {
+        assert_eq!(lexed("(print x"), vec![(TokenKind::LParen, "("), (TokenKind::Ident, "print"), (TokenKind::Ident, "x")]);
+        assert_eq!(lexed("1.5"), vec![(TokenKind::Real, "1.5")]);
+    }
 
-        // enclose strings
-        if token_len == 0 && ch == '"' {
-            let quote_begin = i;
-            i += 1;
-            while i < source.len() && &source[i..i+1] != "\"" {
-                // TI-84 basic does not have escape characters in strings
-                /*if &source[i..i+1] == "\\" {
-                    i += 1;
-                }*/
-                i += 1;
-            }
-            let quote_len = i + 1 - quote_begin;
-            tokens.push(Token::new(quote_begin, quote_len));
-            continue;
-        }
+    #[test]
+    fn spans_after_multi_byte_characters() {
+        let source = "\"θ→\" θx 12";
+        let tokens = tokenize(source).unwrap();
+        assert_eq!(tokens.iter().map(|token| (token.kind, token.begin(), token.len())).collect::<Vec<_>>(), vec![
+            (TokenKind::String, 0, 7),
+            (TokenKind::Ident, 8, 3),
+            (TokenKind::Int, 12, 2),
+        ]);
+        assert_eq!(tokens[2].position(source), (1, 9));
+    }
 
-        // singular symbols that are tokens in and of themselves
-        if ch == '{' || ch == '}' || ch == '[' || ch == ']' || ch == '(' || ch == ')' || ch == ':' {
-            if token_len != 0 {
-                tokens.push(Token::new(token_begin, token_len));
-                token_begin = 0;
-                token_len = 0;
-            }
-            tokens.push(Token::new(i, 1));
-            continue;
-        }
-        token_len += 1;
-        if token_len == 1 {
-            token_begin = i;
-        }
+    #[test]
+    fn comments_are_kept_only_when_asked() {
+        let source = "#| a\nb |# x ; y\nz";
+        assert_eq!(lexed(source), vec![(TokenKind::Ident, "x"), (TokenKind::Ident, "z")]);
+        let kinds = tokenize_keeping_comments(source).unwrap().iter().map(|token| token.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![TokenKind::Comment, TokenKind::Ident, TokenKind::Comment, TokenKind::Ident]);
     }
-    tokens
-}
\ No newline at end of file
+
+    #[test]
+    fn unterminated_string() {
+        assert_eq!(lex_errors("(print \"abc)"), vec![("E0015", 7, 1)]);
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        assert_eq!(lex_errors("x #| never closed"), vec![("E0016", 2, 2)]);
+    }
+}
@@ -1,25 +1,51 @@
 use tokenizer::Token;
-use sexpr::{SexprId, SexprKind};
+use sexpr::{SexprId, SexprKind, MapTarget, MatchArm};
 use manager::Manager;
-use util::Error;
+use scope::ScopeId;
+use ti_tokens;
+use util::{Error, ErrorCode};
+
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Type {
     String,
     Int,
     Real,
     Boole,
     Void,
-  //  Generic(&'static str), //TODO self-generic types
+    /// a type parameter of a generic function, by name
+    Generic(String),
     List(Box<Type>),
+    /// a function value: what it takes, then what it gives back
+    Func(Vec<Type>, Box<Type>),
     CustomType(String, usize), // the name of the type (for display purposes) and our id
 }
 
 impl Type {
-    pub fn from_text(source: &str, token: Token, type_names: &Vec<String>) -> Result<Type, Error> {
-        Type::from_text_primitive(token.get_text(source))
-            .or(Manager::lookup_user_def_type(token.get_text(source), type_names))
-            .ok_or(Error::new(format!("{} is not a recognized type", token.get_text(source)), token))
+    /// `generics` are the type parameters that can be used where the type is written
+    pub fn from_text(source: &str, token: Token, type_names: &Vec<String>, generics: &[String]) -> Result<Type, Error> {
+        Type::parse(token.get_text(source), type_names, generics)
+            .ok_or(Error::new(ErrorCode::UnknownType, format!("{} is not a recognized type", token.get_text(source)), token))
+    }
+    /// any type, including user defined ones, type parameters, function types, and lists of any of them
+    pub fn parse(name: &str, type_names: &Vec<String>, generics: &[String]) -> Option<Type> {
+        if let Some(inner) = name.strip_prefix("list<").and_then(|rest| rest.strip_suffix('>')) {
+            return Some(Type::List(Box::new(Type::parse(inner, type_names, generics)?)));
+        }
+        if let Some(inner) = name.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+            // `(-> arg ... out)`, where each part can be a function type of its own
+            let mut parts = split_type_parts(inner.trim_start().strip_prefix("->")?)
+                .into_iter()
+                .map(|part| Type::parse(part, type_names, generics))
+                .collect::<Option<Vec<Type>>>()?;
+            let out = parts.pop()?;
+            return Some(Type::Func(parts, Box::new(out)));
+        }
+        Type::from_text_primitive(name)
+            .or_else(|| generics.iter().find(|generic| *generic == name).map(|generic| Type::Generic(generic.to_string())))
+            .or_else(|| Manager::lookup_user_def_type(name, type_names))
     }
     pub fn from_text_primitive(name: &str) -> Option<Type> {
         Some(match name {
@@ -39,18 +65,13 @@ impl Type {
         })
     }
 
-
-    pub fn to_string(&self) -> String {
-        format!("{}", self)
-    }
-
     pub fn is_not(type0: &Type, type1: &Type) -> bool {
         match type0 {
-            &Type::String => { if let &Type::String   = type1 { false } else { true }},
-            &Type::Int    => { if let &Type::Int      = type1 { false } else { true }},
-            &Type::Real   => { if let &Type::Real     = type1 { false } else { true }},
-            &Type::Boole  => { if let &Type::Boole    = type1 { false } else { true }},
-            &Type::Void   => { if let &Type::Void     = type1 { false } else { true }},
+            &Type::String => !matches!(type1, Type::String),
+            &Type::Int    => !matches!(type1, Type::Int),
+            &Type::Real   => !matches!(type1, Type::Real),
+            &Type::Boole  => !matches!(type1, Type::Boole),
+            &Type::Void   => !matches!(type1, Type::Void),
             &Type::CustomType(_, id0) => {
                 if let &Type::CustomType(_, id1) = type1 {
                     id0 != id1
@@ -58,7 +79,64 @@ impl Type {
                     true
                 }
             }
-            &Type::List(ref t0) => { if let &Type::List(ref t1) = type1 { Type::is_not(t0, t1) } else { true }}
+            Type::List(t0) => { if let Type::List(t1) = type1 { Type::is_not(t0, t1) } else { true }}
+            Type::Func(args0, out0) => {
+                if let Type::Func(args1, out1) = type1 {
+                    !Type::check_signature(args0, args1) || Type::is_not(out0, out1)
+                } else {
+                    true
+                }
+            }
+            Type::Generic(name0) => { if let Type::Generic(name1) = type1 { name0 != name1 } else { true }}
+        }
+    }
+
+    pub fn is_generic(&self) -> bool {
+        match *self {
+            Type::Generic(_) => true,
+            Type::List(ref t) => t.is_generic(),
+            Type::Func(ref args, ref out) => args.iter().any(Type::is_generic) || out.is_generic(),
+            _ => false,
+        }
+    }
+    /// match a concrete type against one that may have type parameters in it,
+    /// binding the parameters to what they stand for here
+    fn bind(generic: &Type, typ: &Type, bindings: &mut HashMap<String, Type>) -> bool {
+        match (generic, typ) {
+            (Type::Generic(name), _) => match bindings.get(name) {
+                Some(bound) => !Type::is_not(bound, typ),
+                None => {
+                    bindings.insert(name.to_string(), typ.clone());
+                    true
+                }
+            },
+            (Type::List(generic), Type::List(typ)) => Type::bind(generic, typ, bindings),
+            (Type::Func(generic_args, generic_out), Type::Func(args, out)) if generic_args.len() == args.len() => {
+                generic_args.iter().zip(args.iter()).all(|(generic, typ)| Type::bind(generic, typ, bindings)) && Type::bind(generic_out, out, bindings)
+            }
+            _ => !Type::is_not(generic, typ),
+        }
+    }
+    /// what each type parameter stands for when a generic signature is called with these types, if it can be
+    pub fn bind_generics(signature: &[FutureType], types: &[Type]) -> Option<HashMap<String, Type>> {
+        if signature.len() != types.len() {
+            return None;
+        }
+        let mut bindings = HashMap::new();
+        for (generic, typ) in signature.iter().map(FutureType::unwrap).zip(types.iter()) {
+            if !Type::bind(generic, typ, &mut bindings) {
+                return None;
+            }
+        }
+        Some(bindings)
+    }
+    /// replace the type parameters with what they are bound to
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        match *self {
+            Type::Generic(ref name) => bindings.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::List(ref t) => Type::List(Box::new(t.substitute(bindings))),
+            Type::Func(ref args, ref out) => Type::Func(args.iter().map(|arg| arg.substitute(bindings)).collect(), Box::new(out.substitute(bindings))),
+            _ => self.clone(),
         }
     }
 
@@ -107,28 +185,75 @@ impl ::std::fmt::Display for Type {
             Type::Void              => write!(f, "void"),
             Type::CustomType(ref n, _)  => write!(f, "{}", n),
             Type::List(ref cont)    => write!(f, "list<{}>", cont),
+            Type::Generic(ref name) => write!(f, "{}", name),
+            Type::Func(ref args, ref out) => {
+                write!(f, "(->")?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, " {})", out)
+            }
+        }
+    }
+}
+
+/// the parts of a function type between its `->` and its closing parenthesis,
+/// keeping any function types among them whole
+fn split_type_parts(text: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0usize;
+    let mut start = None;
+    for (at, ch) in text.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ if ch.is_whitespace() && depth == 0 => {
+                if let Some(begin) = start.take() {
+                    parts.push(&text[begin..at]);
+                }
+                continue;
+            }
+            _ => {}
         }
+        start.get_or_insert(at);
     }
+    if let Some(begin) = start {
+        parts.push(&text[begin..]);
+    }
+    parts
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FutureType {
     Proto(Token),
     Complete(Type),
+    /// a type that was left out, to be inferred. the token is what it is the type of
+    Infer(Token),
 }
 impl FutureType {
     pub fn new(token: Token) -> FutureType {
         FutureType::Proto(token)
     }
-    pub fn upgrade(&mut self, source: &str, type_names: &Vec<String>) -> Result<(), Error> {
+    pub fn upgrade(&mut self, source: &str, type_names: &Vec<String>, generics: &[String]) -> Result<(), Error> {
         *self =
             if let FutureType::Proto(token) = *self {
-                FutureType::Complete(Type::from_text(source, token, type_names)?)
+                FutureType::Complete(Type::from_text(source, token, type_names, generics)?)
             } else {
                 return Ok(())
             };
         Ok(())
     }
+    /// the type, if it is known by now
+    pub fn known(&self) -> Option<&Type> {
+        if let FutureType::Complete(ref typ) = self { Some(typ) } else { None }
+    }
+    /// where the type was written, or where it was left out
+    pub fn token(&self) -> Token {
+        match *self {
+            FutureType::Proto(token) | FutureType::Infer(token) => token,
+            FutureType::Complete(_) => panic!("a complete FutureType has no token"),
+        }
+    }
     pub fn unwrap(&self) -> &Type {
         if let FutureType::Complete(ref typ) = self {
             typ
@@ -147,6 +272,25 @@ impl FutureType {
  */
 
 impl Manager {
+    /// every signature that something named `name` can be called with, for when a call matches none of them
+    fn candidate_signatures(&self, name: &str) -> Vec<String> {
+        let show = |types: Vec<&Type>| format!("({})", types.iter().map(|typ| typ.to_string()).collect::<Vec<String>>().join(" "));
+        let funcs = (0..self.func_manager.name.len())
+            .filter(|&id| *self.func_manager.name[id] == *name)
+            .map(|id| show(self.func_manager.in_types[id].iter().map(FutureType::unwrap).collect()));
+        let structs = (0..self.udt_manager.name.len())
+            .filter(|&id| *self.udt_manager.name[id] == *name && !self.udt_manager.is_enum(id))
+            .map(|id| show(self.udt_manager.sgntr[id].iter().map(FutureType::unwrap).collect()));
+        let variants = self.udt_manager.variants
+            .iter()
+            .flatten()
+            .filter(|variant| variant.name == name)
+            .map(|variant| show(variant.sgntr.iter().map(FutureType::unwrap).collect()));
+        let builtins = (0..self.builtin_manager.name.len())
+            .filter(|&id| self.builtin_manager.name[id] == name)
+            .map(|id| show(self.builtin_manager.in_types[id].iter().collect()));
+        funcs.chain(structs).chain(variants).chain(builtins).collect()
+    }
     /// convert the given sexpr from SexprKind::Other to something else
     fn realize_other(&mut self, sexpr_id: SexprId) -> Result<(), Error> {
         // during type checking, we also see if we can determine what unknowns are supposed to be
@@ -165,154 +309,504 @@ impl Manager {
             .collect::<Result<Vec<Type>, Error>>()?;
 
         let new_kind = {
-            if let Some(func_id) = self.resolve_func(self.scope_of(sexpr_id), name, &types) {
+            if let Some(callee_type) = self.variable_type(self.scope_of(sexpr_id), name) {
+                // are we calling the function value in a variable?
+                match callee_type {
+                    Type::Func(ref params, _) if Type::check_signature(params, &types) => {}
+                    Type::Func(..) => {
+                        let mut error = Error::new(ErrorCode::SignatureMismatch, format!(
+                            "`{}` is a {}, which can not take `{}`",
+                            name,
+                            callee_type,
+                            types.iter().map(Type::to_string).collect::<Vec<String>>().join(" ")
+                        ), self.sexpr(sexpr_id).token);
+                        for (expr, typ) in exprs.iter().zip(types.iter()) {
+                            error = error.with_label(self.sexpr(*expr).token, format!("this is {}", typ));
+                        }
+                        return Err(error);
+                    }
+                    typ => return Err(Error::new(ErrorCode::NotAFunction, format!("`{}` is {}, not a function, so it can not be called", name, typ), self.sexpr(sexpr_id).token)),
+                }
+                let call_id = self.declare_call_site(None)?;
+                SexprKind::ValueCall { call_id, exprs }
+            } else if let Some(func_id) = self.resolve_func(self.scope_of(sexpr_id), name, &types) {
                 // are we a user defined function?
-                let call_id = self.declare_call_site(func_id)?;
+                let func_id = if self.func_manager.is_generic(func_id) {
+                    self.instantiate(func_id, &types, sexpr_id)?
+                } else {
+                    func_id
+                };
+                let call_id = self.declare_call_site(Some(func_id))?;
                 SexprKind::FuncCall { func_id, call_id, exprs }
             } else if let Some(type_id) = self.resolve_struct_init(self.scope_of(sexpr_id), name, &types) {
                 // are we a user defined type initializer?
                 SexprKind::StructInit { id: type_id, exprs }
+            } else if let Some((enum_id, variant)) = self.resolve_variant(self.scope_of(sexpr_id), name, &types) {
+                // are we a variant of a user defined enum?
+                SexprKind::EnumInit { id: enum_id, variant, exprs }
             } else if let Some(builtin_id) = self.builtin_manager.resolve_name(name, &types) {
                 // are we a builtin?
                 SexprKind::BuiltIn { id: builtin_id, exprs }
             } else {
-                return Err(Error::new(format!(
+                let mut error = Error::new(ErrorCode::SignatureMismatch, format!(
                     "no operation found with name: `{}` and type signature: `{}`",
                     name,
                     types.iter().map(Type::to_string).collect::<Vec<String>>().join(" ")
-                ), self.sexpr(sexpr_id).token))
+                ), self.sexpr(sexpr_id).token);
+                for (expr, typ) in exprs.iter().zip(types.iter()) {
+                    error = error.with_label(self.sexpr(*expr).token, format!("this is {}", typ));
+                }
+                let candidates = self.candidate_signatures(name);
+                if !candidates.is_empty() {
+                    error = error.with_note(format!("`{}` takes: {}", name, candidates.join(", ")));
+                }
+                return Err(error)
             }
         };
         *self.sexpr_mut(sexpr_id).kind = new_kind;
         Ok(())
     }
+    /// the type of the variable with this name, if there is one
+    fn variable_type(&self, scope_id: ScopeId, name: &str) -> Option<Type> {
+        self.resolve_variable(scope_id, name)
+            .map(|(scope_id, var_id)| self.scope(scope_id).variable_types[var_id].clone().expect("typeless variable"))
+    }
+    /// a function's name used as a value, which there has to be only one of
+    fn realize_func_ref(&mut self, sexpr_id: SexprId) -> Result<Type, Error> {
+        let name = self.text(sexpr_id).to_string();
+        let func_id = match self.funcs_named(self.scope_of(sexpr_id), &name)[..] {
+            [func_id] if !self.func_manager.is_generic(func_id) => func_id,
+            [func_id] => return Err(Error::new(ErrorCode::SignatureMismatch, format!("`{}` is generic, so it can only be called, not used as a value", self.func_manager.title(func_id)), self.sexpr(sexpr_id).token)),
+            _ => return Err(Error::new(ErrorCode::SignatureMismatch, format!("there is more than one function named `{}`, so it can't be used as a value", name), self.sexpr(sexpr_id).token)),
+        };
+        *self.sexpr_mut(sexpr_id).kind = SexprKind::FuncRef { func_id };
+        Ok(self.func_manager.func_type(func_id))
+    }
+    /// settle what `list-map` calls on each element and what that gives back:
+    /// something named, or a function value that takes an element
+    fn check_map_func(&mut self, sexpr_id: SexprId, func: SexprId, element_type: Type) -> Result<(MapTarget, Type), Error> {
+        let is_name = matches!(*self.kind(func), SexprKind::Identifier) && self.resolve_variable(self.scope_of(func), self.text(func)).is_none();
+        if is_name {
+            let name = self.text(func).to_string();
+            let (target, out_type) = self.resolve_map_target(sexpr_id, &name, element_type.clone())?;
+            self.sexpr_result_types[func.index] = Type::Func(vec![element_type], Box::new(out_type.clone()));
+            return Ok(match target {
+                // a function that captures variables is mapped through a value holding what it captured
+                MapTarget::Func { func_id, call_id } if !self.func_manager.captures[func_id].is_empty() => {
+                    *self.sexpr_mut(func).kind = SexprKind::FuncRef { func_id };
+                    (MapTarget::Value { call_id }, out_type)
+                }
+                target => (target, out_type),
+            });
+        }
+        match self.type_check(func)? {
+            Type::Func(ref params, ref out) if Type::check_signature(params, ::std::slice::from_ref(&element_type)) => {
+                let call_id = self.declare_call_site(None)?;
+                Ok((MapTarget::Value { call_id }, (**out).clone()))
+            }
+            typ @ Type::Func(..) => Err(Error::new(ErrorCode::SignatureMismatch, format!("`list-map` needs a function that takes a single {}, not a {}", element_type, typ), self.sexpr(func).token)),
+            typ => Err(Error::new(ErrorCode::NotAFunction, format!("`list-map` needs a function, not {}", typ), self.sexpr(func).token)),
+        }
+    }
+    /// make sure that the function's body matches up with the out type
+    fn check_func_body(&mut self, func_id: usize, at: Token) -> Result<(), Error> {
+        let body_type = self.type_check(self.func_manager.body[func_id])?;
+        if Type::is_not(&body_type, self.func_manager.out_type[func_id].unwrap()) {
+            return Err(Error::new(ErrorCode::TypeMismatch, format!("function body returns {} but function declaration states {}", body_type, self.func_manager.out_type[func_id].unwrap()), at));
+        }
+        Ok(())
+    }
+    /// the instance of a generic function for the types it is called with at `call`,
+    /// making it and checking its body the first time those types come up
+    fn instantiate(&mut self, func_id: usize, types: &[Type], call: SexprId) -> Result<usize, Error> {
+        let bindings = Type::bind_generics(&self.func_manager.in_types[func_id], types).expect("instantiating a generic function with types that don't fit");
+        let mut type_args = vec![];
+        for param in self.func_manager.type_params[func_id].iter() {
+            match bindings.get(param) {
+                Some(typ) => type_args.push(typ.clone()),
+                None => return Err(Error::new(ErrorCode::CannotInferType, format!("could not decide what `{}` is for this call to `{}`", param, self.func_manager.name[func_id]), self.sexpr(call).token)
+                    .with_note(format!("`{}` has to be the type of one of the arguments", param))),
+            }
+        }
+        if let Some(&instance) = self.func_manager.instances.get(&(func_id, type_args.clone())) {
+            return Ok(instance);
+        }
+        let body = self.copy_sexpr(self.func_manager.body[func_id], &bindings);
+        let in_types = self.func_manager.in_types[func_id].iter().map(|typ| FutureType::Complete(typ.unwrap().substitute(&bindings))).collect();
+        let out_type = FutureType::Complete(self.func_manager.out_type[func_id].unwrap().substitute(&bindings));
+        let name = self.func_manager.name[func_id].clone();
+        let args = self.func_manager.args[func_id].clone();
+        let instance = self.func_manager.declare_func(name, vec![], args, in_types, out_type, body)?;
+        self.func_manager.type_args[instance] = type_args.clone();
+        // register the instance before checking it, so that it can call itself
+        self.func_manager.instances.insert((func_id, type_args), instance);
+        // an instance sees what the generic function it is made from does
+        let parent = self.scope(self.scope_of(self.func_manager.body[func_id])).parent.expect("a function's scope has a parent");
+        self.scope_func(instance, parent)?;
+        let call_token = self.sexpr(call).token;
+        let title = self.func_manager.title(instance);
+        self.check_func_body(instance, call_token)
+            .map_err(|error| error.with_label(call_token, format!("in `{}`, which is called here", title)))?;
+        Ok(instance)
+    }
+    /// an integer literal is also a real literal, so it takes on real when that is what's expected.
+    /// a list literal fits when each of its elements does, and an empty one takes on whatever list is expected
+    fn fit_literal(&mut self, sexpr_id: SexprId, found: Type, expected: &Type) -> Type {
+        let kind = self.kind(sexpr_id).clone();
+        match (kind, &found, expected) {
+            (SexprKind::IntegerLiteral, &Type::Int, &Type::Real) => {
+                *self.sexpr_mut(sexpr_id).kind = SexprKind::RealLiteral;
+                Type::Real
+            }
+            (SexprKind::List { ref elements }, &Type::List(_), Type::List(expected_element)) => {
+                for &element in elements {
+                    let element_type = self.result_type(element).clone();
+                    if Type::is_not(&self.fit_literal(element, element_type, expected_element), expected_element) {
+                        return found;
+                    }
+                }
+                self.sexpr_result_types[sexpr_id.index] = expected.clone();
+                expected.clone()
+            }
+            _ => found,
+        }
+    }
+    /// the type of a list's elements, for the list form `form` that uses it
+    fn element_type(&mut self, list: SexprId, form: &str) -> Result<Type, Error> {
+        match self.type_check(list)? {
+            Type::List(element_type) => Ok(*element_type),
+            typ => Err(Error::new(ErrorCode::NotAList, format!("`{}` needs a list, not {}", form, typ), self.sexpr(list).token)),
+        }
+    }
+    /// make sure that a list index is an int
+    fn check_index(&mut self, index: SexprId) -> Result<(), Error> {
+        let index_type = self.type_check(index)?;
+        if Type::is_not(&index_type, &Type::Int) {
+            return Err(Error::new(ErrorCode::TypeMismatch, format!("list index must be of type int, not {}", index_type), self.sexpr(index).token));
+        }
+        Ok(())
+    }
+    /// make sure that an element being put into a list is of the list's element type
+    fn check_element(&mut self, list: SexprId, elem: SexprId, element_type: &Type) -> Result<(), Error> {
+        let elem_type = self.type_check(elem)?;
+        let elem_type = self.fit_literal(elem, elem_type, element_type);
+        if Type::is_not(&elem_type, element_type) {
+            return Err(Error::new(ErrorCode::TypeMismatch, format!("a list<{}> can not hold {}", element_type, elem_type), self.sexpr(elem).token)
+                .with_label(self.sexpr(list).token, format!("this is list<{}>", element_type)));
+        }
+        Ok(())
+    }
+    /// settle what `list-map` calls on each element, the same way a call with just that element would be
+    fn resolve_map_target(&mut self, sexpr_id: SexprId, func: &str, element_type: Type) -> Result<(MapTarget, Type), Error> {
+        let types = vec![element_type];
+        let scope_id = self.scope_of(sexpr_id);
+        if let Some(func_id) = self.resolve_func(scope_id, func, &types) {
+            let func_id = if self.func_manager.is_generic(func_id) {
+                self.instantiate(func_id, &types, sexpr_id)?
+            } else {
+                func_id
+            };
+            let call_id = self.declare_call_site(Some(func_id))?;
+            Ok((MapTarget::Func { func_id, call_id }, self.func_manager.out_type[func_id].unwrap_clone()))
+        } else if let Some(id) = self.resolve_struct_init(scope_id, func, &types) {
+            Ok((MapTarget::StructInit { id }, Type::CustomType(self.udt_manager.name[id].to_string(), id)))
+        } else if let Some(id) = self.builtin_manager.resolve_name(func, &types) {
+            Ok((MapTarget::BuiltIn { id }, self.builtin_manager.out_type[id].clone()))
+        } else {
+            let mut error = Error::new(ErrorCode::SignatureMismatch, format!("no operation found with name: `{}` that takes a single {}", func, types[0]), self.sexpr(sexpr_id).token);
+            let candidates = self.candidate_signatures(func);
+            if !candidates.is_empty() {
+                error = error.with_note(format!("`{}` takes: {}", func, candidates.join(", ")));
+            }
+            Err(error)
+        }
+    }
+    /// check a match's arms against the variants of the enum it matches on, every one of which has to be covered
+    fn check_match(&mut self, sexpr_id: SexprId, expr: SexprId, arms: &[MatchArm]) -> Result<Type, Error> {
+        let expr_type = self.type_check(expr)?;
+        let enum_id = match expr_type {
+            Type::CustomType(_, id) if self.udt_manager.is_enum(id) => id,
+            typ => return Err(Error::new(ErrorCode::NotAnEnum, format!("can only match on an enum, not {}", typ), self.sexpr(expr).token)),
+        };
+        let enum_name = self.udt_manager.name[enum_id].clone();
+        let mut covered = vec![false; self.udt_manager.variants[enum_id].len()];
+        let mut arms_type: Option<(Type, Token)> = None;
+        for (index, arm) in arms.iter().enumerate() {
+            if let Some(wildcard) = arms[..index].iter().find(|arm| arm.variant.is_none()) {
+                return Err(Error::new(ErrorCode::DuplicateName, "this arm can never be reached".to_string(), arm.pattern)
+                    .with_label(wildcard.pattern, "this takes every variant that is left".to_string()));
+            }
+            match arm.variant {
+                Some(ref name) => {
+                    let variant = self.udt_manager.variant_index(enum_id, name).ok_or_else(|| {
+                        let names = self.udt_manager.variants[enum_id].iter().map(|variant| format!("`{}`", variant.name)).collect::<Vec<String>>();
+                        Error::new(ErrorCode::UnknownVariant, format!("enum `{}` has no variant named `{}`", enum_name, name), arm.pattern)
+                            .with_note(format!("`{}` is one of: {}", enum_name, names.join(", ")))
+                    })?;
+                    if covered[variant] {
+                        return Err(Error::new(ErrorCode::DuplicateName, format!("`{}` is matched more than once", name), arm.pattern));
+                    }
+                    covered[variant] = true;
+                    let field_types = self.udt_manager.variants[enum_id][variant].sgntr.iter().map(FutureType::unwrap_clone).collect::<Vec<Type>>();
+                    if arm.bindings.len() != field_types.len() {
+                        return Err(Error::new(ErrorCode::WrongArgumentCount, format!("variant `{}` has {} field(s), but the pattern names {}", name, field_types.len(), arm.bindings.len()), arm.pattern));
+                    }
+                    for (binding, typ) in arm.bindings.iter().zip(field_types.iter()) {
+                        self.inform_var_type(self.scope_of(arm.body), binding, typ, arm.pattern)?;
+                    }
+                }
+                None => covered.iter_mut().for_each(|covered| *covered = true),
+            }
+            let body_type = self.type_check(arm.body)?;
+            let body_token = self.sexpr(arm.body).token;
+            match arms_type {
+                Some((ref arms_type, first_token)) if Type::is_not(&body_type, arms_type) => {
+                    return Err(Error::new(ErrorCode::TypeMismatch, format!("match arms must be of the same type. {} is not {}", body_type, arms_type), self.sexpr(sexpr_id).token)
+                        .with_label(first_token, format!("this is {}", arms_type))
+                        .with_label(body_token, format!("this is {}", body_type)));
+                }
+                Some(_) => {}
+                None => arms_type = Some((body_type, body_token)),
+            }
+        }
+        let missing = self.udt_manager.variants[enum_id]
+            .iter()
+            .zip(covered.iter())
+            .filter(|&(_, &covered)| !covered)
+            .map(|(variant, _)| format!("`{}`", variant.name))
+            .collect::<Vec<String>>();
+        if !missing.is_empty() {
+            return Err(Error::new(ErrorCode::NonExhaustiveMatch, format!("match on `{}` does not cover {}", enum_name, missing.join(", ")), self.sexpr(sexpr_id).token)
+                .with_note("add an arm for each of them, or `_` for all of the rest".to_string()));
+        }
+        if let SexprKind::Match { ref mut id, .. } = *self.sexpr_mut(sexpr_id).kind {
+            *id = Some(enum_id);
+        }
+        Ok(arms_type.map_or(Type::Void, |(typ, _)| typ))
+    }
     fn type_check(&mut self, sexpr_id: SexprId) -> Result<Type, Error> {
         // assume that our dependencies have been type checked
         self.realize_other(sexpr_id)?;
+        // clone the kind so that we are free to mutate the manager while checking the children
+        let kind = self.kind(sexpr_id).clone();
         let ret_type: Type =
-            match & *self.sexpr(sexpr_id).kind {
-                SexprKind::Declare {ref variable_pattern, expr, body} => {
-                    let expr_type = self.type_check(*expr)?;
-                    self.inform_var_type(self.scope_of(*body), variable_pattern, &expr_type);
-                    self.type_check(*body)?
+            match kind {
+                SexprKind::Declare {ref variable_pattern, ref annotation, expr, body} => {
+                    let mut expr_type = self.type_check(expr)?;
+                    match *annotation {
+                        Some(ref annotation) => expr_type = self.fit_literal(expr, expr_type, annotation.unwrap()),
+                        None if matches!(*self.kind(expr), SexprKind::List { ref elements } if elements.is_empty()) => {
+                            return Err(Error::new(ErrorCode::CannotInferType, format!("could not infer what `{}` is a list of", variable_pattern), self.sexpr(expr).token)
+                                .with_note(format!("give it a type, like `{}: list<int>`", variable_pattern)));
+                        }
+                        None => {}
+                    }
+                    let expr_token = self.sexpr(expr).token;
+                    self.inform_var_type(self.scope_of(body), variable_pattern, &expr_type, expr_token)?;
+                    self.type_check(body)?
                 },
                 SexprKind::Assign {ref variable_pattern, expr} => {
                     let var_type = self
                         .resolve_variable(self.scope_of(sexpr_id), variable_pattern)
                         .and_then(|(scope_id, v_index)| self.scope(scope_id).variable_types[v_index].clone())
                         .expect("unresolved variable in typecheck");
-                    let expr_type = self.type_check(*expr)?;
+                    let expr_type = self.type_check(expr)?;
+                    let expr_type = self.fit_literal(expr, expr_type, &var_type);
                     if Type::is_not(&var_type, &expr_type) {
-                        return Err(Error::new(format!("assigning an expression of type {} to variable of type {}", expr_type, var_type), self.sexpr(sexpr_id).token));
+                        return Err(Error::new(ErrorCode::TypeMismatch, format!("assigning an expression of type {} to variable of type {}", expr_type, var_type), self.sexpr(sexpr_id).token));
                     }
                     var_type
                 }
                 SexprKind::IfSwitch {predicate, if_branch, else_branch} => {
-                    let predicate_type = self.type_check(*predicate)?;
+                    let predicate_type = self.type_check(predicate)?;
                     if Type::is_not(&predicate_type, &Type::Boole) {
-                        return Err(Error::new(format!("if condition must be of type boole not {}", predicate_type), self.sexpr(sexpr_id).token));
+                        return Err(Error::new(ErrorCode::TypeMismatch, format!("if condition must be of type boole not {}", predicate_type), self.sexpr(sexpr_id).token));
                     }
-                    let if_type= self.type_check(*if_branch)?;
-                    let else_type = self.type_check(*else_branch)?;
+                    let if_type= self.type_check(if_branch)?;
+                    let else_type = self.type_check(else_branch)?;
                     if Type::is_not(&if_type, &else_type) {
-                        return Err(Error::new_many(format!("branches must be of the same type. {} is not {}", if_type, else_type), vec![self.sexpr(*if_branch).token, self.sexpr(*else_branch).token]));
+                        return Err(Error::new(ErrorCode::TypeMismatch, format!("branches must be of the same type. {} is not {}", if_type, else_type), self.sexpr(sexpr_id).token)
+                            .with_label(self.sexpr(if_branch).token, format!("this is {}", if_type))
+                            .with_label(self.sexpr(else_branch).token, format!("this is {}", else_type)));
                     }
                     if_type
                 }
                 SexprKind::WhileLoop {predicate, body} => {
-                    let predicate_type = self.type_check(*predicate)?;
+                    let predicate_type = self.type_check(predicate)?;
                     if Type::is_not(&predicate_type, &Type::Boole) {
-                        return Err(Error::new(format!("while condition must be of type boole, not {}", predicate_type), self.sexpr(*predicate).token));
+                        return Err(Error::new(ErrorCode::TypeMismatch, format!("while condition must be of type boole, not {}", predicate_type), self.sexpr(predicate).token));
                     }
-                    self.type_check(*body)?
+                    self.type_check(body)?
                 }
                 SexprKind::Block {ref statements} => {
                     let mut statement_type = Type::Void;
-                    for i in 0..statements.len() {
-                        statement_type = self.type_check(statements[i])?;
+                    for statement in statements {
+                        statement_type = self.type_check(*statement)?;
                     }
                     statement_type
                 }
                 SexprKind::FuncDef { func_id } => {
-                    // make sure that the function's body matches up with the out type
-                    let body_type = self.type_check(self.func_manager.body[*func_id])?;
-                    if Type::is_not(&body_type, self.func_manager.out_type[*func_id].unwrap()) {
-                        return Err(Error::new(format!("function body returns {} but function declaration states {}", body_type, self.func_manager.out_type[*func_id].unwrap()), self.sexpr(sexpr_id).token));
+                    // generic functions are checked as each of their instances
+                    if !self.func_manager.is_generic(func_id) {
+                        let token = self.sexpr(sexpr_id).token;
+                        self.check_func_body(func_id, token)?;
                     }
                     Type::Void
                 },
                 SexprKind::FuncCall { func_id, call_id: _, exprs: _ } => {
                     // exprs are type checked where we destruct the other kind
-                    self.func_manager.out_type[*func_id].unwrap_clone()
+                    self.func_manager.out_type[func_id].unwrap_clone()
+                }
+                SexprKind::Lambda { func_id } => {
+                    let token = self.sexpr(sexpr_id).token;
+                    self.check_func_body(func_id, token)?;
+                    self.func_manager.func_type(func_id)
+                }
+                SexprKind::FuncRef { func_id } => self.func_manager.func_type(func_id),
+                SexprKind::ValueCall { .. } => {
+                    // exprs are type checked where we destruct the other kind
+                    match self.variable_type(self.scope_of(sexpr_id), self.text(sexpr_id)) {
+                        Some(Type::Func(_, out)) => *out,
+                        typ => panic!("calling a {:?} as a function", typ),
+                    }
                 }
-                SexprKind::StructDef { id: _ } => Type::Void,
+                SexprKind::StructDef { id: _ } | SexprKind::EnumDef { id: _ } => Type::Void,
+                SexprKind::EnumInit { id, .. } => {
+                    // exprs are type checked when we evaluate what SexprKind other should be
+                    Type::CustomType(self.udt_manager.name[id].to_string(), id)
+                }
+                SexprKind::Match { expr, ref arms, .. } => self.check_match(sexpr_id, expr, arms)?,
                 SexprKind::StructInit { id, exprs: _ } => {
                     // exprs are type checked when we evaluate what SexprKind other should be
-                    Type::CustomType(self.udt_manager.name[*id].to_string(),*id)
+                    Type::CustomType(self.udt_manager.name[id].to_string(),id)
                 }
-                SexprKind::StructGet { ref mut id, expr, ref field } => {
-                    let expr_type = self.type_check(*expr)?;
+                SexprKind::StructGet { id: _, expr, ref field } => {
+                    let expr_type = self.type_check(expr)?;
                     if let Type::CustomType(_, struct_id) = &expr_type {
-                        if !self.udt_manager.args[*struct_id].contains(&field) {
-                            return Err(Error::new(format!("struct `{}` has no field with `{}`", expr_type, field), self.sexpr(sexpr_id).token));
+                        if !self.udt_manager.args[*struct_id].contains(field) {
+                            return Err(Error::new(ErrorCode::UnknownField, format!("struct `{}` has no field with `{}`", expr_type, field), self.sexpr(sexpr_id).token));
                         }
                         let offset = self.udt_manager.get_field_offset(*struct_id, field);
                         let field_type = self.udt_manager.sgntr[*struct_id][offset].unwrap_clone();
-                        *id = Some(*struct_id);
+                        if let SexprKind::StructGet { ref mut id, .. } = *self.sexpr_mut(sexpr_id).kind {
+                            *id = Some(*struct_id);
+                        }
                         field_type
                     } else {
-                        return Err(Error::new(format!("type `{}` is not a struct: cannot access field `{}`", expr_type, field), self.sexpr(sexpr_id).token));
+                        return Err(Error::new(ErrorCode::NotAStruct, format!("type `{}` is not a struct: cannot access field `{}`", expr_type, field), self.sexpr(sexpr_id).token));
                     }
                 },
-                SexprKind::StructSet { ref mut id, expr, ref field, value } => {
-                    let expr_type = self.type_check(*expr)?;
+                SexprKind::StructSet { id: _, expr, ref field, value } => {
+                    let expr_type = self.type_check(expr)?;
                     if let Type::CustomType(_, struct_id) = &expr_type {
-                        if !self.udt_manager.args[*struct_id].contains(&field) {
-                            return Err(Error::new(format!("struct `{}` has no field named `{}`", expr_type, field), self.sexpr(sexpr_id).token));
+                        if !self.udt_manager.args[*struct_id].contains(field) {
+                            return Err(Error::new(ErrorCode::UnknownField, format!("struct `{}` has no field named `{}`", expr_type, field), self.sexpr(sexpr_id).token));
                         }
                         let offset =self.udt_manager.get_field_offset(*struct_id, field);
                         let field_type = self.udt_manager.sgntr[*struct_id][offset].unwrap_clone();
-                        *id = Some(*struct_id);
-                        let value_type = self.type_check(*value)?;
+                        if let SexprKind::StructSet { ref mut id, .. } = *self.sexpr_mut(sexpr_id).kind {
+                            *id = Some(*struct_id);
+                        }
+                        let value_type = self.type_check(value)?;
                         if Type::is_not(&value_type, &field_type) {
-                            return Err(Error::new(format!("field `{}` on struct `{}` is of type `{}`, not `{}`", field, expr_type, field_type, value_type), self.sexpr(sexpr_id).token))
+                            return Err(Error::new(ErrorCode::TypeMismatch, format!("field `{}` on struct `{}` is of type `{}`, not `{}`", field, expr_type, field_type, value_type), self.sexpr(sexpr_id).token))
                         }
                         Type::Void
                     } else {
-                        return Err(Error::new(format!("cannot access field from type `{}`", expr_type), self.sexpr(*expr).token));
+                        return Err(Error::new(ErrorCode::NotAStruct, format!("cannot access field from type `{}`", expr_type), self.sexpr(expr).token));
                     }
                 },
-                SexprKind::Format { ref mut exprs } => {
-                    for ref mut expr in exprs {
-                        self.type_check(**expr)?;
+                SexprKind::List { ref elements } => {
+                    let types = elements.iter().map(|&element| self.type_check(element)).collect::<Result<Vec<Type>, Error>>()?;
+                    // the elements take the type of the first one that isn't an integer literal, which could be a real.
+                    // an empty list holds nothing yet, and takes its type from where it is stored
+                    let first = elements.iter().position(|&element| !matches!(*self.kind(element), SexprKind::IntegerLiteral)).unwrap_or(0);
+                    let element_type = types.get(first).cloned().unwrap_or(Type::Void);
+                    for (&element, typ) in elements.iter().zip(types) {
+                        let typ = self.fit_literal(element, typ, &element_type);
+                        if Type::is_not(&typ, &element_type) {
+                            return Err(Error::new(ErrorCode::TypeMismatch, format!("list elements must be of the same type. {} is not {}", typ, element_type), self.sexpr(element).token)
+                                .with_label(self.sexpr(elements[first]).token, format!("this is {}", element_type)));
+                        }
+                    }
+                    Type::List(Box::new(element_type))
+                }
+                SexprKind::ListGet { list, index } => {
+                    let element_type = self.element_type(list, "list-get")?;
+                    self.check_index(index)?;
+                    element_type
+                }
+                SexprKind::ListSet { list, index, elem } => {
+                    let element_type = self.element_type(list, "list-set")?;
+                    self.check_index(index)?;
+                    self.check_element(list, elem, &element_type)?;
+                    Type::Void
+                }
+                SexprKind::ListLen { list } => {
+                    self.element_type(list, "list-len")?;
+                    Type::Int
+                }
+                SexprKind::ListPush { list, elem } => {
+                    let element_type = self.element_type(list, "list-push")?;
+                    self.check_element(list, elem, &element_type)?;
+                    Type::Void
+                }
+                SexprKind::ListMap { func, list, .. } => {
+                    let element_type = self.element_type(list, "list-map")?;
+                    let (map_target, out_type) = self.check_map_func(sexpr_id, func, element_type)?;
+                    if out_type.is_void() {
+                        return Err(Error::new(ErrorCode::TypeMismatch, format!("`list-map` needs `{}` to give back a value, not void", self.text(func)), self.sexpr(sexpr_id).token));
+                    }
+                    if let SexprKind::ListMap { ref mut target, .. } = *self.sexpr_mut(sexpr_id).kind {
+                        *target = Some(map_target);
+                    }
+                    Type::List(Box::new(out_type))
+                }
+                SexprKind::Format { ref exprs } => {
+                    for expr in exprs {
+                        // a list's elements are spread over ⌊OBJ, which format has no way of going through
+                        if let typ @ Type::List(_) = self.type_check(*expr)? {
+                            return Err(Error::new(ErrorCode::TypeMismatch, format!("`format` can not show a {}", typ), self.sexpr(*expr).token)
+                                .with_note("show its elements one at a time with `list-get`".to_string()));
+                        }
                     }
                     Type::String
                 }
                 SexprKind::Other { opt_exprs: _ } => panic!("we are type checking an Other (this should have been switched from an other before we got here)"),
                 SexprKind::BuiltIn { id, exprs: _ } => {
                     // exprs are type checked when we resolve SexprKind::Other
-                    self.builtin_manager.out_type[*id].clone()
+                    self.builtin_manager.out_type[id].clone()
                 }
                 SexprKind::Identifier       => {
-                    self
-                        .resolve_variable(self.scope_of(sexpr_id), self.text(sexpr_id))
-                        .and_then(|(scope_id, v_index)| self.scope(self.scope_of(sexpr_id)).variable_types[v_index].clone())
-                        .expect("typeless variable")
+                    match self.variable_type(self.scope_of(sexpr_id), self.text(sexpr_id)) {
+                        Some(typ) => typ,
+                        None => self.realize_func_ref(sexpr_id)?,
+                    }
                 }
                 SexprKind::RealLiteral      => Type::Real,
                 SexprKind::IntegerLiteral   => Type::Int,
-                SexprKind::StringLiteral    => Type::String,
+                SexprKind::StringLiteral    => {
+                    // the string goes into the program as it is, so the calculator has to have a token for all of it
+                    let text = self.text(sexpr_id);
+                    if let Some(missing) = ti_tokens::untokenizable(&text[1..text.len() - 1]) {
+                        return Err(Error::new(ErrorCode::NoCalculatorToken, format!("the calculator has no token for `{}`, so it can not be in a string", missing), self.sexpr(sexpr_id).token));
+                    }
+                    Type::String
+                }
                 SexprKind::BooleLiteral     => Type::Boole,
         };
-        assert_eq!(self.sexpr_result_types.len(), sexpr_id.index);
-        self.sexpr_result_types.push(ret_type.clone());
+        self.sexpr_result_types[sexpr_id.index] = ret_type.clone();
         Ok(ret_type)
     }
 }
 
-pub fn type_check_all(m: &mut Manager) -> Result<(), Error> {
-     for ref sexpr_id in &m.top_level_sexprs { // iterate over all sexprs
-        m.type_check(**sexpr_id)?;
-    }
-    Ok(())
-}
\ No newline at end of file
+pub fn type_check_all(m: &mut Manager) -> Result<(), Vec<Error>> {
+    m.infer_signatures()?;
+    m.sexpr_result_types = vec![Type::Void; m.all_sexprs.len()];
+    let errors = m.top_level_sexprs
+        .clone()
+        .into_iter()
+        .filter_map(|sexpr_id| m.type_check(sexpr_id).err())
+        .collect::<Vec<Error>>();
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
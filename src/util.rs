@@ -1,42 +1,275 @@
 use manager::Manager;
 use std::hash::Hash;
-use std::collections::HashSet;
+use std::collections::{HashSet, BTreeMap};
 use tokenizer::Token;
 use std::str::Chars;
+use std::fmt;
 
-pub enum Error {
-    Zero{why: String},
-    Single{why: String, at: Token},
-    Many{why: String, ats: Vec<Token>},
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// the kind of failure, so that every diagnostic carries a stable code
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ErrorCode {
+    UnclosedSexpr,
+    UnpairedParen,
+    UnrecognizedAtom,
+    IllegalHead,
+    WrongArgumentCount,
+    MalformedDefinition,
+    NotAnIdentifier,
+    DuplicateName,
+    UnknownType,
+    UndeclaredVariable,
+    SignatureMismatch,
+    TypeMismatch,
+    UnknownField,
+    NotAStruct,
+    UnterminatedString,
+    UnterminatedComment,
+    UnrepresentableNumber,
+    CannotInferType,
+    NotAList,
+    NotAnEnum,
+    UnknownVariant,
+    NonExhaustiveMatch,
+    NotAFunction,
+    AssignToCapture,
+    ShadowedVariable,
+    TooManyLabels,
+    NoCalculatorToken,
+    BadProgramName,
+    ProgramTooLarge,
+    Malformed8xp,
+    UnreadableFile,
+    RunTime,
+}
+impl ErrorCode {
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ErrorCode::UnclosedSexpr => "E0001",
+            ErrorCode::UnpairedParen => "E0002",
+            ErrorCode::UnrecognizedAtom => "E0003",
+            ErrorCode::IllegalHead => "E0004",
+            ErrorCode::WrongArgumentCount => "E0005",
+            ErrorCode::MalformedDefinition => "E0006",
+            ErrorCode::NotAnIdentifier => "E0007",
+            ErrorCode::DuplicateName => "E0008",
+            ErrorCode::UnknownType => "E0009",
+            ErrorCode::UndeclaredVariable => "E0010",
+            ErrorCode::SignatureMismatch => "E0011",
+            ErrorCode::TypeMismatch => "E0012",
+            ErrorCode::UnknownField => "E0013",
+            ErrorCode::NotAStruct => "E0014",
+            ErrorCode::UnterminatedString => "E0015",
+            ErrorCode::UnterminatedComment => "E0016",
+            ErrorCode::UnrepresentableNumber => "E0017",
+            ErrorCode::CannotInferType => "E0018",
+            ErrorCode::NotAList => "E0019",
+            ErrorCode::NotAnEnum => "E0020",
+            ErrorCode::UnknownVariant => "E0021",
+            ErrorCode::NonExhaustiveMatch => "E0022",
+            ErrorCode::NotAFunction => "E0023",
+            ErrorCode::AssignToCapture => "E0024",
+            ErrorCode::ShadowedVariable => "W0001",
+            ErrorCode::TooManyLabels => "E0100",
+            ErrorCode::NoCalculatorToken => "E0101",
+            ErrorCode::BadProgramName => "E0102",
+            ErrorCode::ProgramTooLarge => "E0103",
+            ErrorCode::Malformed8xp => "E0104",
+            ErrorCode::UnreadableFile => "E0105",
+            ErrorCode::RunTime => "E0200",
+        }
+    }
+}
+
+/// the part of the compiler that an error came out of
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Stage {
+    Lexing,
+    Parsing,
+    TypeInfo,
+    Scoping,
+    TypeChecking,
+    Mir,
+    CodeGen,
+    Output,
+}
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Stage::Lexing => "lexing",
+            Stage::Parsing => "parsing",
+            Stage::TypeInfo => "type info",
+            Stage::Scoping => "scoping",
+            Stage::TypeChecking => "type checking",
+            Stage::Mir => "MIR",
+            Stage::CodeGen => "code generation",
+            Stage::Output => "output",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub severity: Severity,
+    pub code: ErrorCode,
+    pub why: String,
+    /// the tokens at fault, underlined with ^
+    pub ats: Vec<Token>,
+    /// tokens that help explain the failure, underlined with - and followed by their message
+    pub labels: Vec<(Token, String)>,
+    pub notes: Vec<String>,
+    pub stage: Option<Stage>,
 }
 impl Error {
     ///produce an error with a singular point of failure
-    pub fn new(why: String, at: Token) -> Error {
-        Error::Single{why, at}
+    pub fn new(code: ErrorCode, why: String, at: Token) -> Error {
+        Error::new_many(code, why, vec![at])
     }
     ///produce an error with zero points of failure
-    pub fn new_zero(why: String) -> Error { Error::Zero{why} }
+    pub fn new_zero(code: ErrorCode, why: String) -> Error {
+        Error::new_many(code, why, vec![])
+    }
     ///produce an error with many points of failure
-    pub fn new_many(why: String, ats: Vec<Token>) -> Error {
-        Error::Many{why, ats}
+    pub fn new_many(code: ErrorCode, why: String, ats: Vec<Token>) -> Error {
+        Error { severity: Severity::Error, code, why, ats, labels: vec![], notes: vec![], stage: None }
+    }
+    ///produce a warning with a singular point of interest
+    pub fn warning(code: ErrorCode, why: String, at: Token) -> Error {
+        Error { severity: Severity::Warning, ..Error::new(code, why, at) }
+    }
+    ///produce a note, which points at something that explains the diagnostic before it
+    pub fn note(code: ErrorCode, why: String, at: Token) -> Error {
+        Error { severity: Severity::Note, ..Error::new(code, why, at) }
+    }
+    ///point out another token that explains the failure
+    pub fn with_label(mut self, at: Token, message: String) -> Error {
+        self.labels.push((at, message));
+        self
+    }
+    ///add a note to the bottom of the readout
+    pub fn with_note(mut self, note: String) -> Error {
+        self.notes.push(note);
+        self
+    }
+    ///record which stage of the compiler the error came out of
+    pub fn in_stage(mut self, stage: Stage) -> Error {
+        self.stage = Some(stage);
+        self
+    }
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+    /// where the failure is, as file:line:col
+    pub fn location(&self, m: &Manager) -> Option<String> {
+        self.ats.first().or(self.labels.first().map(|label| &label.0)).map(|at| {
+            let (line, col) = at.position(&m.source);
+            format!("{}:{}:{}", m.file_name, line, col)
+        })
+    }
+    /// the error as a single line JSON object, for tools that read the compiler's output
+    pub fn get_json(&self, m: &Manager) -> String {
+        let span = |at: &Token, primary: bool, label: Option<&str>| {
+            let (line, col) = at.position(&m.source);
+            format!(
+                "{{\"begin\":{},\"len\":{},\"line\":{},\"column\":{},\"primary\":{},\"label\":{}}}",
+                at.begin(), at.len(), line, col, primary, label.map_or("null".to_string(), json_string))
+        };
+        let spans = self.ats.iter().map(|at| span(at, true, None))
+            .chain(self.labels.iter().map(|(at, message)| span(at, false, Some(message))))
+            .collect::<Vec<String>>();
+        let notes = self.notes.iter().map(|note| json_string(note)).collect::<Vec<String>>();
+        format!(
+            "{{\"severity\":\"{}\",\"code\":\"{}\",\"stage\":{},\"message\":{},\"file\":{},\"spans\":[{}],\"notes\":[{}]}}",
+            self.severity,
+            self.code.code(),
+            self.stage.map_or("null".to_string(), |stage| json_string(stage.name())),
+            json_string(&self.why),
+            json_string(&m.file_name),
+            spans.join(","),
+            notes.join(","))
     }
     pub fn get_readout(&self, m: &Manager) -> String {
-        match *self {
-            Error::Zero { ref why } => why.clone(),
-            Error::Single { ref why, ref at } => format!("{}\n{}", at.get_underlined(&m.source), why),
-            Error::Many { ref why, ref ats } => {
-                //TODO smart underlining: check to see if it all fits on one line
-                let mut readout = String::new();
-                for ref tok in ats {
-                    readout.push_str(&tok.get_underlined(&m.source));
-                    readout.push('\n');
+        let mut readout = format!("{}[{}]: {}", self.severity, self.code.code(), self.why);
+
+        // every span we underline, grouped by the line it's on: (column, length, marker, message)
+        let mut lines: BTreeMap<usize, Vec<(usize, usize, char, &str)>> = BTreeMap::new();
+        let spans = self.ats.iter().map(|at| (at, '^', ""))
+            .chain(self.labels.iter().map(|(at, message)| (at, '-', message.as_str())));
+        for (at, marker, message) in spans {
+            let (line, col) = at.position(&m.source);
+            let len = at.get_text(&m.source).chars().take_while(|&ch| ch != '\n').count().max(1);
+            lines.entry(line).or_default().push((col, len, marker, message));
+        }
+        let gutter = " ".repeat(lines.keys().last().map_or(0, |line| line.to_string().len()));
+
+        if let Some(location) = self.location(m) {
+            readout.push_str(&format!("\n{}--> {}", gutter, location));
+            readout.push_str(&format!("\n{} |", gutter));
+        }
+        for (line, mut spans) in lines {
+            let text = m.source.lines().nth(line - 1).unwrap_or("");
+            readout.push_str(&format!("\n{:>width$} | {}", line, text, width = gutter.len()));
+            // all of the line's spans share one underline
+            spans.sort_by_key(|span| span.0);
+            let mut underline = vec![];
+            for &(col, len, marker, _) in spans.iter() {
+                if underline.len() < col - 1 + len {
+                    underline.resize(col - 1 + len, ' ');
                 }
-                readout.push_str(why);
-                readout
-            },
+                for ch in underline.iter_mut().skip(col - 1).take(len) {
+                    *ch = marker;
+                }
+            }
+            let mut messages = spans.iter().filter(|span| !span.3.is_empty()).collect::<Vec<_>>();
+            let last = messages.pop();
+            readout.push_str(&format!("\n{} | {}", gutter, underline.into_iter().collect::<String>()));
+            if let Some(&&(_, _, _, message)) = last.as_ref() {
+                readout.push(' ');
+                readout.push_str(message);
+            }
+            // the other messages each get their own line, starting under their span
+            for &&(col, _, _, message) in messages.iter().rev() {
+                readout.push_str(&format!("\n{} | {}{}", gutter, " ".repeat(col - 1), message));
+            }
+        }
+        for note in self.notes.iter() {
+            readout.push_str(&format!("\n{} = note: {}", gutter, note));
         }
+        readout
+    }
+}
 
+/// quote and escape text as a JSON string
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            ch if (ch as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
     }
+    quoted.push('"');
+    quoted
 }
 
 pub fn has_unique_elements<T>(iter: T) -> bool
@@ -55,7 +288,7 @@ pub fn has_unique_elements<T>(iter: T) -> bool
 /// let arguments = vec![String::from("world"), String::from("turn"), String::from("I")];
 /// assert_eq!(vec_fmt(format_string, &arguments), String::from("I look at the world and I notice it's turning"));
 /// ```
-pub fn vec_fmt(format_string: &str, args: &Vec<String>) -> String  {
+pub fn vec_fmt(format_string: &str, args: &[String]) -> String  {
     let mut string = String::new();
     let mut char_iter = format_string.chars();
     while let Some(ch) = char_iter.next() {
@@ -63,7 +296,7 @@ pub fn vec_fmt(format_string: &str, args: &Vec<String>) -> String  {
             let digit = consume_until_brace(&mut char_iter).parse::<usize>().unwrap();
             string.push_str(&args[digit]);
         } else if ch == '}' {
-            panic!("dangling `}`. Did you mean to escape it, like so: `\\}` ?")
+            panic!("{}", "dangling `}`. Did you mean to escape it, like so: `\\}` ?")
         } else if ch == '\\' {
             string.push(char_iter.next().unwrap());
         } else {
@@ -75,11 +308,11 @@ pub fn vec_fmt(format_string: &str, args: &Vec<String>) -> String  {
 
 fn consume_until_brace(char_iter: &mut Chars) -> String {
     let mut string = String::new();
-    while let Some(ch) = char_iter.next() {
+    for ch in char_iter.by_ref() {
         if ch == '}' {
             return string;
         }
         string.push(ch);
     }
-    panic!("encountered end of string while scanning for closing `}` (did you forget to use escape the `{` like so: `\\{` ?)")
+    panic!("{}", "encountered end of string while scanning for closing `}` (did you forget to use escape the `{` like so: `\\{` ?)")
 }
\ No newline at end of file
@@ -0,0 +1,273 @@
+//! tests of the command line itself: what goes to stdout, what goes to stderr, and what the diagnostics say
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// just enough of JSON to check what the compiler prints
+#[derive(Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> &Json {
+        match *self {
+            Json::Object(ref fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value).unwrap_or(&Json::Null),
+            _ => &Json::Null,
+        }
+    }
+    fn num(&self) -> usize {
+        match *self {
+            Json::Num(value) => value as usize,
+            ref other => panic!("expected a number, found {:?}", other),
+        }
+    }
+    fn str(&self) -> &str {
+        match *self {
+            Json::Str(ref text) => text,
+            ref other => panic!("expected a string, found {:?}", other),
+        }
+    }
+    fn array(&self) -> &[Json] {
+        match *self {
+            Json::Array(ref items) => items,
+            ref other => panic!("expected an array, found {:?}", other),
+        }
+    }
+}
+
+/// read one JSON value from the front of the characters, or say why it isn't one
+fn parse_value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Json, String> {
+    let skip_space = |chars: &mut std::iter::Peekable<std::str::Chars>| while chars.peek().is_some_and(|ch| ch.is_whitespace()) {
+        chars.next();
+    };
+    skip_space(chars);
+    let value = match chars.next() {
+        Some('n') => expect_word(chars, "ull").map(|_| Json::Null)?,
+        Some('t') => expect_word(chars, "rue").map(|_| Json::Bool(true))?,
+        Some('f') => expect_word(chars, "alse").map(|_| Json::Bool(false))?,
+        Some('"') => Json::Str(parse_string(chars)?),
+        Some('[') => {
+            let mut items = vec![];
+            skip_space(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+            } else {
+                loop {
+                    items.push(parse_value(chars)?);
+                    match chars.next() {
+                        Some(',') => {}
+                        Some(']') => break,
+                        other => return Err(format!("expected `,` or `]` in an array, found {:?}", other)),
+                    }
+                }
+            }
+            Json::Array(items)
+        }
+        Some('{') => {
+            let mut fields = vec![];
+            skip_space(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+            } else {
+                loop {
+                    skip_space(chars);
+                    if chars.next() != Some('"') {
+                        return Err("expected a key in an object".to_string());
+                    }
+                    let key = parse_string(chars)?;
+                    skip_space(chars);
+                    if chars.next() != Some(':') {
+                        return Err(format!("expected `:` after the key {:?}", key));
+                    }
+                    fields.push((key, parse_value(chars)?));
+                    match chars.next() {
+                        Some(',') => {}
+                        Some('}') => break,
+                        other => return Err(format!("expected `,` or `}}` in an object, found {:?}", other)),
+                    }
+                }
+            }
+            Json::Object(fields)
+        }
+        Some(ch) if ch == '-' || ch.is_ascii_digit() => {
+            let mut text = ch.to_string();
+            while let Some(&ch) = chars.peek().filter(|ch| ch.is_ascii_digit() || "+-.eE".contains(**ch)) {
+                text.push(ch);
+                chars.next();
+            }
+            Json::Num(text.parse().map_err(|_| format!("`{}` is not a number", text))?)
+        }
+        other => return Err(format!("expected a value, found {:?}", other)),
+    };
+    skip_space(chars);
+    Ok(value)
+}
+
+fn expect_word(chars: &mut std::iter::Peekable<std::str::Chars>, rest: &str) -> Result<(), String> {
+    if rest.chars().all(|expected| chars.next() == Some(expected)) { Ok(()) } else { Err("misspelled literal".to_string()) }
+}
+
+/// the rest of a string whose opening quote was already read
+fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(text),
+            Some('\\') => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('r') => text.push('\r'),
+                Some('u') => {
+                    let hex = chars.by_ref().take(4).collect::<String>();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("bad escape \\u{}", hex))?;
+                    text.push(char::from_u32(code).ok_or(format!("bad escape \\u{}", hex))?);
+                }
+                Some(ch) if "\"\\/".contains(ch) => text.push(ch),
+                other => return Err(format!("bad escape {:?}", other)),
+            },
+            Some(ch) if (ch as u32) < 0x20 => return Err("control character in a string".to_string()),
+            Some(ch) => text.push(ch),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+/// the line as one JSON value, with nothing after it
+fn parse_json(line: &str) -> Result<Json, String> {
+    let mut chars = line.chars().peekable();
+    let value = parse_value(&mut chars)?;
+    if chars.next().is_some() { Err("text after the value".to_string()) } else { Ok(value) }
+}
+
+/// run the compiler on this source, giving back its exit code, stdout and stderr
+fn run_compiler(name: &str, source: &str, args: &[&str]) -> (i32, String, String) {
+    let path: PathBuf = env::temp_dir().join(format!("basic_transpilation_cli_{}_{}.txt", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_BasicTranspilation"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .expect("couldn't run the compiler");
+    fs::remove_file(&path).unwrap();
+    (
+        output.status.code().unwrap_or(-1),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// every line of stderr as a diagnostic, checking that each span points where its line and column say
+fn diagnostics(source: &str, stderr: &str) -> Vec<Json> {
+    stderr
+        .lines()
+        .map(|line| {
+            let diagnostic = parse_json(line).unwrap_or_else(|why| panic!("not a JSON object ({}): {}", why, line));
+            for span in diagnostic.get("spans").array() {
+                let (begin, len) = (span.get("begin").num(), span.get("len").num());
+                assert!(begin + len <= source.len(), "span runs past the source: {}", line);
+                let before = &source[..begin];
+                let line_number = before.matches('\n').count() + 1;
+                let column = before[before.rfind('\n').map_or(0, |newline| newline + 1)..].chars().count() + 1;
+                assert_eq!((span.get("line").num(), span.get("column").num()), (line_number, column), "span is not where it says: {}", line);
+            }
+            diagnostic
+        })
+        .collect()
+}
+
+#[test]
+fn human_diagnostics_stay_off_stdout() {
+    let (code, stdout, stderr) = run_compiler("human", "(declare x 1 (declare x 2 (print x)))\n", &["build"]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "1→A\n2→B\nDisp B\n");
+    assert!(stderr.contains("warning[W0001]"), "missing the warning: {}", stderr);
+}
+
+#[test]
+fn json_diagnostics_are_one_object_per_line_on_stderr() {
+    let source = "(declare x 1 (declare x 2 (print x)))\n";
+    let (code, stdout, stderr) = run_compiler("json", source, &["build", "--error-format=json"]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "1→A\n2→B\nDisp B\n");
+    let diagnostics = diagnostics(source, &stderr);
+    assert_eq!(diagnostics.len(), 2, "{}", stderr);
+    let warning = &diagnostics[0];
+    assert_eq!(warning.get("severity").str(), "warning");
+    assert_eq!(warning.get("code").str(), "W0001");
+    let span = &warning.get("spans").array()[0];
+    assert_eq!((span.get("begin").num(), span.get("len").num()), (14, 7));
+    assert_eq!((span.get("line").num(), span.get("column").num()), (1, 15));
+    // followed by a note at the declaration it shadows
+    let note = &diagnostics[1];
+    assert_eq!(note.get("severity").str(), "note");
+    let span = &note.get("spans").array()[0];
+    assert_eq!((span.get("begin").num(), span.get("len").num()), (1, 7));
+    assert_eq!((span.get("line").num(), span.get("column").num()), (1, 2));
+}
+
+#[test]
+fn json_spans_count_columns_in_characters() {
+    // the string before the bad name is several bytes per character
+    let source = "(print \"θθθ\")\n(print nope)\n";
+    let (code, stdout, stderr) = run_compiler("json_utf8", source, &["check", "--error-format=json"]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "");
+    let diagnostics = diagnostics(source, &stderr);
+    assert_eq!(diagnostics.len(), 1, "{}", stderr);
+    let span = &diagnostics[0].get("spans").array()[0];
+    assert_eq!((span.get("line").num(), span.get("column").num()), (2, 8));
+    assert_eq!(&source[span.get("begin").num()..][..span.get("len").num()], "nope");
+}
+
+#[test]
+fn dump_mir_prints_the_mir_once_even_when_asked_to_dump_it() {
+    let (code, stdout, _) = run_compiler("dump_mir", "(print 1)\n", &["dump-mir", "--dump=mir"]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.matches("main (").count(), 1, "{}", stdout);
+}
+
+/// every program in tests/errors has to fail with exactly the diagnostics its `; expect CODE at LINE:COLUMN ...` comments list, in order.
+/// the first position is the span at fault, and any after it are the spans labelled to explain it
+#[test]
+fn error_fixtures_report_what_they_expect() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/errors");
+    let mut paths = fs::read_dir(&dir)
+        .expect("missing the error fixture directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    assert!(!paths.is_empty(), "no error fixtures found in {}", dir.display());
+
+    let mut failures = vec![];
+    for path in paths {
+        let source = fs::read_to_string(&path).unwrap();
+        let expected = source
+            .lines()
+            .filter_map(|line| line.strip_prefix("; expect "))
+            .map(|expectation| expectation.replace(" at ", " "))
+            .collect::<Vec<String>>();
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let (code, _, stderr) = run_compiler(name, &source, &["check", "--error-format=json"]);
+        let found = diagnostics(&source, &stderr)
+            .iter()
+            .map(|diagnostic| {
+                let positions = diagnostic.get("spans").array().iter().map(|span| format!("{}:{}", span.get("line").num(), span.get("column").num()));
+                ::std::iter::once(diagnostic.get("code").str().to_string()).chain(positions).collect::<Vec<String>>().join(" ")
+            })
+            .collect::<Vec<String>>();
+        let fails = expected.iter().any(|expectation| expectation.starts_with('E'));
+        if found != expected || (code == 1) != fails {
+            failures.push(format!("{}: expected {:?} but found {:?} (exit code {})", path.display(), expected, found, code));
+        }
+    }
+    assert!(failures.is_empty(), "{} error fixture(s) failed\n\n{}", failures.len(), failures.join("\n"));
+}